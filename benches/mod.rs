@@ -2,8 +2,9 @@
 extern crate test;
 extern crate velodyne;
 
-use velodyne::{TurnIterator, FullPoint};
-use velodyne::packet::PcapSource;
+use velodyne::{TurnIterator, FullPoint, Convertor};
+use velodyne::hdl32::Hdl32Convertor;
+use velodyne::packet::{PcapSource, PacketSource};
 
 use test::Bencher;
 
@@ -32,3 +33,38 @@ fn bench_hdl32_turn(b: &mut Bencher) {
         test::black_box(point);
     });
 }
+
+fn bench_hdl32_convertor(b: &mut Bencher, convertor: Hdl32Convertor) {
+    let path = "data/hdl32.pcap";
+    let mut source = PcapSource::new(path, false, true).unwrap();
+    let packet = *source.next_packet().unwrap().unwrap().1;
+
+    b.iter(|| {
+        let mut n = 0;
+        convertor.convert(&packet, |p: FullPoint| n += p.laser_id as usize)
+            .unwrap();
+        test::black_box(n);
+    });
+}
+
+#[bench]
+fn bench_hdl32_packet(b: &mut Bencher) {
+    bench_hdl32_convertor(b, Hdl32Convertor::default());
+}
+
+#[bench]
+fn bench_hdl32_packet_scalar(b: &mut Bencher) {
+    #[allow(unused_mut)]
+    let mut convertor = Hdl32Convertor::default();
+    #[cfg(feature = "simd")]
+    convertor.set_simd(false);
+    bench_hdl32_convertor(b, convertor);
+}
+
+#[cfg(feature = "simd")]
+#[bench]
+fn bench_hdl32_packet_simd(b: &mut Bencher) {
+    let mut convertor = Hdl32Convertor::default();
+    convertor.set_simd(true);
+    bench_hdl32_convertor(b, convertor);
+}
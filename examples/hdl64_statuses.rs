@@ -83,24 +83,14 @@ fn print_fields(status: &Status, fields: &str) {
     println!();
 }
 
-fn run<T: PacketSource>(packet_source: T, fields: &str) -> io::Result<()> {
+fn run<T: PacketSource>(packet_source: T, fields: String) -> io::Result<()> {
     print!("Listener initialization... ");
     let mut point_source = PointSource::hdl64_init(packet_source)?;
     println!("Done.");
 
-    let mut prev_dt = point_source.get_status().dt;
-    loop {
-        let (_, _meta) = match point_source.process_points(|_: FullPoint| {})? {
-            Some(meta) => meta,
-            None => break,
-        };
-
-        let status = point_source.get_status();
-        if status.dt != prev_dt {
-            print_fields(status, fields);
-            prev_dt = status.dt;
-        }
-    }
+    print_fields(point_source.get_status(), &fields);
+    point_source.on_status_change(move |status| print_fields(status, &fields));
+    while point_source.process_points(|_: FullPoint| {})?.is_some() {}
 
     //println!("{:?}", point_source.get_calib_db());
     println!("Listener does not provide packets anymore.");
@@ -113,15 +103,15 @@ fn main() -> io::Result<()> {
         Cli::Pcap{ path, loop_play, sync, fields } => {
             let source = packet::PcapSource::new(path, sync, loop_play)
                 .expect("Failed to initialize pcap source");
-            run(source, &fields)
+            run(source, fields)
         },
         Cli::Udp{ port, timeout, fields } => {
             let ip_addr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
             let addr = SocketAddr::new(ip_addr, port);
-            let timeout = timeout.map(|t| Duration::from_secs(t));
+            let timeout = timeout.map(Duration::from_secs);
             let source = packet::UdpSource::new_custom(addr, timeout)
                 .expect("Failed to initialize pcap source");
-            run(source, &fields)
+            run(source, fields)
         },
     }
 }
@@ -0,0 +1,141 @@
+use std::mem;
+use std::collections::VecDeque;
+
+use crate::FullPoint;
+
+/// Rigid (or any affine) transform stored as the first three rows of a 4x4
+/// homogeneous matrix, i.e. `[R | t]` with row-major rotation `R`
+pub type Transform = [[f32; 4]; 3];
+
+/// Identity `Transform`
+pub const IDENTITY_TRANSFORM: Transform = [
+    [1., 0., 0., 0.],
+    [0., 1., 0., 0.],
+    [0., 0., 1., 0.],
+];
+
+/// Accumulator of consecutive turns into a single dense point cloud
+///
+/// Keeps points of up to `max_turns` last turns in one buffer, pushing a turn
+/// into a full accumulator evicts points of the oldest turn. Turns can be
+/// transformed into a common frame while being pushed, e.g. using odometry
+/// of a moving platform.
+///
+/// # Example
+/// ```
+/// use velodyne::{FullPoint, TurnAccumulator, IDENTITY_TRANSFORM};
+///
+/// let turn = |x: f32, n: usize| -> Vec<FullPoint> {
+///     (0..n).map(|i| FullPoint {
+///         xyz: [x, i as f32, 0.], ..Default::default()
+///     }).collect()
+/// };
+///
+/// let mut acc = TurnAccumulator::new(2);
+/// acc.push_turn_transformed(&turn(1., 10), &IDENTITY_TRANSFORM);
+/// acc.push_turn(&turn(2., 20));
+/// assert_eq!(acc.turns(), 2);
+/// assert_eq!(acc.points().len(), 30);
+/// assert_eq!(acc.points()[5].xyz, [1., 5., 0.]);
+///
+/// // the oldest turn is evicted
+/// acc.push_turn(&turn(3., 5));
+/// assert_eq!(acc.points().len(), 25);
+/// assert_eq!(acc.points()[0].xyz, [2., 0., 0.]);
+///
+/// // platform has moved by 10 m along the X axis
+/// let mut shift = IDENTITY_TRANSFORM;
+/// shift[0][3] = 10.;
+/// acc.push_turn_transformed(&turn(0., 1), &shift);
+/// let points = acc.take();
+/// assert_eq!(points.len(), 6);
+/// assert_eq!(points[5].xyz, [10., 0., 0.]);
+/// assert_eq!(acc.turns(), 0);
+/// assert!(acc.points().is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TurnAccumulator {
+    points: Vec<FullPoint>,
+    // number of points in each accumulated turn
+    turn_lens: VecDeque<usize>,
+    max_turns: usize,
+}
+
+impl TurnAccumulator {
+    /// Create new accumulator which keeps up to `max_turns` last turns
+    pub fn new(max_turns: usize) -> Self {
+        Self {
+            points: vec![], turn_lens: VecDeque::with_capacity(max_turns),
+            max_turns,
+        }
+    }
+
+    /// Append points of the next turn as is
+    pub fn push_turn(&mut self, turn: &[FullPoint]) {
+        if !self.make_room() { return; }
+        self.points.extend_from_slice(turn);
+        self.turn_lens.push_back(turn.len());
+    }
+
+    /// Append points of the next turn transformed by `transform`
+    ///
+    /// Only `xyz` coordinates are modified, NaN coordinates of points without
+    /// return stay NaN.
+    pub fn push_turn_transformed(
+        &mut self, turn: &[FullPoint], transform: &Transform,
+    ) {
+        if !self.make_room() { return; }
+        self.points.extend(turn.iter().map(|p| {
+            let [x, y, z] = p.xyz;
+            let mut xyz = [0f32; 3];
+            for (v, row) in xyz.iter_mut().zip(transform.iter()) {
+                *v = row[0]*x + row[1]*y + row[2]*z + row[3];
+            }
+            FullPoint { xyz, ..*p }
+        }));
+        self.turn_lens.push_back(turn.len());
+    }
+
+    /// Evict the oldest turn if accumulator is full, returns `false` if turns
+    /// can not be kept at all
+    fn make_room(&mut self) -> bool {
+        if self.max_turns == 0 { return false; }
+        if self.turn_lens.len() == self.max_turns {
+            let n = self.turn_lens.pop_front().unwrap_or(0);
+            self.points.drain(..n);
+        }
+        true
+    }
+
+    /// Get accumulated points ordered from the oldest turn to the latest one
+    pub fn points(&self) -> &[FullPoint] {
+        &self.points
+    }
+
+    /// Get number of accumulated turns
+    pub fn turns(&self) -> usize {
+        self.turn_lens.len()
+    }
+
+    /// Get maximum number of accumulated turns
+    pub fn max_turns(&self) -> usize {
+        self.max_turns
+    }
+
+    /// Returns `true` if the next pushed turn will evict the oldest one
+    pub fn is_full(&self) -> bool {
+        self.turn_lens.len() == self.max_turns
+    }
+
+    /// Take accumulated points leaving accumulator empty
+    pub fn take(&mut self) -> Vec<FullPoint> {
+        self.turn_lens.clear();
+        mem::take(&mut self.points)
+    }
+
+    /// Remove all accumulated points keeping allocated buffer
+    pub fn clear(&mut self) {
+        self.turn_lens.clear();
+        self.points.clear();
+    }
+}
@@ -0,0 +1,322 @@
+//! Helper functions for analysis of converted point clouds
+//!
+//! # Example
+//! ```
+//! use velodyne::FullPoint;
+//! use velodyne::analysis::{bounds, centroid};
+//!
+//! let points = [
+//!     FullPoint { xyz: [0., 0., 0.], ..Default::default() },
+//!     FullPoint { xyz: [2., -4., 1.], ..Default::default() },
+//! ];
+//! assert_eq!(bounds(&points), Some(([0., -4., 0.], [2., 0., 1.])));
+//! assert_eq!(centroid(&points), Some([1., -2., 0.5]));
+//! assert_eq!(centroid(&[]), None);
+//! ```
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use crate::FullPoint;
+use crate::math::round;
+#[cfg(feature = "std")]
+use crate::{azimuth_to_column, column_to_azimuth};
+
+/// Iterator over point coordinates, skipping points with NaN coordinates
+fn valid_xyz(points: &[FullPoint]) -> impl Iterator<Item=[f32; 3]> + '_ {
+    points.iter()
+        .map(|p| p.xyz)
+        .filter(|xyz| !xyz.iter().any(|v| v.is_nan()))
+}
+
+/// Compute axis-aligned bounding box of the points
+///
+/// Returns `(min, max)` corners or `None` if there is no points with valid
+/// (non-NaN) coordinates.
+pub fn bounds(points: &[FullPoint]) -> Option<([f32; 3], [f32; 3])> {
+    let mut iter = valid_xyz(points);
+    let first = iter.next()?;
+    let res = iter.fold((first, first), |(mut min, mut max), xyz| {
+        for i in 0..3 {
+            min[i] = min[i].min(xyz[i]);
+            max[i] = max[i].max(xyz[i]);
+        }
+        (min, max)
+    });
+    Some(res)
+}
+
+/// Compute centroid (mean position) of the points
+///
+/// Points with NaN coordinates are ignored. Returns `None` if there is no
+/// valid points.
+pub fn centroid(points: &[FullPoint]) -> Option<[f32; 3]> {
+    // accumulate in f64 to reduce error for large clouds
+    let mut sum = [0f64; 3];
+    let mut n = 0usize;
+    for xyz in valid_xyz(points) {
+        for i in 0..3 { sum[i] += xyz[i] as f64; }
+        n += 1;
+    }
+    if n == 0 { return None; }
+    let n = n as f64;
+    Some([(sum[0]/n) as f32, (sum[1]/n) as f32, (sum[2]/n) as f32])
+}
+
+/// Split points into `laser_count` clouds, `i`-th cloud contains points
+/// measured by the laser `i` in the original order
+///
+/// Requires `std` feature.
+///
+/// Points with `laser_id` greater or equal to `laser_count` are ignored.
+/// `Convertor::laser_count` can be used to get number of lasers.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::analysis::partition_by_laser;
+///
+/// let point = |laser_id, x| FullPoint {
+///     xyz: [x, 0., 0.], laser_id, ..Default::default()
+/// };
+/// let points = [point(1, 0.), point(0, 1.), point(1, 2.), point(5, 3.)];
+/// let clouds = partition_by_laser(&points, 3);
+/// assert_eq!(clouds.len(), 3);
+/// for (laser_id, cloud) in clouds.iter().enumerate() {
+///     assert!(cloud.iter().all(|p| p.laser_id as usize == laser_id));
+/// }
+/// let x: Vec<f32> = clouds[1].iter().map(|p| p.xyz[0]).collect();
+/// assert_eq!(x, [0., 2.]);
+/// assert_eq!(clouds[0].len(), 1);
+/// // point of the laser 5 is out of range
+/// assert!(clouds[2].is_empty());
+/// ```
+#[cfg(feature = "std")]
+pub fn partition_by_laser(points: &[FullPoint], laser_count: usize)
+    -> Vec<Vec<FullPoint>>
+{
+    let mut res = vec![vec![]; laser_count];
+    for p in points {
+        if let Some(cloud) = res.get_mut(p.laser_id as usize) {
+            cloud.push(*p);
+        }
+    }
+    res
+}
+
+/// Coordinates used for cells without valid points in organized clouds
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum InvalidPointRepr {
+    /// NaN coordinates (default)
+    #[default]
+    Nan,
+    /// Zero coordinates, i.e. point in the sensor origin
+    Zero,
+    /// All coordinates are equal to the given value, e.g. a large range
+    Sentinel(f32),
+}
+
+impl InvalidPointRepr {
+    /// Get coordinates of invalid points
+    pub fn xyz(self) -> [f32; 3] {
+        match self {
+            InvalidPointRepr::Nan => [f32::NAN; 3],
+            InvalidPointRepr::Zero => [0.; 3],
+            InvalidPointRepr::Sentinel(v) => [v; 3],
+        }
+    }
+}
+
+/// Arrange points of a turn into an organized cloud with `laser_count` rows
+/// and `columns` columns stored in the row-major order
+///
+/// Row of a point is equal to its laser ID and column is computed from its
+/// azimuth using `azimuth_to_column`. If several points fall into the same
+/// cell, the first one is kept. Cells without points and cells with points
+/// without return (NaN coordinates) get coordinates defined by `invalid`,
+/// zero intensity, laser ID of their row and azimuth of their column.
+/// Points with `laser_id` greater or equal to `laser_count` are ignored.
+///
+/// Requires `std` feature.
+///
+/// # Panics
+/// If `columns` is equal to zero.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::analysis::{organize, InvalidPointRepr};
+///
+/// let point = |laser_id, azimuth, x| FullPoint {
+///     xyz: [x, 0., 0.], laser_id, azimuth, intensity: 10,
+///     ..Default::default()
+/// };
+/// // 2 lasers, 4 columns, the point at 180° has no return
+/// let points = [
+///     point(0, 0, 1.), point(1, 9000, 2.), point(1, 18000, f32::NAN),
+///     point(0, 27000, 4.), point(0, 27010, 5.), point(7, 0, 6.),
+/// ];
+///
+/// let cloud = organize(&points, 2, 4, InvalidPointRepr::Nan);
+/// assert_eq!(cloud.len(), 8);
+/// let valid = [0, 3, 5];
+/// for (i, p) in cloud.iter().enumerate() {
+///     assert_eq!(p.laser_id as usize, i/4);
+///     assert_eq!(p.azimuth as usize, 9000*(i % 4));
+///     assert_eq!(p.xyz[0].is_nan(), !valid.contains(&i));
+/// }
+/// let x: Vec<f32> = valid.iter().map(|&i| cloud[i].xyz[0]).collect();
+/// assert_eq!(x, [1., 4., 2.]);
+/// assert_eq!(cloud[6].intensity, 0);
+///
+/// let cloud = organize(&points, 2, 4, InvalidPointRepr::Zero);
+/// for i in [1, 2, 4, 6, 7].iter() {
+///     assert_eq!(cloud[*i].xyz, [0.; 3]);
+/// }
+///
+/// let cloud = organize(&points, 2, 4, InvalidPointRepr::Sentinel(1e3));
+/// for i in [1, 2, 4, 6, 7].iter() {
+///     assert_eq!(cloud[*i].xyz, [1e3; 3]);
+/// }
+/// assert_eq!(cloud[0].xyz, [1., 0., 0.]);
+/// ```
+#[cfg(feature = "std")]
+pub fn organize(
+    points: &[FullPoint], laser_count: usize, columns: usize,
+    invalid: InvalidPointRepr,
+) -> Vec<FullPoint> {
+    assert!(columns > 0, "number of columns must be positive");
+    let xyz = invalid.xyz();
+    let mut res: Vec<FullPoint> = (0..laser_count*columns)
+        .map(|i| FullPoint {
+            xyz,
+            laser_id: (i/columns) as u8,
+            azimuth: column_to_azimuth(i % columns, columns),
+            ..Default::default()
+        })
+        .collect();
+    let mut filled = vec![false; res.len()];
+    for p in points {
+        let row = p.laser_id as usize;
+        if row >= laser_count || p.xyz.iter().any(|v| v.is_nan()) {
+            continue;
+        }
+        let i = row*columns + azimuth_to_column(p.azimuth, columns);
+        if !filled[i] {
+            filled[i] = true;
+            res[i] = *p;
+        }
+    }
+    res
+}
+
+/// Compute histogram of point intensities, `i`-th element contains number of
+/// points with intensity equal to `i`
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::analysis::{intensity_histogram, histogram_percentile};
+///
+/// let points: Vec<FullPoint> = (0..100u8)
+///     .map(|i| FullPoint { intensity: i % 10, ..Default::default() })
+///     .chain(Some(FullPoint { intensity: 255, ..Default::default() }))
+///     .collect();
+/// let hist = intensity_histogram(&points);
+/// assert!(hist[..10].iter().all(|&n| n == 10));
+/// assert!(hist[10..255].iter().all(|&n| n == 0));
+/// assert_eq!(hist[255], 1);
+///
+/// assert_eq!(histogram_percentile(&hist, 50.), Some(5));
+/// assert_eq!(histogram_percentile(&hist, 95.), Some(9));
+/// assert_eq!(histogram_percentile(&hist, 100.), Some(255));
+/// assert_eq!(histogram_percentile(&[0; 256], 50.), None);
+/// ```
+pub fn intensity_histogram(points: &[FullPoint]) -> [u32; 256] {
+    let mut hist = [0u32; 256];
+    for p in points {
+        hist[p.intensity as usize] += 1;
+    }
+    hist
+}
+
+/// Find the smallest intensity which is greater or equal to `percentile`
+/// percent of values in the intensity histogram
+///
+/// `percentile` is clamped to the `[0, 100]` range. Returns `None` if
+/// histogram is empty.
+pub fn histogram_percentile(hist: &[u32; 256], percentile: f32) -> Option<u8> {
+    let total: u64 = hist.iter().map(|&n| n as u64).sum();
+    if total == 0 { return None; }
+    let p = percentile.clamp(0., 100.) as f64/100.;
+    // number of values which must be covered rounded up, at least one value
+    // is needed to cover the zero percentile
+    let x = p*total as f64;
+    let target = x as u64 + ((x as u64 as f64) < x) as u64;
+    let target = target.max(1);
+    let mut acc = 0u64;
+    for (i, &n) in hist.iter().enumerate() {
+        acc += n as u64;
+        if acc >= target { return Some(i as u8); }
+    }
+    Some(255)
+}
+
+/// Compute deterministic fingerprint of converted points
+///
+/// Fingerprint is a 64-bit FNV-1a hash of coordinates quantized to
+/// millimeters, intensity and laser ID of each point in the given order, so
+/// it is sensitive to the order of points, but not to sub-millimeter
+/// differences in coordinates (unless they cross the rounding boundary).
+/// All points with NaN coordinates are hashed identically. It is intended
+/// for regression testing of the conversion code.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::{Convertor, FullPoint};
+/// use velodyne::analysis::turn_fingerprint;
+/// use velodyne::hdl32::{Hdl32Convertor, ELEVATION_ANGLES};
+/// use velodyne::packet::{PacketSource, PcapSource};
+///
+/// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let (_, packet) = source.next_packet()?.unwrap();
+/// let convert = |c: Hdl32Convertor| {
+///     let mut points = vec![];
+///     c.convert(packet, |p: FullPoint| points.push(p)).unwrap();
+///     points
+/// };
+///
+/// let mut points = convert(Hdl32Convertor::default());
+/// let fp = turn_fingerprint(&points);
+/// // explicitly setting the default table does not change the output
+/// let same = convert(Hdl32Convertor::default()
+///     .with_vertical_table(ELEVATION_ANGLES));
+/// assert_eq!(turn_fingerprint(&same), fp);
+///
+/// points[10].xyz[0] += 0.01;
+/// assert_ne!(turn_fingerprint(&points), fp);
+/// points[10].xyz[0] -= 0.01;
+/// assert_eq!(turn_fingerprint(&points), fp);
+///
+/// points.swap(0, 1);
+/// assert_ne!(turn_fingerprint(&points), fp);
+/// # Ok(()) }
+/// ```
+pub fn turn_fingerprint(points: &[FullPoint]) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = FNV_OFFSET;
+    let mut feed = |bytes: &[u8]| for &b in bytes {
+        hash = (hash ^ b as u64).wrapping_mul(FNV_PRIME);
+    };
+    for p in points {
+        for &v in p.xyz.iter() {
+            // `as` saturates on overflow, NaN is mapped to a sentinel value
+            let q = if v.is_nan() { i32::MIN } else { round(v*1000.) as i32 };
+            feed(&q.to_le_bytes());
+        }
+        feed(&[p.intensity, p.laser_id]);
+    }
+    hash
+}
@@ -0,0 +1,238 @@
+//! Typed azimuth
+use core::fmt;
+use core::ops::{Add, Sub};
+
+use crate::math;
+
+/// Number of azimuth units in the full turn
+pub(crate) const FULL_TURN: u16 = 36000;
+
+/// Units of raw azimuth values reported by the sensor
+///
+/// Scale is defined by the number of units in the full turn. Convertors use
+/// it to interpret block azimuths and report azimuths of points and packets
+/// in hundredths of degree, so the rest of the crate (e.g. splitting of
+/// turns) does not depend on sensor units. Since azimuth is stored in packets
+/// as `u16`, the full turn can contain at most 65536 units.
+///
+/// # Example
+/// ```
+/// use velodyne::AzimuthScale;
+///
+/// let scale = AzimuthScale::default();
+/// assert_eq!(scale, AzimuthScale::HUNDREDTHS);
+/// assert_eq!(scale.to_hundredths(12345), Some(12345.));
+/// assert_eq!(scale.to_hundredths(36000), None);
+///
+/// // tenths of degree
+/// let scale = AzimuthScale::new(3600).unwrap();
+/// assert_eq!(scale.units(), 3600);
+/// assert_eq!(scale.to_hundredths(1234), Some(12340.));
+/// assert_eq!(scale.to_hundredths(3600), None);
+/// assert!(AzimuthScale::new(0).is_none());
+/// assert!(AzimuthScale::new(65537).is_none());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AzimuthScale(u32);
+
+impl AzimuthScale {
+    /// Hundredths of degree (36000 units in the full turn) used by all
+    /// supported sensors
+    pub const HUNDREDTHS: AzimuthScale = AzimuthScale(FULL_TURN as u32);
+
+    /// Create scale with `units` in the full turn, returns `None` if `units`
+    /// is zero or greater than 65536
+    pub fn new(units: u32) -> Option<Self> {
+        if units == 0 || units > 1 << 16 { return None; }
+        Some(AzimuthScale(units))
+    }
+
+    /// Get number of units in the full turn
+    pub fn units(self) -> u32 {
+        self.0
+    }
+
+    /// Convert `raw` azimuth into hundredths of degree without rounding,
+    /// returns `None` if `raw` is not less than the full turn
+    pub fn to_hundredths(self, raw: u16) -> Option<f32> {
+        let raw = raw as u32;
+        if raw >= self.0 { return None; }
+        if self == Self::HUNDREDTHS { return Some(raw as f32); }
+        Some((raw as f64*FULL_TURN as f64/self.0 as f64) as f32)
+    }
+
+    /// Convert `raw` azimuth into hundredths of degree rounded to the
+    /// nearest integer and wrapped into the `[0, 36000)` range
+    pub(crate) fn to_raw_hundredths(self, raw: u16) -> Option<u16> {
+        self.to_hundredths(raw)
+            .map(|a| (math::round(a) as u32 % FULL_TURN as u32) as u16)
+    }
+}
+
+impl Default for AzimuthScale {
+    fn default() -> Self {
+        Self::HUNDREDTHS
+    }
+}
+
+/// Sensor azimuth in hundredths of degree, always in the `[0, 36000)` range
+///
+/// Arithmetic operations wrap around the full turn.
+///
+/// # Example
+/// ```
+/// use velodyne::Azimuth;
+///
+/// let a = Azimuth::new(35990);
+/// let b = a + Azimuth::new(20);
+/// assert_eq!(b.raw(), 10);
+/// assert_eq!((b - a).raw(), 20);
+/// assert_eq!((a - b).raw(), 35980);
+/// assert_eq!(Azimuth::new(36005).raw(), 5);
+/// assert_eq!(Azimuth::from_degrees(-90.).raw(), 27000);
+///
+/// // rotation from 359.99° to 0.01° crosses 0°, but not 180°
+/// let split = Azimuth::new(0);
+/// assert!(split.is_crossed(Azimuth::new(35999), Azimuth::new(1)));
+/// assert!(!Azimuth::new(18000).is_crossed(Azimuth::new(35999), Azimuth::new(1)));
+///
+/// // sector from 350° to 10°
+/// let (start, end) = (Azimuth::new(35000), Azimuth::new(1000));
+/// assert!(Azimuth::new(0).is_within(start, end));
+/// assert!(Azimuth::new(1000).is_within(start, end));
+/// assert!(!Azimuth::new(18000).is_within(start, end));
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Azimuth(u16);
+
+impl Azimuth {
+    /// Create azimuth from the raw value in hundredths of degree, values
+    /// outside of the `[0, 36000)` range are wrapped.
+    pub fn new(raw: u16) -> Self {
+        Azimuth(raw % FULL_TURN)
+    }
+
+    /// Create azimuth from degrees, value is wrapped into the `[0, 360)`
+    /// range and rounded to hundredths of degree.
+    pub fn from_degrees(deg: f32) -> Self {
+        let raw = math::rem_euclid(math::round(deg*100.), FULL_TURN as f32);
+        Azimuth::new(raw as u16)
+    }
+
+    /// Get raw value in hundredths of degree
+    pub fn raw(self) -> u16 {
+        self.0
+    }
+
+    /// Get azimuth in degrees
+    pub fn as_degrees(self) -> f32 {
+        self.0 as f32/100.
+    }
+
+    /// Get azimuth in radians
+    pub fn as_radians(self) -> f32 {
+        self.as_degrees().to_radians()
+    }
+
+    /// Returns `true` if sensor rotation from `prev` to `next` has crossed
+    /// this azimuth, i.e. if it lies in the `(prev, next]` interval.
+    ///
+    /// Sensor is assumed to rotate clockwise (i.e. with increasing azimuth)
+    /// by less than a full turn between `prev` and `next`.
+    pub fn is_crossed(self, prev: Azimuth, next: Azimuth) -> bool {
+        let d = self - prev;
+        d.0 != 0 && d <= next - prev
+    }
+
+    /// Returns `true` if azimuth lies in the `[start, end]` sector, sector
+    /// can cross zero azimuth (i.e. `start` can be greater than `end`).
+    pub fn is_within(self, start: Azimuth, end: Azimuth) -> bool {
+        self - start <= end - start
+    }
+}
+
+impl From<u16> for Azimuth {
+    fn from(raw: u16) -> Self { Azimuth::new(raw) }
+}
+
+impl From<Azimuth> for u16 {
+    fn from(a: Azimuth) -> Self { a.0 }
+}
+
+impl Add for Azimuth {
+    type Output = Azimuth;
+
+    fn add(self, other: Azimuth) -> Azimuth {
+        Azimuth(((self.0 as u32 + other.0 as u32) % FULL_TURN as u32) as u16)
+    }
+}
+
+impl Sub for Azimuth {
+    type Output = Azimuth;
+
+    fn sub(self, other: Azimuth) -> Azimuth {
+        if self.0 >= other.0 {
+            Azimuth(self.0 - other.0)
+        } else {
+            Azimuth(self.0 + (FULL_TURN - other.0))
+        }
+    }
+}
+
+impl fmt::Display for Azimuth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.2}°", self.as_degrees())
+    }
+}
+
+/// Map azimuth in hundredths of degree onto column index of a range image
+/// with `columns` columns covering the full turn
+///
+/// Column `i` is centered at azimuth `i*36000/columns`, azimuths are rounded
+/// to the nearest column center, so azimuths just below `36000` wrap to the
+/// column 0. Azimuths outside of the `[0, 36000)` range are wrapped as well.
+///
+/// # Panics
+/// If `columns` is equal to zero.
+///
+/// # Example
+/// ```
+/// use velodyne::{azimuth_to_column, column_to_azimuth};
+///
+/// assert_eq!(azimuth_to_column(0, 360), 0);
+/// assert_eq!(azimuth_to_column(17999, 360), 180);
+/// assert_eq!(azimuth_to_column(35999, 360), 0);
+///
+/// assert_eq!(azimuth_to_column(0, 1024), 0);
+/// assert_eq!(azimuth_to_column(17999, 1024), 512);
+/// assert_eq!(azimuth_to_column(35999, 1024), 0);
+///
+/// assert_eq!(azimuth_to_column(17999, 1), 0);
+/// for &columns in [1, 7, 360, 1024, 2048, 36000].iter() {
+///     for col in 0..columns {
+///         let azimuth = column_to_azimuth(col, columns);
+///         assert_eq!(azimuth_to_column(azimuth, columns), col);
+///     }
+/// }
+/// ```
+pub fn azimuth_to_column(azimuth: u16, columns: usize) -> usize {
+    assert!(columns > 0, "number of columns must be positive");
+    let azimuth = (azimuth % FULL_TURN) as u64;
+    let (turn, columns) = (FULL_TURN as u64, columns as u64);
+    (((azimuth*columns + turn/2)/turn) % columns) as usize
+}
+
+/// Get azimuth of the center of range image column `column`, inverse of
+/// `azimuth_to_column`
+///
+/// Azimuth is rounded to the nearest integer, column indices not less than
+/// `columns` are wrapped.
+///
+/// # Panics
+/// If `columns` is equal to zero.
+pub fn column_to_azimuth(column: usize, columns: usize) -> u16 {
+    assert!(columns > 0, "number of columns must be positive");
+    let column = (column % columns) as u64;
+    let (turn, columns) = (FULL_TURN as u64, columns as u64);
+    (((column*turn + columns/2)/columns) % turn) as u16
+}
@@ -0,0 +1,84 @@
+//! Segmentation of point clouds into objects
+//!
+//! Points are grouped by Euclidean distance, neighbors are searched using a
+//! kd-tree built over the whole cloud.
+use crate::FullPoint;
+use crate::kdtree::KdTree;
+
+/// Group points into clusters of points connected by chains of neighbors
+/// separated by no more than `tolerance` meters
+///
+/// Returns indices of points in each cluster sorted in ascending order,
+/// clusters are ordered by their smallest index. Clusters with less than
+/// `min_size` points are dropped. Points with NaN coordinates (i.e. without
+/// return) are not assigned to any cluster.
+///
+/// Requires `perception` feature.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::cluster::euclidean_cluster;
+///
+/// let point = |x, y, z| FullPoint { xyz: [x, y, z], ..Default::default() };
+/// // two 10x10 grids with 10 cm step separated by ~10 m
+/// let mut points = vec![];
+/// for i in 0..10 {
+///     for j in 0..10 {
+///         let (a, b) = (0.1*i as f32, 0.1*j as f32);
+///         points.push(point(5. + a, b, -1.));
+///         points.push(point(-5., 3. + a, b));
+///     }
+/// }
+/// // outlier and point without return
+/// points.push(point(0., 0., 0.));
+/// points.push(point(f32::NAN, f32::NAN, f32::NAN));
+///
+/// let clusters = euclidean_cluster(&points, 0.15, 5);
+/// assert_eq!(clusters.len(), 2);
+/// for (n, cluster) in clusters.iter().enumerate() {
+///     assert_eq!(cluster.len(), 100);
+///     assert!(cluster.iter().all(|&i| i % 2 == n));
+/// }
+///
+/// // with smaller tolerance all points are isolated
+/// assert!(euclidean_cluster(&points, 0.05, 2).is_empty());
+/// assert_eq!(euclidean_cluster(&points, 0.05, 1).len(), 201);
+/// ```
+pub fn euclidean_cluster(points: &[FullPoint], tolerance: f32, min_size: usize)
+    -> Vec<Vec<usize>>
+{
+    let xyz: Vec<[f32; 3]> = points.iter().map(|p| p.xyz).collect();
+    let valid: Vec<usize> = (0..xyz.len())
+        .filter(|&i| !xyz[i].iter().any(|v| v.is_nan()))
+        .collect();
+    let mut clusters = vec![];
+    if valid.is_empty() { return clusters; }
+
+    let tree = KdTree::new(&xyz, valid.clone());
+    let r2 = tolerance*tolerance;
+    let mut visited = vec![false; xyz.len()];
+    let mut neighbors = vec![];
+    for seed in valid {
+        if visited[seed] { continue; }
+        visited[seed] = true;
+        // breadth-first search, `cluster` doubles as the queue
+        let mut cluster = vec![seed];
+        let mut pos = 0;
+        while pos < cluster.len() {
+            tree.within(xyz[cluster[pos]], r2, &mut neighbors);
+            for &i in neighbors.iter() {
+                if !visited[i] {
+                    visited[i] = true;
+                    cluster.push(i);
+                }
+            }
+            pos += 1;
+        }
+        if cluster.len() >= min_size {
+            cluster.sort_unstable();
+            clusters.push(cluster);
+        }
+    }
+    clusters
+}
@@ -0,0 +1,598 @@
+//! Writers of point clouds into various file formats
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+use std::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use log::info;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use crate::{Azimuth, FullPoint, TurnIterator};
+use crate::azimuth::FULL_TURN;
+use crate::model::{SensorModel, SensorConfig};
+use crate::packet::PcapSource;
+
+/// Options of the simple XYZ text format
+#[derive(Copy, Clone, Debug)]
+pub struct XyzOptions {
+    /// Number of digits after the decimal point used for coordinates, default
+    /// value is 3 (i.e. millimeter precision)
+    pub precision: usize,
+    /// Skip points with NaN coordinates instead of writing them as `nan`,
+    /// default value is `false`
+    pub skip_nan: bool,
+}
+
+impl Default for XyzOptions {
+    fn default() -> Self {
+        Self { precision: 3, skip_nan: false }
+    }
+}
+
+/// Write points in the simple XYZ text format
+///
+/// Each point is written on a separate line as whitespace separated
+/// `x y z intensity` values. Output can be directly used by tools like
+/// gnuplot.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::export::{write_xyz, XyzOptions};
+///
+/// let points = [
+///     FullPoint { xyz: [1., -2., 0.5], intensity: 10, ..Default::default() },
+///     FullPoint { xyz: [std::f32::NAN; 3], ..Default::default() },
+///     FullPoint { xyz: [0.1234, 0., 3.], intensity: 255, ..Default::default() },
+/// ];
+///
+/// let mut buf = vec![];
+/// write_xyz(&mut buf, &points, XyzOptions::default()).unwrap();
+/// assert_eq!(String::from_utf8(buf).unwrap(), "\
+///     1.000 -2.000 0.500 10\n\
+///     nan nan nan 0\n\
+///     0.123 0.000 3.000 255\n");
+///
+/// let mut buf = vec![];
+/// let opts = XyzOptions { precision: 1, skip_nan: true };
+/// write_xyz(&mut buf, &points, opts).unwrap();
+/// assert_eq!(String::from_utf8(buf).unwrap(), "\
+///     1.0 -2.0 0.5 10\n\
+///     0.1 0.0 3.0 255\n");
+/// ```
+pub fn write_xyz<W: Write>(
+    mut writer: W, points: &[FullPoint], opts: XyzOptions,
+) -> io::Result<()> {
+    let prec = opts.precision;
+    for p in points {
+        let has_nan = p.xyz.iter().any(|v| v.is_nan());
+        if has_nan && opts.skip_nan { continue; }
+        for v in p.xyz.iter() {
+            if v.is_nan() {
+                writer.write_all(b"nan ")?;
+            } else {
+                write!(writer, "{:.*} ", prec, v)?;
+            }
+        }
+        writeln!(writer, "{}", p.intensity)?;
+    }
+    Ok(())
+}
+
+/// Number of microseconds in an hour, point timestamps wrap at this value
+const HOUR_US: u64 = 3_600_000_000;
+
+/// Metadata of an exported turn, see `ManifestWriter`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TurnRecord {
+    /// Index of the turn, e.g. frame number of the exported file
+    pub frame: usize,
+    /// Timestamp of the first point in microseconds from the top of the hour
+    pub start_timestamp: u32,
+    /// Timestamp of the last point in microseconds from the top of the hour
+    pub end_timestamp: u32,
+    /// Azimuth of the first point in `degrees*100`
+    pub start_azimuth: u16,
+    /// Azimuth of the last point in `degrees*100`
+    pub end_azimuth: u16,
+    /// Number of points in the turn
+    pub points: usize,
+    /// Rotation speed in revolutions per minute estimated from the azimuth
+    /// span and duration of the turn, zero if duration is zero
+    pub rpm: f32,
+    /// Snapshot of the sensor status, e.g. its `Debug` representation
+    pub status: String,
+}
+
+impl TurnRecord {
+    /// Create record from points of the turn
+    ///
+    /// Returns `None` if `points` is empty.
+    pub fn from_points(frame: usize, points: &[FullPoint], status: String)
+        -> Option<Self>
+    {
+        let (first, last) = (points.first()?, points.last()?);
+        let span = Azimuth::new(last.azimuth) - Azimuth::new(first.azimuth);
+        // handle wrap at the top of the hour
+        let (t0, t1) = (first.timestamp as u64, last.timestamp as u64);
+        let dt = (t1 + HOUR_US - t0) % HOUR_US;
+        let rpm = if dt == 0 {
+            0.
+        } else {
+            let turns = span.raw() as f64/FULL_TURN as f64;
+            (turns*60e6/dt as f64) as f32
+        };
+        Some(Self {
+            frame,
+            start_timestamp: first.timestamp,
+            end_timestamp: last.timestamp,
+            start_azimuth: first.azimuth,
+            end_azimuth: last.azimuth,
+            points: points.len(),
+            rpm,
+            status,
+        })
+    }
+}
+
+/// Writer of turn records into CSV manifest, one line per turn
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::export::{ManifestWriter, TurnRecord};
+///
+/// let point = |timestamp, azimuth| FullPoint {
+///     timestamp, azimuth, ..Default::default()
+/// };
+/// // half turns taking 50 ms, the second one wraps at the top of the hour
+/// let turns = [
+///     [point(1_000, 0), point(51_000, 18_000)],
+///     [point(3_599_990_000, 35_000), point(40_000, 17_000)],
+/// ];
+///
+/// let mut buf = vec![];
+/// let mut writer = ManifestWriter::new(&mut buf).unwrap();
+/// for (i, turn) in turns.iter().enumerate() {
+///     let status = format!("ok, \"turn {}\"", i + 1);
+///     let record = TurnRecord::from_points(i + 1, turn, status).unwrap();
+///     assert_eq!(record.rpm, 600.);
+///     writer.write(&record).unwrap();
+/// }
+/// assert_eq!(String::from_utf8(buf).unwrap(), "\
+///     frame,start_timestamp,end_timestamp,start_azimuth,end_azimuth,\
+///     points,rpm,status\n\
+///     1,1000,51000,0,18000,2,600.0,\"ok, \"\"turn 1\"\"\"\n\
+///     2,3599990000,40000,35000,17000,2,600.0,\"ok, \"\"turn 2\"\"\"\n");
+/// ```
+pub struct ManifestWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ManifestWriter<W> {
+    /// Create new writer and write CSV header
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "frame,start_timestamp,end_timestamp,start_azimuth,\
+            end_azimuth,points,rpm,status")?;
+        Ok(Self { writer })
+    }
+
+    /// Write `record` as a single line, status is quoted
+    pub fn write(&mut self, record: &TurnRecord) -> io::Result<()> {
+        let r = record;
+        writeln!(self.writer, "{},{},{},{},{},{},{:.1},\"{}\"",
+            r.frame, r.start_timestamp, r.end_timestamp, r.start_azimuth,
+            r.end_azimuth, r.points, r.rpm, r.status.replace('"', "\"\""))
+    }
+
+    /// Destruct writer and return the inner writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Encoding of point data in PCD files
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PcdData {
+    /// Text with a point per line
+    #[default]
+    Ascii,
+    /// LZF compressed binary data with each field stored contiguously
+    BinaryCompressed,
+}
+
+/// Write points in the ASCII variant of the PCD format used by PCL
+///
+/// Points are written as an unorganized cloud with `x y z intensity` fields,
+/// NaN coordinates are written as `nan`. Use `write_pcd_with` for compressed
+/// output.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::export::write_pcd;
+///
+/// let points = [
+///     FullPoint { xyz: [1., -2., 0.5], intensity: 10, ..Default::default() },
+///     FullPoint { xyz: [0.25, 0., 3.], intensity: 255, ..Default::default() },
+/// ];
+///
+/// let mut buf = vec![];
+/// write_pcd(&mut buf, &points).unwrap();
+/// let pcd = String::from_utf8(buf).unwrap();
+/// assert!(pcd.contains("FIELDS x y z intensity\nSIZE 4 4 4 1\n"));
+/// assert!(pcd.contains("WIDTH 2\nHEIGHT 1\n"));
+/// assert!(pcd.ends_with("POINTS 2\nDATA ascii\n1 -2 0.5 10\n0.25 0 3 255\n"));
+/// ```
+pub fn write_pcd<W: Write>(writer: W, points: &[FullPoint])
+    -> io::Result<()>
+{
+    write_pcd_with(writer, points, PcdData::Ascii)
+}
+
+/// Write points in the PCD format with the given `data` encoding
+///
+/// Header is the same as in `write_pcd`. For `PcdData::BinaryCompressed`
+/// header is followed by compressed and uncompressed data sizes stored as
+/// little-endian `u32` values and LZF compressed data in which all `x`
+/// values are followed by all `y` values, etc. Empty cloud is written with
+/// zero sizes and without data.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::export::{write_pcd_with, PcdData};
+///
+/// /// Minimal LZF decompressor
+/// fn lzf_decompress(mut src: &[u8]) -> Vec<u8> {
+///     let mut res: Vec<u8> = vec![];
+///     while let Some((&ctrl, rest)) = src.split_first() {
+///         src = rest;
+///         let ctrl = ctrl as usize;
+///         if ctrl < 32 {
+///             res.extend_from_slice(&src[..ctrl + 1]);
+///             src = &src[ctrl + 1..];
+///             continue;
+///         }
+///         let mut len = ctrl >> 5;
+///         if len == 7 {
+///             len += src[0] as usize;
+///             src = &src[1..];
+///         }
+///         let start = res.len() - ((ctrl & 0x1F) << 8) - src[0] as usize - 1;
+///         src = &src[1..];
+///         for i in start..start + len + 2 { res.push(res[i]); }
+///     }
+///     res
+/// }
+///
+/// let points: Vec<FullPoint> = (0..1000).map(|i| FullPoint {
+///     xyz: [(i % 10) as f32, 0.5, -1.], intensity: (i % 7) as u8,
+///     ..Default::default()
+/// }).collect();
+/// let mut buf = vec![];
+/// write_pcd_with(&mut buf, &points, PcdData::BinaryCompressed).unwrap();
+///
+/// let header = b"POINTS 1000\nDATA binary_compressed\n";
+/// let pos = buf.windows(header.len()).position(|w| w == header).unwrap();
+/// let data = &buf[pos + header.len()..];
+/// let read_u32 = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+/// let (compressed, uncompressed) = (read_u32(data), read_u32(&data[4..]));
+/// assert_eq!(uncompressed, 13*1000);
+/// assert_eq!(compressed as usize, data.len() - 8);
+/// assert!(compressed < uncompressed/10);
+///
+/// let fields = lzf_decompress(&data[8..]);
+/// assert_eq!(fields.len(), 13*1000);
+/// for (i, p) in points.iter().enumerate() {
+///     for (j, v) in p.xyz.iter().enumerate() {
+///         let pos = 4*(1000*j + i);
+///         assert_eq!(&fields[pos..pos + 4], &v.to_le_bytes());
+///     }
+///     assert_eq!(fields[12*1000 + i], p.intensity);
+/// }
+///
+/// // empty cloud contains only zero sizes
+/// let mut buf = vec![];
+/// write_pcd_with(&mut buf, &[], PcdData::BinaryCompressed).unwrap();
+/// assert!(buf.ends_with(b"DATA binary_compressed\n\0\0\0\0\0\0\0\0"));
+/// ```
+pub fn write_pcd_with<W: Write>(
+    mut writer: W, points: &[FullPoint], data: PcdData,
+) -> io::Result<()> {
+    let n = points.len();
+    writeln!(writer, "# .PCD v0.7 - Point Cloud Data file format")?;
+    writeln!(writer, "VERSION 0.7")?;
+    writeln!(writer, "FIELDS x y z intensity")?;
+    writeln!(writer, "SIZE 4 4 4 1")?;
+    writeln!(writer, "TYPE F F F U")?;
+    writeln!(writer, "COUNT 1 1 1 1")?;
+    writeln!(writer, "WIDTH {}", n)?;
+    writeln!(writer, "HEIGHT 1")?;
+    writeln!(writer, "VIEWPOINT 0 0 0 1 0 0 0")?;
+    writeln!(writer, "POINTS {}", n)?;
+    match data {
+        PcdData::Ascii => {
+            writeln!(writer, "DATA ascii")?;
+            for p in points {
+                let [x, y, z] = p.xyz;
+                writeln!(writer, "{} {} {} {}", x, y, z, p.intensity)?;
+            }
+        },
+        PcdData::BinaryCompressed => {
+            writeln!(writer, "DATA binary_compressed")?;
+            let mut fields = Vec::with_capacity(13*n);
+            for i in 0..3 {
+                for p in points {
+                    fields.extend_from_slice(&p.xyz[i].to_le_bytes());
+                }
+            }
+            fields.extend(points.iter().map(|p| p.intensity));
+            let compressed = lzf_compress(&fields);
+            let size = |len: usize| u32::try_from(len).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput,
+                    "point cloud is too large for PCD")
+            });
+            writer.write_all(&size(compressed.len())?.to_le_bytes())?;
+            writer.write_all(&size(fields.len())?.to_le_bytes())?;
+            writer.write_all(&compressed)?;
+        },
+    }
+    Ok(())
+}
+
+/// Compress data using LZF algorithm
+///
+/// Output is compatible with `lzf_decompress` from liblzf, which is used
+/// by PCL for reading of compressed PCD files.
+fn lzf_compress(input: &[u8]) -> Vec<u8> {
+    const HASH_LOG: u32 = 14;
+    const MAX_LITERALS: usize = 32;
+    const MAX_OFFSET: usize = 1 << 13;
+    const MAX_MATCH: usize = (1 << 8) + (1 << 3);
+
+    fn push_literals(out: &mut Vec<u8>, literals: &[u8]) {
+        for chunk in literals.chunks(MAX_LITERALS) {
+            out.push((chunk.len() - 1) as u8);
+            out.extend_from_slice(chunk);
+        }
+    }
+
+    let mut out = Vec::with_capacity(input.len()/2);
+    // last positions of 3-byte sequences with the given hash
+    let mut table = vec![usize::MAX; 1 << HASH_LOG];
+    let mut literals_start = 0;
+    let mut i = 0;
+    while i + 3 <= input.len() {
+        let v = u32::from_le_bytes([input[i], input[i + 1], input[i + 2], 0]);
+        let h = (v.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize;
+        let r = std::mem::replace(&mut table[h], i);
+        if r == usize::MAX || i - r > MAX_OFFSET
+            || input[r..r + 3] != input[i..i + 3]
+        {
+            i += 1;
+            continue;
+        }
+        let max_len = (input.len() - i).min(MAX_MATCH);
+        let mut len = 3;
+        while len < max_len && input[r + len] == input[i + len] { len += 1; }
+
+        push_literals(&mut out, &input[literals_start..i]);
+        // back reference stores length minus 2 and offset minus 1
+        let (len_code, offset) = (len - 2, i - r - 1);
+        if len_code < 7 {
+            out.push(((len_code << 5) | (offset >> 8)) as u8);
+        } else {
+            out.push(((7 << 5) | (offset >> 8)) as u8);
+            out.push((len_code - 7) as u8);
+        }
+        out.push(offset as u8);
+        i += len;
+        literals_start = i;
+    }
+    push_literals(&mut out, &input[literals_start..]);
+    out
+}
+
+/// Convert pcap file into a sequence of PCD files, one for each turn
+///
+/// Files are named `frame_00001.pcd`, `frame_00002.pcd`, etc. and written
+/// into `out_dir`, which is created if it does not exist. Points before the
+/// first crossing of zero azimuth are skipped, so all written turns are
+/// complete. Progress is logged on the `info` level. Returns number of
+/// written frames.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::export::export_pcap_to_pcd;
+/// use velodyne::model::{SensorModel, SensorConfig};
+///
+/// let out_dir = std::env::temp_dir().join("velodyne_pcd_frames");
+/// # let _ = std::fs::remove_dir_all(&out_dir);
+/// let config = SensorConfig::default();
+/// let n = export_pcap_to_pcd(
+///     "data/hdl32.pcap", &out_dir, SensorModel::Hdl32, config)?;
+/// assert!(n > 0);
+/// assert_eq!(std::fs::read_dir(&out_dir)?.count(), n);
+/// assert!(out_dir.join("frame_00001.pcd").exists());
+/// # std::fs::remove_dir_all(&out_dir)?;
+/// # Ok(()) }
+/// ```
+pub fn export_pcap_to_pcd<P, Q>(
+    pcap_path: P, out_dir: Q, model: SensorModel, config: SensorConfig,
+) -> io::Result<usize>
+    where P: AsRef<Path>, Q: AsRef<Path>
+{
+    let out_dir = out_dir.as_ref();
+    fs::create_dir_all(out_dir)?;
+    let source = PcapSource::new(pcap_path, false, false)?;
+    let mut turns: TurnIterator<_, _, _, FullPoint> =
+        TurnIterator::for_model(source, model, config)?;
+    turns.skip_partial_first_turn();
+
+    let mut points = vec![];
+    let mut n = 0;
+    while turns.fill_turn(&mut points)?.is_some() {
+        n += 1;
+        write_pcd_frame(out_dir, n, &points)?;
+    }
+    Ok(n)
+}
+
+/// Write frame number `n` into `out_dir` using naming of `export_pcap_to_pcd`
+fn write_pcd_frame(out_dir: &Path, n: usize, points: &[FullPoint])
+    -> io::Result<()>
+{
+    let path = out_dir.join(format!("frame_{:05}.pcd", n));
+    let mut writer = BufWriter::new(File::create(&path)?);
+    write_pcd(&mut writer, points)?;
+    writer.flush()?;
+    info!("frame {} with {} points written to {}",
+        n, points.len(), path.display());
+    Ok(())
+}
+
+/// Multithreaded version of `export_pcap_to_pcd`
+///
+/// Turns are decoded on the calling thread, while serialization and writing
+/// of files is done by a pool of `threads` workers (at least one worker is
+/// always used). See `write_pcd_frames` for details.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::export::{export_pcap_to_pcd, export_pcap_to_pcd_parallel};
+/// use velodyne::model::{SensorModel, SensorConfig};
+///
+/// let dir = std::env::temp_dir().join("velodyne_pcd_frames_parallel");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// let config = SensorConfig::default();
+/// let n = export_pcap_to_pcd_parallel(
+///     "data/hdl32.pcap", dir.join("par"), SensorModel::Hdl32, config, 4)?;
+/// let config = SensorConfig::default();
+/// let m = export_pcap_to_pcd(
+///     "data/hdl32.pcap", dir.join("seq"), SensorModel::Hdl32, config)?;
+/// assert_eq!(n, m);
+/// for i in 1..=n {
+///     let name = format!("frame_{:05}.pcd", i);
+///     let a = std::fs::read(dir.join("par").join(&name))?;
+///     let b = std::fs::read(dir.join("seq").join(&name))?;
+///     assert!(a == b);
+/// }
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok(()) }
+/// ```
+pub fn export_pcap_to_pcd_parallel<P, Q>(
+    pcap_path: P, out_dir: Q, model: SensorModel, config: SensorConfig,
+    threads: usize,
+) -> io::Result<usize>
+    where P: AsRef<Path>, Q: AsRef<Path>
+{
+    let source = PcapSource::new(pcap_path, false, false)?;
+    let mut turns: TurnIterator<_, _, _, FullPoint> =
+        TurnIterator::for_model(source, model, config)?;
+    turns.skip_partial_first_turn();
+    write_pcd_frames(turns, out_dir, threads)
+}
+
+/// Write frames into PCD files using a pool of `threads` worker threads
+///
+/// Frames are taken from the `frames` iterator on the calling thread (e.g.
+/// from `TurnIterator`) and passed to workers through a bounded queue, so
+/// decoding is paused when workers fall behind. Files are named in the
+/// order of frames as in `export_pcap_to_pcd`, regardless of the order in
+/// which workers complete them. `out_dir` is created if it does not exist.
+///
+/// Processing stops at the first error returned by the iterator or by any
+/// of the workers. Returns number of written frames.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::FullPoint;
+/// use velodyne::export::{write_pcd, write_pcd_frames};
+///
+/// let frames: Vec<Vec<FullPoint>> = (0..20u8)
+///     .map(|i| (0..100u8).map(|j| FullPoint {
+///         xyz: [i as f32, j as f32, 0.5], intensity: j,
+///         ..Default::default()
+///     }).collect())
+///     .collect();
+///
+/// let out_dir = std::env::temp_dir().join("velodyne_pcd_frames_pool");
+/// # let _ = std::fs::remove_dir_all(&out_dir);
+/// let iter = frames.iter().map(|f| Ok(((), f.clone())));
+/// let n = write_pcd_frames(iter, &out_dir, 4)?;
+/// assert_eq!(n, frames.len());
+/// assert_eq!(std::fs::read_dir(&out_dir)?.count(), n);
+/// for (i, frame) in frames.iter().enumerate() {
+///     let path = out_dir.join(format!("frame_{:05}.pcd", i + 1));
+///     let mut expected = vec![];
+///     write_pcd(&mut expected, frame)?;
+///     assert!(std::fs::read(path)? == expected);
+/// }
+///
+/// // errors of the iterator are propagated
+/// let err = std::io::Error::new(std::io::ErrorKind::Other, "broken");
+/// let iter = vec![Ok(((), frames[0].clone())), Err(err)];
+/// assert!(write_pcd_frames(iter, &out_dir, 2).is_err());
+/// # std::fs::remove_dir_all(&out_dir)?;
+/// # Ok(()) }
+/// ```
+pub fn write_pcd_frames<I, S, Q>(frames: I, out_dir: Q, threads: usize)
+    -> io::Result<usize>
+    where I: IntoIterator<Item=io::Result<(S, Vec<FullPoint>)>>,
+        Q: AsRef<Path>
+{
+    let out_dir = out_dir.as_ref();
+    fs::create_dir_all(out_dir)?;
+    let threads = threads.max(1);
+    let (tx, rx) = mpsc::sync_channel::<(usize, Vec<FullPoint>)>(2*threads);
+    let rx = Mutex::new(rx);
+    let failed = AtomicBool::new(false);
+
+    let worker = || -> io::Result<()> {
+        loop {
+            let task = rx.lock().expect("worker has panicked").recv();
+            // channel is closed after the last frame
+            let (n, points) = match task {
+                Ok(task) => task,
+                Err(_) => return Ok(()),
+            };
+            if let Err(err) = write_pcd_frame(out_dir, n, &points) {
+                failed.store(true, Ordering::Relaxed);
+                return Err(err);
+            }
+        }
+    };
+
+    thread::scope(|s| {
+        let workers: Vec<_> = (0..threads).map(|_| s.spawn(worker)).collect();
+        let mut res = Ok(());
+        let mut n = 0;
+        for frame in frames {
+            if failed.load(Ordering::Relaxed) { break; }
+            let points = match frame {
+                Ok((_, points)) => points,
+                Err(err) => {
+                    res = Err(err);
+                    break;
+                },
+            };
+            n += 1;
+            // sending fails only if all workers have exited with an error
+            if tx.send((n, points)).is_err() { break; }
+        }
+        drop(tx);
+        for w in workers {
+            let r = w.join().unwrap_or_else(|e| std::panic::resume_unwind(e));
+            if res.is_ok() { res = r; }
+        }
+        res.map(|()| n)
+    })
+}
@@ -0,0 +1,62 @@
+use core::ops::ControlFlow;
+
+use crate::{Convertor, ConversionError, FullPoint};
+use crate::packet::{RawPacket, RawPoint, PacketMeta};
+
+/// Convertor which passes only points accepted by predicate, usually
+/// created by `Convertor::filter`
+#[derive(Copy, Clone, Debug)]
+pub struct FilteredConvertor<C, F> {
+    inner: C,
+    predicate: F,
+}
+
+impl<C, F> FilteredConvertor<C, F>
+    where C: Convertor, F: Fn(&FullPoint) -> bool
+{
+    /// Create new convertor which passes points produced by `inner` only if
+    /// `predicate` returns `true` for them
+    pub fn new(inner: C, predicate: F) -> Self {
+        Self { inner, predicate }
+    }
+
+    /// Get reference to the inner convertor
+    pub fn get_inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Get mutable reference to the inner convertor
+    pub fn get_inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Destruct filter and return inner convertor
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C, F> Convertor for FilteredConvertor<C, F>
+    where C: Convertor, F: Fn(&FullPoint) -> bool
+{
+    fn laser_count(&self) -> usize {
+        self.inner.laser_count()
+    }
+
+    fn start_turn(&self) {
+        self.inner.start_turn()
+    }
+
+    fn try_convert_with_raw<G>(&self, raw_packet: &RawPacket, mut f: G)
+        -> Result<(PacketMeta, usize), ConversionError>
+        where G: FnMut(FullPoint, RawPoint) -> ControlFlow<()>
+    {
+        let mut n = 0;
+        let (meta, _) = self.inner.try_convert_with_raw(raw_packet, |p, raw| {
+            if !(self.predicate)(&p) { return ControlFlow::Continue(()); }
+            n += 1;
+            f(p, raw)
+        })?;
+        Ok((meta, n))
+    }
+}
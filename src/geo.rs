@@ -0,0 +1,148 @@
+//! Georeferencing of points using GPS position fixes
+//!
+//! [`GeoReferencer`](struct.GeoReferencer.html) converts points from the
+//! sensor frame into local East-North-Up (ENU) frame anchored at the chosen
+//! origin. Position fixes (e.g. from position packets or NMEA messages) should
+//! be fed into it using
+//! [`set_fix(..)`](struct.GeoReferencer.html#method.set_fix) method.
+//!
+//! Note that GPS fix does not contain sensor attitude, so sensor axes are
+//! assumed to be aligned with the local ENU axes: X points to the east, Y to
+//! the north and Z up.
+use crate::FullPoint;
+
+/// WGS84 semi-major axis in meters
+const WGS84_A: f64 = 6_378_137.0;
+/// WGS84 flattening
+const WGS84_F: f64 = 1.0/298.257_223_563;
+
+/// Geodetic position on the WGS84 ellipsoid
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GeoPosition {
+    /// Latitude in degrees
+    pub lat: f64,
+    /// Longitude in degrees
+    pub lon: f64,
+    /// Height above ellipsoid in meters
+    pub alt: f64,
+}
+
+/// Convert geodetic coordinates to Earth-Centered Earth-Fixed coordinates
+pub fn geodetic_to_ecef(pos: GeoPosition) -> [f64; 3] {
+    let e2 = WGS84_F*(2. - WGS84_F);
+    let (lat_sin, lat_cos) = pos.lat.to_radians().sin_cos();
+    let (lon_sin, lon_cos) = pos.lon.to_radians().sin_cos();
+    let n = WGS84_A/(1. - e2*lat_sin*lat_sin).sqrt();
+    [
+        (n + pos.alt)*lat_cos*lon_cos,
+        (n + pos.alt)*lat_cos*lon_sin,
+        (n*(1. - e2) + pos.alt)*lat_sin,
+    ]
+}
+
+/// Converter from the sensor frame to the local ENU frame
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::geo::{GeoPosition, GeoReferencer, geodetic_to_ecef};
+///
+/// let close = |a: [f64; 3], b: [f64; 3], tol: f64| {
+///     a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < tol)
+/// };
+/// // north pole lies on the WGS84 semi-minor axis
+/// let pole = GeoPosition { lat: 90., lon: 0., alt: 0. };
+/// assert!(close(geodetic_to_ecef(pole), [0., 0., 6_356_752.3142], 1e-3));
+///
+/// let origin = GeoPosition::default();
+/// let mut georef = GeoReferencer::new(origin);
+/// assert_eq!(georef.geodetic_to_enu(origin), [0.; 3]);
+/// // at the equator 1° of longitude is 111 319.49 m and 1° of latitude is
+/// // 110 574.28 m, offsets from the origin lie below its tangent plane
+/// let east = GeoPosition { lat: 0., lon: 0.001, alt: 0. };
+/// let north = GeoPosition { lat: 0.001, lon: 0., alt: 0. };
+/// let up = GeoPosition { lat: 0., lon: 0., alt: 10. };
+/// assert!(close(georef.geodetic_to_enu(east), [111.3195, 0., -0.001], 1e-4));
+/// assert!(close(georef.geodetic_to_enu(north), [0., 110.5743, -0.001], 1e-4));
+/// assert!(close(georef.geodetic_to_enu(up), [0., 0., 10.], 1e-6));
+///
+/// let point = FullPoint { xyz: [1., 2., 3.], ..Default::default() };
+/// assert_eq!(georef.georeference(&point), None);
+/// georef.set_fix(east);
+/// let enu = georef.georeference(&point).unwrap();
+/// assert!(close(enu, [112.3195, 2., 2.999], 1e-4));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct GeoReferencer {
+    origin: GeoPosition,
+    origin_ecef: [f64; 3],
+    // rows of ECEF to ENU rotation matrix
+    rot: [[f64; 3]; 3],
+    fix: Option<GeoPosition>,
+    // sensor position in the ENU frame
+    fix_enu: [f64; 3],
+}
+
+impl GeoReferencer {
+    /// Create new georeferencer with ENU frame anchored at `origin`
+    pub fn new(origin: GeoPosition) -> Self {
+        let (lat_sin, lat_cos) = origin.lat.to_radians().sin_cos();
+        let (lon_sin, lon_cos) = origin.lon.to_radians().sin_cos();
+        let rot = [
+            [-lon_sin, lon_cos, 0.],
+            [-lat_sin*lon_cos, -lat_sin*lon_sin, lat_cos],
+            [lat_cos*lon_cos, lat_cos*lon_sin, lat_sin],
+        ];
+        let origin_ecef = geodetic_to_ecef(origin);
+        Self { origin, origin_ecef, rot, fix: None, fix_enu: [0.; 3] }
+    }
+
+    /// Get ENU frame origin
+    pub fn origin(&self) -> GeoPosition {
+        self.origin
+    }
+
+    /// Update current sensor position
+    pub fn set_fix(&mut self, fix: GeoPosition) {
+        self.fix = Some(fix);
+        self.fix_enu = self.geodetic_to_enu(fix);
+    }
+
+    /// Get current sensor position, `None` if no fix was provided
+    pub fn fix(&self) -> Option<GeoPosition> {
+        self.fix
+    }
+
+    /// Convert geodetic position to ENU coordinates in meters
+    pub fn geodetic_to_enu(&self, pos: GeoPosition) -> [f64; 3] {
+        let ecef = geodetic_to_ecef(pos);
+        let o = self.origin_ecef;
+        let d = [ecef[0] - o[0], ecef[1] - o[1], ecef[2] - o[2]];
+        let r = &self.rot;
+        [
+            r[0][0]*d[0] + r[0][1]*d[1] + r[0][2]*d[2],
+            r[1][0]*d[0] + r[1][1]*d[1] + r[1][2]*d[2],
+            r[2][0]*d[0] + r[2][1]*d[1] + r[2][2]*d[2],
+        ]
+    }
+
+    /// Convert sensor frame coordinates to ENU coordinates in meters
+    ///
+    /// Returns `None` if sensor position is unknown.
+    pub fn to_enu(&self, xyz: [f32; 3]) -> Option<[f64; 3]> {
+        self.fix?;
+        let s = self.fix_enu;
+        Some([
+            s[0] + xyz[0] as f64,
+            s[1] + xyz[1] as f64,
+            s[2] + xyz[2] as f64,
+        ])
+    }
+
+    /// Convert point coordinates to ENU coordinates in meters
+    ///
+    /// Returns `None` if sensor position is unknown.
+    pub fn georeference(&self, point: &FullPoint) -> Option<[f64; 3]> {
+        self.to_enu(point.xyz)
+    }
+}
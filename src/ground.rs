@@ -0,0 +1,142 @@
+//! Segmentation of ground points
+//!
+//! Ground is modeled as a single plane fitted using RANSAC, so the method
+//! works best for flat areas around the sensor.
+use crate::FullPoint;
+
+/// Number of RANSAC iterations
+const ITERATIONS: usize = 200;
+
+/// Deterministic xorshift generator used for sampling of plane candidates
+struct XorShift(u32);
+
+impl XorShift {
+    fn next(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]]
+}
+
+/// Compute plane `(n, d)` with unit normal `n` pointing up passing through
+/// `a`, `b` and `c`, points `p` of the plane satisfy `dot(n, p) + d = 0`
+fn plane(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Option<([f32; 3], f32)> {
+    let n = cross(sub(b, a), sub(c, a));
+    let norm = dot(n, n).sqrt();
+    // collinear or coinciding points
+    if norm <= 1e-6 { return None; }
+    let s = if n[2] < 0. { -1./norm } else { 1./norm };
+    let n = [s*n[0], s*n[1], s*n[2]];
+    Some((n, -dot(n, a)))
+}
+
+/// Label points which belong to the ground plane
+///
+/// Plane is fitted using RANSAC, only planes located below the sensor with
+/// normal deviating from the vertical axis by no more than `max_angle`
+/// degrees are considered. Points closer than `max_dist` meters to the plane
+/// with the largest number of such points are labeled as ground. Candidate
+/// planes are sampled using a fixed seed, so results are deterministic.
+///
+/// Returns a mask with `true` for ground points, points with NaN coordinates
+/// (i.e. without return) are never labeled as ground.
+///
+/// Requires `perception` feature.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::ground::segment_ground;
+///
+/// let point = |x, y, z| FullPoint { xyz: [x, y, z], ..Default::default() };
+/// // flat floor 1.8 m below the sensor
+/// let mut points = vec![];
+/// for i in 0..40 {
+///     for j in 0..40 {
+///         let (x, y) = (0.25*i as f32 - 5., 0.25*j as f32 - 5.);
+///         points.push(point(x, y, -1.8));
+///     }
+/// }
+/// let floor = points.len();
+/// // 1x1x1 m box standing on the floor
+/// for i in 0..10 {
+///     for j in 0..10 {
+///         let (a, b) = (0.1*i as f32, 0.1*j as f32);
+///         points.push(point(2. + a, 2. + b, -0.8));
+///         points.push(point(2., 2. + a, -1.7 + b));
+///         points.push(point(2. + a, 2., -1.7 + b));
+///     }
+/// }
+/// points.push(point(f32::NAN, f32::NAN, f32::NAN));
+///
+/// let mask = segment_ground(&points, 10., 0.05);
+/// assert_eq!(mask.len(), points.len());
+/// assert!(mask[..floor].iter().all(|&g| g));
+/// assert!(mask[floor..].iter().all(|&g| !g));
+///
+/// // the floor is too steep for a sensor tilted by 30 degrees
+/// let (sin, cos) = 30f32.to_radians().sin_cos();
+/// let tilted: Vec<FullPoint> = points.iter()
+///     .map(|p| point(cos*p.xyz[0] - sin*p.xyz[2], p.xyz[1],
+///         sin*p.xyz[0] + cos*p.xyz[2]))
+///     .collect();
+/// assert!(segment_ground(&tilted, 10., 0.05).iter().all(|&g| !g));
+/// assert!(segment_ground(&tilted, 35., 0.05)[..floor].iter().all(|&g| g));
+/// ```
+pub fn segment_ground(points: &[FullPoint], max_angle: f32, max_dist: f32)
+    -> Vec<bool>
+{
+    let mut mask = vec![false; points.len()];
+    let valid: Vec<[f32; 3]> = points.iter()
+        .map(|p| p.xyz)
+        .filter(|xyz| !xyz.iter().any(|v| v.is_nan()))
+        .collect();
+    if valid.len() < 3 { return mask; }
+
+    let min_cos = max_angle.to_radians().cos();
+    let is_inlier = |(n, d): ([f32; 3], f32), p: [f32; 3]| {
+        (dot(n, p) + d).abs() <= max_dist
+    };
+    let mut rng = XorShift(0x9E37_79B9);
+    let mut best = None;
+    let mut best_count = 0;
+    for _ in 0..ITERATIONS {
+        let mut sample = || valid[rng.next() as usize % valid.len()];
+        let candidate = match plane(sample(), sample(), sample()) {
+            Some(val) => val,
+            None => continue,
+        };
+        // normal points up, so positive `d` means that sensor origin is
+        // above the plane
+        let (n, d) = candidate;
+        if n[2] < min_cos || d <= 0. { continue; }
+        let count = valid.iter().filter(|&&p| is_inlier(candidate, p)).count();
+        if count > best_count {
+            best = Some(candidate);
+            best_count = count;
+        }
+    }
+
+    if let Some(plane) = best {
+        for (m, p) in mask.iter_mut().zip(points.iter()) {
+            // comparison with NaN distance is always `false`
+            *m = is_inlier(plane, p.xyz);
+        }
+    }
+    mask
+}
@@ -1,6 +1,25 @@
 //! HDL-32E sensor types
-use super::{FullPoint, ConversionError, Convertor};
-use crate::packet::{RawPacket, PacketMeta, parse_packet};
+use core::ops::ControlFlow;
+#[cfg(feature = "std")]
+use std::io;
+use super::{
+    FullPoint, FullPointF64, ConversionError, Convertor, ReturnType,
+    AzimuthScale,
+};
+use crate::math::{sin_cos, sin_cos_f64};
+#[cfg(feature = "std")]
+use crate::packet::PacketSource;
+use crate::packet::{
+    RawPacket, RawPoint, PacketMeta, StatusBytes, get_status,
+    parse_packet_with, dual_return_partners, return_info, is_duplicate_return,
+    BlockHeaders,
+};
+
+/// Model byte reported by HDL-32E in the factory bytes
+pub const MODEL_ID: u8 = 0x21;
+
+/// Block headers used by HDL-32E
+pub const BLOCK_HEADERS: BlockHeaders = BlockHeaders(&[([0xFF, 0xEE], 0)]);
 
 const HDL_32_TABLE: [f32; 32] = [
     -30.67, -9.33, -29.33, -8.00, -28.00, -6.67, -26.67, -5.33,
@@ -9,54 +28,638 @@ const HDL_32_TABLE: [f32; 32] = [
     -14.67,  6.67, -13.33,  8.00, -12.00,  9.33, -10.67, 10.67,
 ];
 
+/// Elevation angles of HDL-32E lasers in degrees indexed by laser ID
+///
+/// # Example
+/// ```
+/// use velodyne::hdl32::ELEVATION_ANGLES;
+///
+/// assert_eq!(ELEVATION_ANGLES[0], -30.67);
+/// assert_eq!(ELEVATION_ANGLES[31], 10.67);
+/// ```
+pub const ELEVATION_ANGLES: [f32; 32] = HDL_32_TABLE;
+
+/// Factory bytes stored in the last two bytes of the HDL-32E packets
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FactoryBytes {
+    /// Return mode which sensor is configured to use
+    pub return_mode: ReturnType,
+    /// Sensor model byte (`0x21` for HDL-32E, `0x22` for VLP-16 and `0x24`
+    /// for Puck Hi-Res)
+    pub model: u8,
+}
+
+impl FactoryBytes {
+    /// Parse factory bytes, returns `None` if return mode or sensor model
+    /// byte is unknown
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::ReturnType;
+    /// use velodyne::hdl32::FactoryBytes;
+    /// use velodyne::packet::StatusBytes;
+    ///
+    /// let parse = |id, value| FactoryBytes::parse(StatusBytes { id, value });
+    /// let modes = [
+    ///     (0x37, ReturnType::Strongest),
+    ///     (0x38, ReturnType::Last),
+    ///     (0x39, ReturnType::Both),
+    /// ];
+    /// for &(id, return_mode) in modes.iter() {
+    ///     let expected = FactoryBytes { return_mode, model: 0x21 };
+    ///     assert_eq!(parse(id, 0x21), Some(expected));
+    /// }
+    /// assert_eq!(parse(0x37, 0x22).unwrap().model, 0x22);
+    /// // unknown return mode
+    /// assert_eq!(parse(0x36, 0x21), None);
+    /// assert_eq!(parse(0x00, 0x21), None);
+    /// // unknown sensor model
+    /// assert_eq!(parse(0x37, 0x00), None);
+    /// assert_eq!(parse(0x37, 0x99), None);
+    /// ```
+    pub fn parse(status: StatusBytes) -> Option<Self> {
+        let return_mode = match status.id {
+            0x37 => ReturnType::Strongest,
+            0x38 => ReturnType::Last,
+            0x39 => ReturnType::Both,
+            _ => return None,
+        };
+        match status.value {
+            0x21 | 0x22 | 0x24 => (),
+            _ => return None,
+        }
+        Some(FactoryBytes { return_mode, model: status.value })
+    }
+}
+
+/// HDL-32E status listener
+///
+/// Unlike HDL-64 listener it does not require any packets for initialization,
+/// status is available after the first packet with valid factory bytes.
+/// Change of the return mode is reported as sensor reconfiguration.
+#[cfg(feature = "std")]
 #[derive(Copy, Clone, Debug, Default)]
+pub struct StatusListener {
+    status: Option<FactoryBytes>,
+    reconfigured: bool,
+}
+
+#[cfg(feature = "std")]
+impl super::StatusListener for StatusListener {
+    type Status = Option<FactoryBytes>;
+
+    fn init<T: PacketSource>(_source: &mut T) -> io::Result<Self> {
+        Ok(Default::default())
+    }
+
+    fn feed(&mut self, status: StatusBytes) {
+        self.feed_updated(status);
+    }
+
+    fn feed_updated(&mut self, status: StatusBytes) -> bool {
+        let new_status = FactoryBytes::parse(status);
+        if new_status.is_some() && new_status != self.status {
+            let mode = |s: Option<FactoryBytes>| s.map(|s| s.return_mode);
+            self.reconfigured |= self.status.is_some()
+                && mode(self.status) != mode(new_status);
+            self.status = new_status;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn get_status(&self) -> &Self::Status { &self.status }
+
+    fn return_type(&self) -> Option<ReturnType> {
+        self.status.map(|s| s.return_mode)
+    }
+
+    fn take_reconfiguration(&mut self) -> bool {
+        std::mem::replace(&mut self.reconfigured, false)
+    }
+}
+
 /// Default HDL-32E convertor from `RawPoint` to `FullPoint`
-pub struct Hdl32Convertor;
+///
+/// If `simd` crate feature is enabled, coordinates of all points in a block
+/// will be computed using SIMD instructions (see `set_simd`).
+///
+/// In the dual return mode returns of a firing are numbered by distance, see
+/// `FullPoint::return_number`.
+///
+/// # Example
+/// ```
+/// use velodyne::{Convertor, FullPoint};
+/// use velodyne::hdl32::Hdl32Convertor;
+///
+/// // dual return packet, laser 0 has the last return at 4 m and the
+/// // strongest return at 2 m, laser 1 has a single return at 3 m
+/// let mut packet = [0u8; 1206];
+/// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+///     let azimuth = 20*(i as u16/2);
+///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///     block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+///     // the first block of a pair contains the last return
+///     let d0: u16 = if i % 2 == 0 { 2000 } else { 1000 };
+///     block[4..6].copy_from_slice(&d0.to_le_bytes());
+///     block[6] = if i % 2 == 0 { 20 } else { 50 };
+///     block[7..9].copy_from_slice(&1500u16.to_le_bytes());
+///     block[9] = 10;
+/// }
+/// packet[1204..].copy_from_slice(&[0x39, 0x21]);
+///
+/// let mut points = vec![];
+/// let convertor = Hdl32Convertor::default();
+/// convertor.convert(&packet, |p: FullPoint| points.push(p)).unwrap();
+/// assert_eq!(points.len(), 6*3);
+/// for pair in points.chunks_exact(3) {
+///     let returns: Vec<_> = pair.iter()
+///         .map(|p| (p.laser_id, p.return_number, p.number_of_returns))
+///         .collect();
+///     assert_eq!(returns, [(0, 2, 2), (1, 1, 1), (0, 1, 2)]);
+///     for (p, d) in pair.iter().zip([4., 3., 2.]) {
+///         assert!((p.range() - d).abs() < 1e-5);
+///     }
+/// }
+/// ```
+///
+/// Coordinates match the conversion of distance and angles to Cartesian
+/// coordinates:
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::Convertor;
+/// use velodyne::hdl32::{Hdl32Convertor, ELEVATION_ANGLES};
+/// use velodyne::packet::{PacketSource, PcapSource};
+///
+/// let convertor = Hdl32Convertor::default();
+/// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let mut n = 0;
+/// for _ in 0..100 {
+///     let (_, packet) = source.next_packet()?.unwrap();
+///     convertor.convert_with_raw(packet, |p, raw| {
+///         let dist = raw.distance as f32/500.;
+///         let (a_sin, a_cos) = (p.azimuth as f32/100.).to_radians().sin_cos();
+///         let elevation = ELEVATION_ANGLES[p.laser_id as usize];
+///         let (w_sin, w_cos) = elevation.to_radians().sin_cos();
+///         let t = dist*w_cos;
+///         let expected = [t*a_sin, t*a_cos, dist*w_sin];
+///         for (a, b) in p.xyz.iter().zip(expected.iter()) {
+///             assert!((a - b).abs() < 1e-4, "{:?} {:?}", p.xyz, expected);
+///         }
+///         n += 1;
+///     }).unwrap();
+/// }
+/// assert!(n > 100*12*16);
+/// # Ok(()) }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Hdl32Convertor {
+    vert_table: [f32; 32],
+    vert_sin: [f32; 32],
+    vert_cos: [f32; 32],
+    keep_no_return: bool,
+    dedup: bool,
+    azimuth_scale: AzimuthScale,
+    #[cfg(feature = "simd")]
+    simd: bool,
+}
 
-impl Convertor for Hdl32Convertor {
-    fn convert<F, P>(&self, raw_packet: &RawPacket, mut f: F)
+impl Default for Hdl32Convertor {
+    fn default() -> Self {
+        Self {
+            vert_table: [0.; 32], vert_sin: [0.; 32], vert_cos: [0.; 32],
+            keep_no_return: false, dedup: true,
+            azimuth_scale: AzimuthScale::HUNDREDTHS,
+            #[cfg(feature = "simd")]
+            simd: true,
+        }.with_vertical_table(HDL_32_TABLE)
+    }
+}
+
+impl Hdl32Convertor {
+    /// Use the given elevation angles of lasers in degrees indexed by laser
+    /// ID instead of the default `ELEVATION_ANGLES`
+    ///
+    /// It can be used to apply per-unit corrections of the nominal angles.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl32::{Hdl32Convertor, ELEVATION_ANGLES};
+    ///
+    /// // 10 m return of every laser
+    /// let mut packet = [0u8; 1206];
+    /// for block in packet[..1200].chunks_exact_mut(100) {
+    ///     block[..4].copy_from_slice(&[0xFF, 0xEE, 0x10, 0x27]);
+    ///     for point in block[4..].chunks_exact_mut(3) {
+    ///         point[..2].copy_from_slice(&5000u16.to_le_bytes());
+    ///     }
+    /// }
+    /// let elevation = |c: &Hdl32Convertor, laser: u8| {
+    ///     let mut res = None;
+    ///     c.convert(&packet, |p: FullPoint| if p.laser_id == laser {
+    ///         res = Some((p.xyz[2]/10.).asin().to_degrees());
+    ///     }).unwrap();
+    ///     res.unwrap()
+    /// };
+    ///
+    /// let mut table = ELEVATION_ANGLES;
+    /// table[5] = -7.;
+    /// let default = Hdl32Convertor::default();
+    /// let corrected = Hdl32Convertor::default().with_vertical_table(table);
+    /// assert!((elevation(&default, 5) + 6.67).abs() < 1e-3);
+    /// assert!((elevation(&corrected, 5) + 7.).abs() < 1e-3);
+    /// assert_eq!(elevation(&default, 6), elevation(&corrected, 6));
+    /// assert_eq!(corrected.get_vertical_table()[5], -7.);
+    /// ```
+    pub fn with_vertical_table(mut self, table: [f32; 32]) -> Self {
+        for (i, a) in table.iter().enumerate() {
+            let (sin, cos) = sin_cos(a.to_radians());
+            self.vert_sin[i] = sin;
+            self.vert_cos[i] = cos;
+        }
+        self.vert_table = table;
+        self
+    }
+
+    /// Get elevation angles of lasers in degrees used by the convertor
+    pub fn get_vertical_table(&self) -> &[f32; 32] {
+        &self.vert_table
+    }
+
+    /// Set whether points without return (i.e. with zero distance) should
+    /// be kept, by default such points are skipped.
+    ///
+    /// Kept points have NaN coordinates and zero `return_number` and
+    /// `number_of_returns`, so every packet is converted to the full grid of
+    /// 12 blocks by 32 lasers. Duplicated returns in the dual return mode
+    /// are still removed.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let mut convertor = Hdl32Convertor::default();
+    /// convertor.set_keep_no_return(true);
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// let mut points = vec![];
+    /// convertor.convert(packet, |p: FullPoint| points.push(p)).unwrap();
+    /// assert_eq!(points.len(), 12*32);
+    /// assert!(points.iter().any(|p| p.xyz[0].is_nan()));
+    /// # Ok(()) }
+    /// ```
+    pub fn set_keep_no_return(&mut self, keep: bool) {
+        self.keep_no_return = keep;
+    }
+
+    /// Get whether points without return are kept
+    pub fn get_keep_no_return(&self) -> bool {
+        self.keep_no_return
+    }
+
+    /// Set whether duplicated returns should be removed, enabled by default.
+    ///
+    /// In the dual return mode firing with a single return is reported in
+    /// both blocks of the pair, with enabled deduplication only the first
+    /// copy is converted. If the strongest return is equal to the last one,
+    /// the second block contains the next strongest return, which is kept
+    /// even if its distance is equal to the last return. Deduplication is
+    /// also skipped if factory bytes indicate single return mode.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    ///
+    /// // two blocks with the same azimuth and a single return of laser 0
+    /// let mut packet = [0u8; 1206];
+    /// for block in packet[..200].chunks_exact_mut(100) {
+    ///     block[..4].copy_from_slice(&[0xFF, 0xEE, 0x10, 0x27]);
+    ///     block[4..6].copy_from_slice(&1000u16.to_le_bytes());
+    /// }
+    /// for block in packet[200..1200].chunks_exact_mut(100) {
+    ///     block[..4].copy_from_slice(&[0xFF, 0xEE, 0x20, 0x4E]);
+    /// }
+    ///
+    /// let mut convertor = Hdl32Convertor::default();
+    /// let mut n = 0;
+    /// convertor.convert(&packet, |_: FullPoint| n += 1).unwrap();
+    /// assert_eq!(n, 1);
+    ///
+    /// convertor.set_dedup(false);
+    /// let mut n = 0;
+    /// convertor.convert(&packet, |_: FullPoint| n += 1).unwrap();
+    /// assert_eq!(n, 2);
+    /// ```
+    pub fn set_dedup(&mut self, enabled: bool) {
+        self.dedup = enabled;
+    }
+
+    /// Get whether duplicated returns are removed
+    pub fn get_dedup(&self) -> bool {
+        self.dedup
+    }
+
+    /// Set whether coordinates should be computed using SIMD instructions,
+    /// enabled by default.
+    ///
+    /// Disabling it allows to use the scalar computation, e.g. to compare
+    /// results or performance of the both variants. Requires `simd` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let simd = Hdl32Convertor::default();
+    /// let mut scalar = Hdl32Convertor::default();
+    /// scalar.set_simd(false);
+    /// assert!(simd.get_simd() && !scalar.get_simd());
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let mut n = 0;
+    /// while let Some((_, packet)) = source.next_packet()? {
+    ///     let mut a: Vec<FullPoint> = vec![];
+    ///     let mut b: Vec<FullPoint> = vec![];
+    ///     simd.convert(packet, |p| a.push(p)).unwrap();
+    ///     scalar.convert(packet, |p| b.push(p)).unwrap();
+    ///     assert_eq!(a.len(), b.len());
+    ///     for (p, q) in a.iter().zip(b.iter()) {
+    ///         // both variants perform exactly the same operations
+    ///         assert_eq!(p.xyz, q.xyz);
+    ///         assert_eq!((p.laser_id, p.azimuth), (q.laser_id, q.azimuth));
+    ///     }
+    ///     n += a.len();
+    /// }
+    /// assert!(n > 1000);
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "simd")]
+    pub fn set_simd(&mut self, enabled: bool) {
+        self.simd = enabled;
+    }
+
+    /// Get whether coordinates are computed using SIMD instructions.
+    /// Requires `simd` feature.
+    #[cfg(feature = "simd")]
+    pub fn get_simd(&self) -> bool {
+        self.simd
+    }
+
+    /// Set units of azimuths reported by the sensor, by default hundredths
+    /// of degree are used
+    ///
+    /// Azimuths of produced points and packets are always reported in
+    /// hundredths of degree, blocks with azimuth outside of the full turn
+    /// result in `ConversionError::InvalidAzimuth`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{AzimuthScale, Azimuth, Convertor, FullPoint};
+    /// use velodyne::{TurnIterator, DummyStatusListener};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits packets with a single 10 m point, azimuth of blocks grows by
+    /// /// 1 degree starting from 0.5 degree, full turn contains `units`
+    /// struct Synthetic { packet: RawPacket, n: u32, units: u32 }
+    ///
+    /// impl PacketSource for Synthetic {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 80 { return Ok(None); }
+    ///         let step = self.units/360;
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let a = step/2 + step*(12*self.n + i as u32);
+    ///             let a = (a % self.units) as u16;
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&a.to_le_bytes());
+    ///         }
+    ///         self.packet[4..6].copy_from_slice(&5000u16.to_le_bytes());
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// // the same stream in tenths and in hundredths of degree
+    /// let tenths = Synthetic { packet: [0; 1206], n: 0, units: 3600 };
+    /// let hundredths = Synthetic { packet: [0; 1206], n: 0, units: 36000 };
+    ///
+    /// let mut convertor = Hdl32Convertor::default();
+    /// convertor.set_azimuth_scale(AzimuthScale::new(3600).unwrap());
+    /// assert_eq!(convertor.get_azimuth_scale().units(), 3600);
+    /// let iter: TurnIterator<_, _, DummyStatusListener, FullPoint> =
+    ///     TurnIterator::new(tenths, convertor).unwrap();
+    /// let turns: Vec<_> = iter.map(|r| r.unwrap().1).collect();
+    ///
+    /// let iter: TurnIterator<_, _, DummyStatusListener, FullPoint> =
+    ///     TurnIterator::new(hundredths, Hdl32Convertor::default()).unwrap();
+    /// let expected: Vec<_> = iter.map(|r| r.unwrap().1).collect();
+    ///
+    /// // 80 packets cover 960 degrees, incomplete last turn is discarded
+    /// assert_eq!(turns.len(), 2);
+    /// assert_eq!(turns.len(), expected.len());
+    /// for (turn, exp) in turns.iter().zip(expected.iter()) {
+    ///     assert_eq!(turn.len(), exp.len());
+    ///     for (p, e) in turn.iter().zip(exp.iter()) {
+    ///         assert_eq!(p.azimuth, e.azimuth);
+    ///         for i in 0..3 { assert!((p.xyz[i] - e.xyz[i]).abs() < 1e-5); }
+    ///     }
+    /// }
+    /// assert_eq!(turns[0][1].azimuth, 1250);
+    ///
+    /// // azimuth 3600 is outside of the full turn
+    /// let mut packet = [0u8; 1206];
+    /// for block in packet[..1200].chunks_exact_mut(100) {
+    ///     block[..4].copy_from_slice(&[0xFF, 0xEE, 0x10, 0x0E]);
+    /// }
+    /// let mut convertor = Hdl32Convertor::default();
+    /// assert!(convertor.convert(&packet, |_: FullPoint| ()).is_ok());
+    /// convertor.set_azimuth_scale(AzimuthScale::new(3600).unwrap());
+    /// assert!(convertor.convert(&packet, |_: FullPoint| ()).is_err());
+    /// ```
+    pub fn set_azimuth_scale(&mut self, scale: AzimuthScale) {
+        self.azimuth_scale = scale;
+    }
+
+    /// Get units of azimuths reported by the sensor
+    pub fn get_azimuth_scale(&self) -> AzimuthScale {
+        self.azimuth_scale
+    }
+
+    /// Convert packet into points with coordinates computed in double
+    /// precision
+    ///
+    /// Elevation angles are taken from the vertical table (`ELEVATION_ANGLES`
+    /// by default, see `with_vertical_table`), other point fields
+    /// and set of produced points are the same as for `Convertor::convert`.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPointF64};
+    /// use velodyne::hdl32::{Hdl32Convertor, ELEVATION_ANGLES};
+    ///
+    /// let mut packet = [0u8; 1206];
+    /// for block in packet[..1200].chunks_exact_mut(100) {
+    ///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///     block[2..4].copy_from_slice(&12345u16.to_le_bytes());
+    /// }
+    /// // 130 m point measured by the first laser
+    /// packet[4..6].copy_from_slice(&65000u16.to_le_bytes());
+    /// packet[6] = 10;
+    ///
+    /// let convertor = Hdl32Convertor::default();
+    /// let mut p64 = FullPointF64::default();
+    /// convertor.convert_f64(&packet, |p| p64 = p).unwrap();
+    /// let mut p32 = FullPointF64::default();
+    /// convertor.convert(&packet, |p: FullPointF64| p32 = p).unwrap();
+    ///
+    /// let (a_sin, a_cos) = 123.45f64.to_radians().sin_cos();
+    /// let (w_sin, w_cos) = (ELEVATION_ANGLES[0] as f64).to_radians().sin_cos();
+    /// let d = 130.;
+    /// let expected = [d*w_cos*a_sin, d*w_cos*a_cos, d*w_sin];
+    /// let err = |p: FullPointF64| p.xyz.iter().zip(expected.iter())
+    ///     .map(|(a, b)| (a - b).abs())
+    ///     .fold(0., f64::max);
+    /// assert!(err(p64) < 1e-9);
+    /// assert!(err(p32) > 1e3*err(p64));
+    /// ```
+    pub fn convert_f64<F>(&self, raw_packet: &RawPacket, mut f: F)
         -> Result<PacketMeta, ConversionError>
-        where F: FnMut(P), P: From<FullPoint>
+        where F: FnMut(FullPointF64)
     {
-        let (meta, iter) = parse_packet(raw_packet);
+        let res = self.try_convert_with_raw(raw_packet, |p, raw_point| {
+            let mut point = FullPointF64::from(p);
+            if raw_point.distance != 0 {
+                let dist = raw_point.distance as f64/500.;
+                let azimuth = (p.azimuth as f64/100.).to_radians();
+                let (a_sin, a_cos) = sin_cos_f64(azimuth);
+                let elevation = self.vert_table[p.laser_id as usize] as f64;
+                let (w_sin, w_cos) = sin_cos_f64(elevation.to_radians());
+                let t = dist*w_cos;
+                point.xyz = [t*a_sin, t*a_cos, dist*w_sin];
+            }
+            f(point);
+            ControlFlow::Continue(())
+        });
+        res.map(|(meta, _)| meta)
+    }
+}
+
+impl Convertor for Hdl32Convertor {
+    fn laser_count(&self) -> usize {
+        HDL_32_TABLE.len()
+    }
+
+    fn try_convert_with_raw<F>(&self, raw_packet: &RawPacket, mut f: F)
+        -> Result<(PacketMeta, usize), ConversionError>
+        where F: FnMut(FullPoint, RawPoint) -> ControlFlow<()>
+    {
+        let (mut meta, iter) =
+            parse_packet_with(raw_packet, self.keep_no_return);
+        let scale = self.azimuth_scale;
+        meta.azimuth = scale.to_raw_hundredths(meta.azimuth)
+            .ok_or(ConversionError::InvalidAzimuth)?;
         let timestamp = meta.timestamp;
-        let mut cache = [0u16; 32];
-        let mut prev_azimuth = std::u16::MAX;
+        // duplicated returns are possible only in the dual return mode,
+        // if factory bytes are unknown we assume that it can be enabled
+        let dedup = self.dedup && FactoryBytes::parse(get_status(raw_packet))
+            .map_or(true, |f| f.return_mode == ReturnType::Both);
+        let mut n = 0;
+
+        let partners = dual_return_partners(raw_packet);
+
+        for (block, (header, raw_azimuth, block_iter)) in iter.enumerate() {
+            // all lasers are in the same bank
+            if BLOCK_HEADERS.laser_offset(header).is_none() {
+                return Err(ConversionError::InvalidHeader);
+            }
+            let a = scale.to_hundredths(raw_azimuth)
+                .ok_or(ConversionError::InvalidAzimuth)?;
+            let azim_sin_cos = sin_cos((a/100.).to_radians());
+            let azimuth = scale.to_raw_hundredths(raw_azimuth)
+                .ok_or(ConversionError::InvalidAzimuth)?;
+            let scalar_xyz = |raw_point: RawPoint| {
+                let distance = (raw_point.distance as f32)/500.;
+                let l = raw_point.laser as usize;
+                let vert_sin_cos = (self.vert_sin[l], self.vert_cos[l]);
+                compute_xyz(distance, azim_sin_cos, vert_sin_cos)
+            };
+
+            #[cfg(feature = "simd")]
+            let (block_iter, block_xyz) = {
+                let mut points = [None; 32];
+                let mut dists = [0f32; 32];
+                for p in block_iter {
+                    dists[p.laser as usize] = (p.distance as f32)/500.;
+                    points[p.laser as usize] = Some(p);
+                }
+                let xyz = if self.simd {
+                    Some(simd::block_xyz(
+                        &dists, azim_sin_cos, &self.vert_sin, &self.vert_cos))
+                } else {
+                    None
+                };
+                (IntoIterator::into_iter(points).flatten(), xyz)
+            };
 
-        for (header, azimuth, block_iter) in iter {
-            let azim_sin_cos = (azimuth as f32/100.).to_radians().sin_cos();
-            if &header != b"\xFF\xEE" { Err(ConversionError)? }
             for raw_point in block_iter {
                 let laser_id = raw_point.laser;
+                let no_return = raw_point.distance == 0;
 
                 // filter points for double-return mode
-                let cached = &mut cache[laser_id as usize];
-                if azimuth == prev_azimuth && *cached == raw_point.distance {
-                    *cached = 0;
-                    continue
+                if dedup && is_duplicate_return(
+                    raw_packet, block, partners[block], raw_point,
+                ) {
+                    continue;
                 }
-                *cached = raw_point.distance;
 
-                let distance = (raw_point.distance as f32)/500.;
-                let hor_angle = HDL_32_TABLE[laser_id as usize].to_radians();
-
-                let xyz = compute_xyz(distance, azim_sin_cos, hor_angle);
+                #[cfg(feature = "simd")]
+                let xyz = match block_xyz {
+                    Some(xyz) => xyz[laser_id as usize],
+                    None => scalar_xyz(raw_point),
+                };
+                #[cfg(not(feature = "simd"))]
+                let xyz = scalar_xyz(raw_point);
 
+                let xyz = if no_return { [f32::NAN; 3] } else { xyz };
                 let intensity = raw_point.intensity;
 
+                let (return_number, number_of_returns) = if no_return {
+                    (0, 0)
+                } else {
+                    return_info(raw_packet, block, partners[block], raw_point)
+                };
+
                 //  TODO: add timestamp deltas
-                let point = FullPoint { xyz, intensity, laser_id, timestamp };
-                f(point.into());
+                let point = FullPoint {
+                    xyz, intensity, intensity16: intensity as u16,
+                    laser_id, timestamp,
+                    return_number, number_of_returns, azimuth, sensor_id: 0,
+                };
+                n += 1;
+                if f(point, raw_point).is_break() { return Ok((meta, n)); }
             }
-            prev_azimuth = azimuth;
         }
-        Ok(meta)
+        Ok((meta, n))
     }
 }
 
-fn compute_xyz(dist: f32, (a_sin, a_cos): (f32, f32), w: f32) -> [f32; 3] {
-    // TODO: use precomputed table
-    let (w_sin, w_cos) = w.sin_cos();
+fn compute_xyz(
+    dist: f32, (a_sin, a_cos): (f32, f32), (w_sin, w_cos): (f32, f32),
+) -> [f32; 3] {
     let t = dist*w_cos;
     [
         t*a_sin,
@@ -64,3 +667,35 @@ fn compute_xyz(dist: f32, (a_sin, a_cos): (f32, f32), w: f32) -> [f32; 3] {
         dist*w_sin,
     ]
 }
+
+#[cfg(feature = "simd")]
+mod simd {
+    use wide::f32x8;
+
+    /// Compute coordinates of all points in a block, performs exactly the same
+    /// operations as `compute_xyz`, so results are identical.
+    pub(super) fn block_xyz(
+        dists: &[f32; 32], (a_sin, a_cos): (f32, f32),
+        vert_sin: &[f32; 32], vert_cos: &[f32; 32],
+    ) -> [[f32; 3]; 32] {
+        let a_sin = f32x8::splat(a_sin);
+        let a_cos = f32x8::splat(a_cos);
+        let mut res = [[0f32; 3]; 32];
+        for i in (0..32).step_by(8) {
+            let load = |a: &[f32; 32]| {
+                let mut buf = [0f32; 8];
+                buf.copy_from_slice(&a[i..i + 8]);
+                f32x8::from(buf)
+            };
+            let dist = load(dists);
+            let t = dist*load(vert_cos);
+            let x = (t*a_sin).to_array();
+            let y = (t*a_cos).to_array();
+            let z = (dist*load(vert_sin)).to_array();
+            for j in 0..8 {
+                res[i + j] = [x[j], y[j], z[j]];
+            }
+        }
+        res
+    }
+}
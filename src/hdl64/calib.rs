@@ -1,27 +1,94 @@
-use std::{fmt, mem};
+use core::{array, fmt};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write, BufReader, BufWriter};
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+
+use crate::math::asin;
+
+/// Magic bytes at the start of calibration snapshot files
+#[cfg(feature = "std")]
+const SNAPSHOT_MAGIC: &[u8; 8] = b"HDL64CDB";
+/// Version of the snapshot format
+#[cfg(feature = "std")]
+const SNAPSHOT_VERSION: u32 = 1;
 
 /// Laser calibration data
+///
+/// Distances are measured in centimeters regardless of the table source
+/// (sensor or XML file) and sensor hardware variant.
 #[derive(Default, Clone, Debug)]
 pub struct LaserCalib {
+    /// Minimum raw intensity
     pub min_intensity: u8,
+    /// Maximum raw intensity
     pub max_intensity: u8,
 
+    /// Sine of the rotational correction angle
     pub rot_corr_sin: f32,
+    /// Cosine of the rotational correction angle
     pub rot_corr_cos: f32,
+    /// Sine of the vertical correction (elevation) angle
     pub vert_corr_sin: f32,
+    /// Cosine of the vertical correction (elevation) angle
     pub vert_corr_cos: f32,
 
+    /// Distance correction in centimeters
     pub dist_correction: f32,
+    /// Distance correction at 2.4 m along the X axis in centimeters
     pub dist_corr_x: f32,
+    /// Distance correction at 1.93 m along the Y axis in centimeters
     pub dist_corr_y: f32,
+    /// Vertical offset of the laser in centimeters
     pub vert_offset: f32,
+    /// Horizontal offset of the laser in centimeters
     pub horiz_offset: f32,
+    /// Focal distance used for intensity calibration in centimeters
     pub focal_dist: f32,
+    /// Focal slope used for intensity calibration
     pub focal_slope: f32,
 
     //pub color: (f32, f32, f32),
 }
 
+/// Difference in a single calibration field found by `CalibDb::diff`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LaserDiff {
+    /// Laser ID or `None` for fields common for all lasers (i.e. `dist_lsb`)
+    pub laser_id: Option<u8>,
+    /// Name of the field
+    pub field: &'static str,
+    /// Field value in the compared table
+    pub this: f32,
+    /// Field value in the table it was compared with
+    pub other: f32,
+}
+
+#[cfg(feature = "std")]
+type FieldGetter = fn(&LaserCalib) -> f32;
+
+/// Accessors of the compared `LaserCalib` fields
+#[cfg(feature = "std")]
+const LASER_FIELDS: [(&str, FieldGetter); 13] = [
+    ("min_intensity", |l| l.min_intensity as f32),
+    ("max_intensity", |l| l.max_intensity as f32),
+    ("rot_corr_sin", |l| l.rot_corr_sin),
+    ("rot_corr_cos", |l| l.rot_corr_cos),
+    ("vert_corr_sin", |l| l.vert_corr_sin),
+    ("vert_corr_cos", |l| l.vert_corr_cos),
+    ("dist_correction", |l| l.dist_correction),
+    ("dist_corr_x", |l| l.dist_corr_x),
+    ("dist_corr_y", |l| l.dist_corr_y),
+    ("vert_offset", |l| l.vert_offset),
+    ("horiz_offset", |l| l.horiz_offset),
+    ("focal_dist", |l| l.focal_dist),
+    ("focal_slope", |l| l.focal_slope),
+];
+
 /// Sensor calibration data
 #[derive(Clone)]
 pub struct CalibDb {
@@ -31,20 +98,205 @@ pub struct CalibDb {
 
 impl Default for CalibDb {
     fn default() -> Self {
-        let mut lasers: [LaserCalib; 64] = unsafe { mem::uninitialized() };
-        for l in lasers.iter_mut() { *l = Default::default(); }
+        let lasers = array::from_fn(|_| LaserCalib::default());
         CalibDb {dist_lsb: 0., lasers }
     }
 }
 
+impl CalibDb {
+    /// Get elevation angles of lasers in degrees indexed by laser ID
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::hdl64::CalibDb;
+    ///
+    /// let mut db = CalibDb::default();
+    /// for (i, l) in db.lasers.iter_mut().enumerate() {
+    ///     let a = (i as f32 - 40.)/2.;
+    ///     l.vert_corr_sin = a.to_radians().sin();
+    ///     l.vert_corr_cos = a.to_radians().cos();
+    /// }
+    /// let angles = db.elevation_angles();
+    /// assert!((angles[0] + 20.).abs() < 1e-4);
+    /// assert!((angles[63] - 11.5).abs() < 1e-4);
+    /// for (a, l) in angles.iter().zip(db.lasers.iter()) {
+    ///     assert!((a.to_radians().sin() - l.vert_corr_sin).abs() < 1e-6);
+    /// }
+    /// ```
+    pub fn elevation_angles(&self) -> [f32; 64] {
+        array::from_fn(|i| asin(self.lasers[i].vert_corr_sin).to_degrees())
+    }
+
+    /// Find fields which differ from `other` by more than `tolerance`
+    ///
+    /// Differences are reported in the order of laser IDs, `dist_lsb`
+    /// difference goes first. NaN values are considered different from any
+    /// other value.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::hdl64::{CalibDb, LaserDiff};
+    ///
+    /// let mut a = CalibDb::default();
+    /// a.dist_lsb = 0.2;
+    /// let mut b = a.clone();
+    /// b.lasers[5].vert_offset = 21.5;
+    /// b.lasers[7].focal_dist = 1e-4;
+    ///
+    /// let diff = a.diff(&b, 1e-3);
+    /// assert_eq!(diff, [LaserDiff {
+    ///     laser_id: Some(5), field: "vert_offset", this: 0., other: 21.5,
+    /// }]);
+    /// assert!(a.diff(&a, 0.).is_empty());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn diff(&self, other: &CalibDb, tolerance: f32) -> Vec<LaserDiff> {
+        let differs = |a: f32, b: f32| {
+            let d = (a - b).abs();
+            d.is_nan() || d > tolerance
+        };
+        let mut res = vec![];
+        if differs(self.dist_lsb, other.dist_lsb) {
+            res.push(LaserDiff {
+                laser_id: None, field: "dist_lsb",
+                this: self.dist_lsb, other: other.dist_lsb,
+            });
+        }
+        let lasers = self.lasers.iter().zip(other.lasers.iter());
+        for (i, (l1, l2)) in lasers.enumerate() {
+            for &(field, get) in LASER_FIELDS.iter() {
+                let (this, other) = (get(l1), get(l2));
+                if differs(this, other) {
+                    let laser_id = Some(i as u8);
+                    res.push(LaserDiff { laser_id, field, this, other });
+                }
+            }
+        }
+        res
+    }
+
+    /// Write calibration table into file at `path` in the binary snapshot
+    /// format, see `write_snapshot`
+    ///
+    /// Requires `std` feature.
+    #[cfg(feature = "std")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_snapshot(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Read calibration table from file written by `save`
+    ///
+    /// Requires `std` feature.
+    #[cfg(feature = "std")]
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_snapshot(BufReader::new(File::open(path)?))
+    }
+
+    /// Write calibration table in the binary snapshot format
+    ///
+    /// Snapshot starts with 8 magic bytes `HDL64CDB` and format version
+    /// followed by `dist_lsb` and fields of all lasers in the order of
+    /// `LaserCalib` declaration. All values are stored in little-endian
+    /// byte order without any loss of precision, so loaded table is
+    /// identical to the saved one.
+    ///
+    /// Requires `std` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::hdl64::CalibDb;
+    ///
+    /// let mut db = CalibDb::default();
+    /// db.dist_lsb = 0.2;
+    /// db.lasers[3].focal_slope = 1.3;
+    /// let mut buf = vec![];
+    /// db.write_snapshot(&mut buf).unwrap();
+    ///
+    /// let loaded = CalibDb::read_snapshot(&buf[..]).unwrap();
+    /// assert!(db.diff(&loaded, 0.).is_empty());
+    /// assert!(CalibDb::read_snapshot(&buf[1..]).is_err());
+    /// assert!(CalibDb::read_snapshot(&buf[..buf.len() - 1]).is_err());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn write_snapshot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_u32::<LE>(SNAPSHOT_VERSION)?;
+        writer.write_f32::<LE>(self.dist_lsb)?;
+        for l in self.lasers.iter() {
+            writer.write_u8(l.min_intensity)?;
+            writer.write_u8(l.max_intensity)?;
+            let values = [
+                l.rot_corr_sin, l.rot_corr_cos,
+                l.vert_corr_sin, l.vert_corr_cos,
+                l.dist_correction, l.dist_corr_x, l.dist_corr_y,
+                l.vert_offset, l.horiz_offset,
+                l.focal_dist, l.focal_slope,
+            ];
+            for &v in values.iter() {
+                writer.write_f32::<LE>(v)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read calibration table in the format written by `write_snapshot`
+    ///
+    /// Requires `std` feature.
+    #[cfg(feature = "std")]
+    pub fn read_snapshot<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "invalid calibration snapshot magic"));
+        }
+        if reader.read_u32::<LE>()? != SNAPSHOT_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "unsupported calibration snapshot version"));
+        }
+        let mut db = CalibDb {
+            dist_lsb: reader.read_f32::<LE>()?,
+            ..Default::default()
+        };
+        for l in db.lasers.iter_mut() {
+            let min_intensity = reader.read_u8()?;
+            let max_intensity = reader.read_u8()?;
+            let mut values = [0f32; 11];
+            reader.read_f32_into::<LE>(&mut values)?;
+            let [
+                rot_corr_sin, rot_corr_cos,
+                vert_corr_sin, vert_corr_cos,
+                dist_correction, dist_corr_x, dist_corr_y,
+                vert_offset, horiz_offset,
+                focal_dist, focal_slope,
+            ] = values;
+            *l = LaserCalib {
+                min_intensity, max_intensity,
+                rot_corr_sin, rot_corr_cos, vert_corr_sin, vert_corr_cos,
+                dist_correction, dist_corr_x, dist_corr_y,
+                vert_offset, horiz_offset, focal_dist, focal_slope,
+            };
+        }
+        let mut rest = [0u8; 1];
+        if reader.read(&mut rest)? != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "unexpected data after calibration snapshot"));
+        }
+        Ok(db)
+    }
+}
+
 impl fmt::Debug for CalibDb {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "CalibDb")?;
         write!(f, "\tI_max\tI_min\ta_rot\ta_vert\tdist\t")?;
         writeln!(f, "d_x\td_y\tv_off\th_off\tf_dist\tf_slope")?;
+        let elevations = self.elevation_angles();
         for (i, l) in self.lasers.iter().enumerate() {
-            let a_rot = l.rot_corr_sin.asin().to_degrees();
-            let a_vert = l.vert_corr_sin.asin().to_degrees();
+            let a_rot = asin(l.rot_corr_sin).to_degrees();
+            let a_vert = elevations[i];
             writeln!(f,
                 "{}\t{}\t{}\t{:.2}\t{:.2}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
                 i, l.min_intensity, l.max_intensity, a_rot, a_vert,
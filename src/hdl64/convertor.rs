@@ -1,27 +1,41 @@
-use super::super::{FullPoint, ConversionError, Convertor};
+use core::ops::ControlFlow;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::warn;
+use super::super::{FullPoint, ConversionError, Convertor, AzimuthScale};
 use super::{CalibDb, LaserCalib};
-use crate::packet::{RawPacket, PacketMeta, parse_packet};
+use crate::math::{sin_cos, round};
+use crate::packet::{
+    RawPacket, RawPoint, PacketMeta, parse_packet_with, dual_return_partners,
+    return_info, is_duplicate_return, block_header, BlockHeaders, BLOCKS,
+};
 
+/// Number of microseconds in an hour, timestamps wrap at this value
+const HOUR_US: u32 = 3_600_000_000;
+
+/// Block headers used by HDL-64, upper and lower banks contain lasers 0-31
+/// and 32-63 respectively
+pub const BLOCK_HEADERS: BlockHeaders = BlockHeaders(&[
+    ([0xFF, 0xEE], 0),
+    ([0xFF, 0xDD], 32),
+]);
+
+/// Compute calibrated intensity, result is not clamped to any range
 #[inline(always)]
-fn calib_intensity(intens: u8, raw_distance: u16, calib: &LaserCalib) -> u8 {
+fn calib_intensity(intens: u8, raw_distance: u16, calib: &LaserCalib) -> f32 {
     let t1 = 1. - calib.focal_dist/13_100.;
     let t2 = 1. - (raw_distance as f32)/65_535.;
     let t3 = t1*t1 - t2*t2;
     let intens = intens.saturating_sub(calib.min_intensity) as f32;
-    let res = intens + 256.*calib.focal_slope*t3.abs();
-    match res {
-        r if r > 255. => 255,
-        r if r < 0. => 0,
-        r => r as u8,
-    }
+    intens + 256.*calib.focal_slope*t3.abs()
 }
 
 // azimuth in radians
 // distance is already multiplied by lsb
 #[inline(always)]
-fn compute_xyz(distance: f32, azim_sin_cos: (f32, f32), calib: &LaserCalib)
-    -> [f32; 3]
-{
+fn compute_xyz(
+    distance: f32, azim_sin_cos: (f32, f32), calib: &LaserCalib,
+    mode: DistanceCorrectionMode,
+) -> [f32; 3] {
     let cal_distance = distance + calib.dist_correction;
 
     let (sin, cos) = azim_sin_cos;
@@ -33,7 +47,12 @@ fn compute_xyz(distance: f32, azim_sin_cos: (f32, f32), calib: &LaserCalib)
         calib.vert_offset * calib.vert_corr_sin;
     let xx = (xy_dist * sin - calib.horiz_offset * cos).abs();
     let yy = (xy_dist * cos + calib.horiz_offset * sin).abs();
-    let (d_corr_x, d_corr_y) = if cal_distance > 2500. {
+    let blend = match mode {
+        DistanceCorrectionMode::TwoPoint => cal_distance <= 2500.,
+        DistanceCorrectionMode::Linear => true,
+        DistanceCorrectionMode::Constant => false,
+    };
+    let (d_corr_x, d_corr_y) = if !blend {
         (calib.dist_correction, calib.dist_correction)
     } else {
         let dx = calib.dist_correction - calib.dist_corr_x;
@@ -58,62 +77,541 @@ fn compute_xyz(distance: f32, azim_sin_cos: (f32, f32), calib: &LaserCalib)
     [x/100., y/100., z/100.]
 }
 
+/// Number of calibrated intensities clamped to the `[0, 255]` range
+///
+/// Frequent clamping indicates bad focal distance or focal slope calibration
+/// parameters.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClampStats {
+    /// Number of intensities clamped to 0
+    pub low: u64,
+    /// Number of intensities clamped to 255
+    pub high: u64,
+}
+
+/// Heuristic filter of the retroreflector blooming artifacts
+///
+/// Points farther than `distance` with calibrated intensity greater than
+/// `intensity` are dropped.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BloomFilter {
+    /// Distance limit in meters
+    pub distance: f32,
+    /// Calibrated intensity threshold
+    pub intensity: u8,
+}
+
+/// Model of the distance correction applied to HDL-64 points
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DistanceCorrectionMode {
+    /// Corrections along X and Y axes are linearly blended between
+    /// `dist_corr_x`/`dist_corr_y` and `dist_correction` for points closer
+    /// than 25 m, beyond it constant `dist_correction` is used (default)
+    #[default]
+    TwoPoint,
+    /// Blended corrections are used on the full range
+    Linear,
+    /// Constant `dist_correction` is used on the full range
+    Constant,
+}
+
+/// Timing of HDL-64 firings used for computation of point timestamps
+///
+/// Packet contains consecutive firings, each of them is reported as a block
+/// of the upper bank (`0xFFEE` header) followed by a block of the lower bank
+/// (`0xFFDD` header), in the dual return mode all blocks of a firing share
+/// its time. Upper bank lasers are fired at the start of the firing, lower
+/// bank lasers are fired `lower_block_offset` later.
+///
+/// Default values are nominal: 6 firings are evenly distributed over the
+/// packet period of 288 µs and, as stated in the HDL-64E user's manuals,
+/// upper and lower banks are fired simultaneously, so `lower_block_offset` is
+/// zero. Timing may differ between firmware versions, so it should be
+/// verified for the used sensor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FiringTiming {
+    /// Time between starts of consecutive firings in microseconds
+    pub firing_period: f32,
+    /// Delay of the lower bank firing relative to the upper bank firing in
+    /// microseconds
+    pub lower_block_offset: f32,
+}
+
+impl Default for FiringTiming {
+    fn default() -> Self {
+        Self { firing_period: 48., lower_block_offset: 0. }
+    }
+}
+
+/// Compute index of the firing reported by each block of the packet
+fn firing_indices(data: &RawPacket, partners: &[Option<usize>; BLOCKS])
+    -> [u8; BLOCKS]
+{
+    let mut res = [0u8; BLOCKS];
+    let mut upper_blocks = 0;
+    for block in 0..BLOCKS {
+        res[block] = match partners[block] {
+            // other return of the already seen firing
+            Some(p) if p < block => res[p],
+            _ if BLOCK_HEADERS.laser_offset(block_header(data, block))
+                == Some(0) =>
+            {
+                upper_blocks += 1;
+                upper_blocks - 1
+            },
+            // lower block belongs to the firing of the preceding upper block
+            _ => upper_blocks.saturating_sub(1),
+        };
+    }
+    res
+}
+
 /// HDL-64 convertor from `RawPoint` to `FullPoint`
 pub struct Hdl64Convertor {
     pub(crate) db: CalibDb,
+    keep_no_return: bool,
+    dedup: bool,
+    bloom_filter: Option<BloomFilter>,
+    dist_corr_mode: DistanceCorrectionMode,
+    azimuth_scale: AzimuthScale,
+    firing_timing: Option<FiringTiming>,
+    // counters are updated only when clamping happens,
+    // so they do not affect performance of the conversion
+    clamped_low: AtomicUsize,
+    clamped_high: AtomicUsize,
 }
 
 impl Hdl64Convertor {
-    pub fn new(db: CalibDb) -> Self { Self { db } }
+    /// Create new convertor using calibration table `db`
+    ///
+    /// Logs warning if distance LSB is not positive, as all points will be
+    /// collapsed into the origin (or get invalid coordinates) in this case.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use log::{Level, LevelFilter, Log, Metadata, Record};
+    /// use velodyne::hdl64::{CalibDb, Hdl64Convertor};
+    ///
+    /// /// Counts warnings about distance LSB
+    /// struct Capture(AtomicUsize);
+    ///
+    /// impl Log for Capture {
+    ///     fn enabled(&self, _: &Metadata) -> bool { true }
+    ///     fn log(&self, record: &Record) {
+    ///         let msg = record.args().to_string();
+    ///         if record.level() == Level::Warn && msg.contains("LSB") {
+    ///             self.0.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static CAPTURE: Capture = Capture(AtomicUsize::new(0));
+    /// log::set_logger(&CAPTURE).unwrap();
+    /// log::set_max_level(LevelFilter::Warn);
+    ///
+    /// let mut db = CalibDb::default();
+    /// db.dist_lsb = 0.2;
+    /// Hdl64Convertor::new(db.clone());
+    /// assert_eq!(CAPTURE.0.load(Ordering::Relaxed), 0);
+    /// for &lsb in [0., -0.2, f32::NAN].iter() {
+    ///     db.dist_lsb = lsb;
+    ///     Hdl64Convertor::new(db.clone());
+    /// }
+    /// assert_eq!(CAPTURE.0.load(Ordering::Relaxed), 3);
+    /// ```
+    pub fn new(db: CalibDb) -> Self {
+        if db.dist_lsb.is_nan() || db.dist_lsb <= 0. {
+            warn!("invalid distance LSB in calibration table: {}, \
+                all converted points will have invalid coordinates",
+                db.dist_lsb);
+        }
+        Self {
+            db, keep_no_return: false, dedup: true, bloom_filter: None,
+            dist_corr_mode: Default::default(),
+            azimuth_scale: AzimuthScale::HUNDREDTHS, firing_timing: None,
+            clamped_low: AtomicUsize::new(0),
+            clamped_high: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set whether points without return (i.e. with zero distance) should
+    /// be kept, by default such points are skipped.
+    ///
+    /// Kept points have NaN coordinates and zero `return_number` and
+    /// `number_of_returns`, so every packet is converted to the full grid of
+    /// 12 blocks by 32 lasers.
+    pub fn set_keep_no_return(&mut self, keep: bool) {
+        self.keep_no_return = keep;
+    }
+
+    /// Get whether points without return are kept
+    pub fn get_keep_no_return(&self) -> bool {
+        self.keep_no_return
+    }
+
+    /// Set units of azimuths reported by the sensor, by default hundredths
+    /// of degree are used, see `Hdl32Convertor::set_azimuth_scale`
+    pub fn set_azimuth_scale(&mut self, scale: AzimuthScale) {
+        self.azimuth_scale = scale;
+    }
+
+    /// Get units of azimuths reported by the sensor
+    pub fn get_azimuth_scale(&self) -> AzimuthScale {
+        self.azimuth_scale
+    }
+
+    /// Set whether duplicated returns should be removed, enabled by default.
+    ///
+    /// In the dual return mode firing with a single return is reported in
+    /// both blocks of the pair, with enabled deduplication only the first
+    /// copy is converted. If the strongest return is equal to the last one,
+    /// the second block contains the next strongest return, which is kept
+    /// even if its distance is equal to the last return.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl64::{CalibDb, Hdl64Convertor};
+    ///
+    /// let mut db = CalibDb::default();
+    /// db.dist_lsb = 0.2;
+    /// let convertor = Hdl64Convertor::new(db);
+    ///
+    /// // dual return mode: blocks with the same header and azimuth are paired
+    /// let mut packet = [0u8; 1206];
+    /// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+    ///     let header = if i % 2 == 0 { 0xEE } else { 0xDD };
+    ///     block[..4].copy_from_slice(&[0xFF, header, 0x10, 0x27]);
+    /// }
+    /// // laser 0 of the first pair has a single return reported twice
+    /// packet[4..7].copy_from_slice(&[0xE8, 0x03, 100]);
+    /// packet[204..207].copy_from_slice(&[0xE8, 0x03, 100]);
+    /// // laser 1 has the next strongest return at the same distance
+    /// packet[7..10].copy_from_slice(&[0xD0, 0x07, 100]);
+    /// packet[207..210].copy_from_slice(&[0xD0, 0x07, 50]);
+    ///
+    /// let mut points = vec![];
+    /// convertor.convert(&packet, |p: FullPoint| points.push(p)).unwrap();
+    /// let returns: Vec<_> = points.iter()
+    ///     .map(|p| (p.laser_id, p.return_number, p.number_of_returns))
+    ///     .collect();
+    /// // the first block of a pair contains the last return
+    /// assert_eq!(returns, [(0, 1, 1), (1, 2, 2), (1, 1, 2)]);
+    /// ```
+    pub fn set_dedup(&mut self, enabled: bool) {
+        self.dedup = enabled;
+    }
+
+    /// Get whether duplicated returns are removed
+    pub fn get_dedup(&self) -> bool {
+        self.dedup
+    }
+
+    /// Set filter of the blooming artifacts, by default filter is disabled
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl64::{CalibDb, Hdl64Convertor, BloomFilter};
+    ///
+    /// let mut db = CalibDb::default();
+    /// db.dist_lsb = 0.2;
+    /// let mut convertor = Hdl64Convertor::new(db);
+    /// convertor.set_bloom_filter(Some(BloomFilter {
+    ///     distance: 30., intensity: 200,
+    /// }));
+    ///
+    /// let mut packet = [0u8; 1206];
+    /// for block in packet[..1200].chunks_exact_mut(100) {
+    ///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    /// }
+    /// // bloom point: 50 m and maximum intensity
+    /// packet[4..7].copy_from_slice(&[0xA8, 0x61, 255]);
+    /// // the same distance, but low intensity
+    /// packet[7..10].copy_from_slice(&[0xA8, 0x61, 10]);
+    /// // close point with maximum intensity
+    /// packet[10..13].copy_from_slice(&[0xE8, 0x03, 255]);
+    ///
+    /// let mut points = vec![];
+    /// convertor.convert(&packet, |p: FullPoint| points.push(p)).unwrap();
+    /// let lasers: Vec<u8> = points.iter().map(|p| p.laser_id).collect();
+    /// assert_eq!(lasers, [1, 2]);
+    /// ```
+    pub fn set_bloom_filter(&mut self, filter: Option<BloomFilter>) {
+        self.bloom_filter = filter;
+    }
+
+    /// Get filter of the blooming artifacts
+    pub fn get_bloom_filter(&self) -> Option<BloomFilter> {
+        self.bloom_filter
+    }
+
+    /// Set model of the distance correction, by default
+    /// `DistanceCorrectionMode::TwoPoint` is used
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl64::{CalibDb, Hdl64Convertor, DistanceCorrectionMode};
+    ///
+    /// let mut db = CalibDb::default();
+    /// db.dist_lsb = 0.2;
+    /// for laser in db.lasers.iter_mut() {
+    ///     laser.rot_corr_cos = 1.;
+    ///     laser.vert_corr_cos = 1.;
+    ///     laser.dist_correction = 100.;
+    ///     laser.dist_corr_x = 120.;
+    ///     laser.dist_corr_y = 120.;
+    /// }
+    /// let mut convertor = Hdl64Convertor::new(db);
+    ///
+    /// let mut packet = [0u8; 1206];
+    /// for block in packet[..1200].chunks_exact_mut(100) {
+    ///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    /// }
+    /// // 50 m point at zero azimuth (i.e. pointing along Y axis)
+    /// packet[4..7].copy_from_slice(&[0xA8, 0x61, 10]);
+    ///
+    /// let mut get_y = |mode| {
+    ///     convertor.set_distance_correction_mode(mode);
+    ///     let mut y = 0.;
+    ///     convertor.convert(&packet, |p: FullPoint| y = p.xyz[1]).unwrap();
+    ///     y
+    /// };
+    /// let two_point = get_y(DistanceCorrectionMode::TwoPoint);
+    /// let linear = get_y(DistanceCorrectionMode::Linear);
+    /// let constant = get_y(DistanceCorrectionMode::Constant);
+    /// // far points use the constant correction by default
+    /// assert_eq!(two_point, constant);
+    /// assert!((constant - 51.).abs() < 1e-4);
+    /// // blended correction is extrapolated beyond 25 m
+    /// assert!(linear < constant);
+    /// ```
+    pub fn set_distance_correction_mode(&mut self, mode: DistanceCorrectionMode) {
+        self.dist_corr_mode = mode;
+    }
+
+    /// Get model of the distance correction
+    pub fn get_distance_correction_mode(&self) -> DistanceCorrectionMode {
+        self.dist_corr_mode
+    }
+
+    /// Set timing of firings used for computation of point timestamps,
+    /// disabled by default
+    ///
+    /// If timing is set, point timestamps are equal to the packet timestamp
+    /// plus time of the point firing computed using `timing`, so points of the
+    /// upper and lower banks in the same firing have different timestamps.
+    /// Otherwise all points get the packet timestamp.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl64::{CalibDb, Hdl64Convertor, FiringTiming};
+    ///
+    /// // single return packet with one 10 m point in each block
+    /// let mut packet = [0u8; 1206];
+    /// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+    ///     let header = if i % 2 == 0 { 0xEE } else { 0xDD };
+    ///     let azimuth = 10_000 + 20*(i as u16/2);
+    ///     block[..2].copy_from_slice(&[0xFF, header]);
+    ///     block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///     block[4..6].copy_from_slice(&5000u16.to_le_bytes());
+    /// }
+    /// packet[1200..1204].copy_from_slice(&1_000u32.to_le_bytes());
+    ///
+    /// let mut db = CalibDb::default();
+    /// db.dist_lsb = 0.2;
+    /// let mut convertor = Hdl64Convertor::new(db);
+    /// let mut t = vec![];
+    /// convertor.convert(&packet, |p: FullPoint| t.push(p.timestamp)).unwrap();
+    /// assert!(t.iter().all(|&t| t == 1_000));
+    ///
+    /// // by default banks are fired simultaneously, 48 µs apart
+    /// convertor.set_firing_timing(Some(FiringTiming::default()));
+    /// let mut points = vec![];
+    /// convertor.convert(&packet, |p: FullPoint| points.push(p)).unwrap();
+    /// // upper and lower points of the first firing
+    /// assert_eq!((points[0].laser_id, points[1].laser_id), (0, 32));
+    /// assert_eq!((points[0].timestamp, points[1].timestamp), (1_000, 1_000));
+    /// // the next firing
+    /// assert_eq!((points[2].timestamp, points[3].timestamp), (1_048, 1_048));
+    /// assert_eq!(points[11].timestamp, 1_000 + 5*48);
+    ///
+    /// // lower bank delayed by 10 µs
+    /// let timing = FiringTiming {
+    ///     firing_period: 48., lower_block_offset: 10.,
+    /// };
+    /// convertor.set_firing_timing(Some(timing));
+    /// let mut points = vec![];
+    /// convertor.convert(&packet, |p: FullPoint| points.push(p)).unwrap();
+    /// assert_eq!((points[0].timestamp, points[1].timestamp), (1_000, 1_010));
+    /// assert_eq!((points[2].timestamp, points[3].timestamp), (1_048, 1_058));
+    /// ```
+    pub fn set_firing_timing(&mut self, timing: Option<FiringTiming>) {
+        self.firing_timing = timing;
+    }
+
+    /// Get timing of firings used for computation of point timestamps
+    pub fn get_firing_timing(&self) -> Option<FiringTiming> {
+        self.firing_timing
+    }
+
+    /// Get number of clamped intensities since convertor creation or the
+    /// last call to `reset_clamp_stats`
+    ///
+    /// `TurnIterator` resets counters at the start of each turn, so they
+    /// cover only the last returned turn (see `TurnIterator::clamp_stats`).
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl64::{CalibDb, Hdl64Convertor};
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let mut db = CalibDb::default();
+    /// db.dist_lsb = 0.2;
+    /// for laser in db.lasers.iter_mut() {
+    ///     // huge focal slope saturates all intensities
+    ///     laser.focal_slope = 100.;
+    /// }
+    /// let convertor = Hdl64Convertor::new(db);
+    ///
+    /// let mut source = PcapSource::new("data/hdl64.pcap", false, false)?;
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// let mut n = 0;
+    /// convertor.convert(packet, |p: FullPoint| {
+    ///     assert_eq!(p.intensity, 255);
+    ///     n += 1;
+    /// }).unwrap();
+    /// let stats = convertor.clamp_stats();
+    /// assert_eq!((stats.low, stats.high), (0, n));
+    ///
+    /// convertor.reset_clamp_stats();
+    /// assert_eq!(convertor.clamp_stats().high, 0);
+    /// # Ok(()) }
+    /// ```
+    pub fn clamp_stats(&self) -> ClampStats {
+        ClampStats {
+            low: self.clamped_low.load(Ordering::Relaxed) as u64,
+            high: self.clamped_high.load(Ordering::Relaxed) as u64,
+        }
+    }
+
+    /// Reset clamped intensity counters, e.g. at the start of each turn
+    pub fn reset_clamp_stats(&self) {
+        self.clamped_low.store(0, Ordering::Relaxed);
+        self.clamped_high.store(0, Ordering::Relaxed);
+    }
 }
 
 
-impl<'a> Convertor for Hdl64Convertor {
-    fn convert<F, P>(&self, raw_packet: &RawPacket, mut f: F)
-        -> Result<PacketMeta, ConversionError>
-        where F: FnMut(P), P: From<FullPoint>
+impl Convertor for Hdl64Convertor {
+    fn laser_count(&self) -> usize {
+        self.db.lasers.len()
+    }
+
+    fn start_turn(&self) {
+        self.reset_clamp_stats();
+    }
+
+    fn try_convert_with_raw<F>(&self, raw_packet: &RawPacket, mut f: F)
+        -> Result<(PacketMeta, usize), ConversionError>
+        where F: FnMut(FullPoint, RawPoint) -> ControlFlow<()>
     {
-        let (meta, iter) = parse_packet(raw_packet);
+        let (mut meta, iter) =
+            parse_packet_with(raw_packet, self.keep_no_return);
+        let scale = self.azimuth_scale;
+        meta.azimuth = scale.to_raw_hundredths(meta.azimuth)
+            .ok_or(ConversionError::InvalidAzimuth)?;
         let timestamp = meta.timestamp;
 
-        let mut cache = [0u16; 64];
-        let mut prev_azimuth = std::u16::MAX;
+        let mut n = 0;
+
+        let partners = dual_return_partners(raw_packet);
+        let firings = firing_indices(raw_packet, &partners);
 
-        for (header, azimuth, block_iter) in iter {
-            let azim_sin_cos = (azimuth as f32/100.).to_radians().sin_cos();
-            let laser_delta = match &header {
-                b"\xFF\xEE" => 0,
-                b"\xFF\xDD" => 32,
-                _ => return Err(ConversionError),
+        for (block, (header, raw_azimuth, block_iter)) in iter.enumerate() {
+            let laser_delta = BLOCK_HEADERS.laser_offset(header)
+                .ok_or(ConversionError::InvalidHeader)?;
+            let timestamp = match self.firing_timing {
+                Some(timing) => {
+                    let mut dt = firings[block] as f32*timing.firing_period;
+                    if laser_delta != 0 { dt += timing.lower_block_offset; }
+                    ((timestamp as u64 + round(dt) as u64)
+                        % HOUR_US as u64) as u32
+                },
+                None => timestamp,
             };
+            let a = scale.to_hundredths(raw_azimuth)
+                .ok_or(ConversionError::InvalidAzimuth)?;
+            let azim_sin_cos = sin_cos((a/100.).to_radians());
+            let azimuth = scale.to_raw_hundredths(raw_azimuth)
+                .ok_or(ConversionError::InvalidAzimuth)?;
             for raw_point in block_iter {
                 let laser_id = raw_point.laser + laser_delta;
+                let no_return = raw_point.distance == 0;
 
                 // filter points for double-return mode
-                let cached = &mut cache[laser_id as usize];
-                if azimuth == prev_azimuth && *cached == raw_point.distance {
-                    *cached = 0;
-                    continue
+                if self.dedup && is_duplicate_return(
+                    raw_packet, block, partners[block], raw_point,
+                ) {
+                    continue;
                 }
-                *cached = raw_point.distance;
 
                 let distance = raw_point.distance as f32 * self.db.dist_lsb;
                 let calib = &self.db.lasers[laser_id as usize];
 
-                let xyz = compute_xyz(distance, azim_sin_cos, calib);
+                let xyz = if no_return {
+                    [f32::NAN; 3]
+                } else {
+                    let mode = self.dist_corr_mode;
+                    compute_xyz(distance, azim_sin_cos, calib, mode)
+                };
 
-                let intensity = calib_intensity(
+                let calibrated = calib_intensity(
                     raw_point.intensity,
                     raw_point.distance,
                     calib,
                 );
+                let intensity = match calibrated {
+                    r if r > 255. => {
+                        self.clamped_high.fetch_add(1, Ordering::Relaxed);
+                        255
+                    },
+                    r if r < 0. => {
+                        self.clamped_low.fetch_add(1, Ordering::Relaxed);
+                        0
+                    },
+                    r => r as u8,
+                };
+                let intensity16 = calibrated.clamp(0., 65_535.) as u16;
+
+                if let Some(bf) = self.bloom_filter {
+                    // distance is measured in centimeters
+                    if distance > 100.*bf.distance && intensity > bf.intensity {
+                        continue;
+                    }
+                }
+
+                let (return_number, number_of_returns) = if no_return {
+                    (0, 0)
+                } else {
+                    return_info(raw_packet, block, partners[block], raw_point)
+                };
 
                 //  TODO: add timestamp deltas
-                let point = FullPoint { xyz, intensity, laser_id, timestamp };
-                f(point.into());
+                let point = FullPoint {
+                    xyz, intensity, intensity16, laser_id, timestamp,
+                    return_number, number_of_returns, azimuth, sensor_id: 0,
+                };
+                n += 1;
+                if f(point, raw_point).is_break() { return Ok((meta, n)); }
             }
-            prev_azimuth = azimuth;
         }
-        Ok(meta)
+        Ok((meta, n))
     }
 }
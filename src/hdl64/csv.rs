@@ -0,0 +1,199 @@
+use std::path::Path;
+
+use csv::{ReaderBuilder, StringRecord, Trim};
+
+use super::CalibDb;
+
+/// Distance LSB in centimeters used if table does not have `distLSB` column
+const DEFAULT_DIST_LSB: f32 = 0.2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Column {
+    Id,
+    RotCorr,
+    VertCorr,
+    DistCorr,
+    DistCorrX,
+    DistCorrY,
+    VertOffset,
+    HorizOffset,
+    FocalDist,
+    FocalSlope,
+    MinIntensity,
+    MaxIntensity,
+    DistLsb,
+}
+
+const COLUMNS: usize = 13;
+
+/// Map header name to column
+///
+/// Names are compared ignoring case and non-alphanumeric characters, both
+/// VeloView names (e.g. `vertOffsetCorrection`) and names of `LaserCalib`
+/// fields (e.g. `vert_offset`) are accepted.
+fn parse_header(name: &str) -> Option<Column> {
+    let name: String = name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    Some(match name.as_str() {
+        "id" | "laserid" => Column::Id,
+        "rotcorrection" | "rotcorr" => Column::RotCorr,
+        "vertcorrection" | "vertcorr" => Column::VertCorr,
+        "distcorrection" => Column::DistCorr,
+        "distcorrectionx" | "distcorrx" => Column::DistCorrX,
+        "distcorrectiony" | "distcorry" => Column::DistCorrY,
+        "vertoffsetcorrection" | "vertoffset" => Column::VertOffset,
+        "horizoffsetcorrection" | "horizoffset" => Column::HorizOffset,
+        "focaldistance" | "focaldist" => Column::FocalDist,
+        "focalslope" => Column::FocalSlope,
+        "minintensity" => Column::MinIntensity,
+        "maxintensity" => Column::MaxIntensity,
+        "distlsb" => Column::DistLsb,
+        _ => return None,
+    })
+}
+
+/// Positions of the known columns in the table
+struct Layout([Option<usize>; COLUMNS]);
+
+impl Layout {
+    fn new(headers: &StringRecord) -> Result<Self, &'static str> {
+        let mut pos = [None; COLUMNS];
+        for (i, name) in headers.iter().enumerate() {
+            // unknown columns (e.g. laser colors) are ignored
+            let col = match parse_header(name) {
+                Some(col) => col,
+                None => continue,
+            };
+            if pos[col as usize].replace(i).is_some() {
+                return Err("Duplicated column in CSV header");
+            }
+        }
+        // only `distLSB` column is optional
+        if pos[..Column::DistLsb as usize].iter().any(|p| p.is_none()) {
+            return Err("Missing column in CSV header");
+        }
+        Ok(Self(pos))
+    }
+
+    fn get<'a>(&self, record: &'a StringRecord, col: Column)
+        -> Option<&'a str>
+    {
+        record.get(self.0[col as usize]?)
+    }
+
+    fn parse<T: std::str::FromStr>(
+        &self, record: &StringRecord, col: Column, err: &'static str,
+    ) -> Result<T, &'static str> {
+        self.get(record, col)
+            .ok_or("Missing value in CSV row")?
+            .parse().map_err(|_| err)
+    }
+}
+
+/// Read calibration table from CSV file and parse data into `CalibDb` struct
+///
+/// File must contain a header row followed by one row per laser, for example
+/// as exported by VeloView. Columns are matched by their header names, so
+/// their order does not matter, unknown columns are ignored. Rotational and
+/// vertical corrections are expected in degrees, other values use the same
+/// units as in the XML table read by `read_db`. All 64 lasers must be present
+/// exactly once. If table does not have `distLSB` column, distance LSB is set
+/// to 0.2 cm.
+///
+/// Requires `csv` feature.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::PointSource;
+/// use velodyne::hdl64::read_db_csv;
+/// use velodyne::packet::PcapSource;
+///
+/// // the file contains the same table as `hdl64_db.xml` with reordered
+/// // columns and rows
+/// let db = read_db_csv("data/hdl64_db.csv").unwrap();
+/// assert_eq!(db.dist_lsb, 0.2);
+/// let laser = &db.lasers[0];
+/// assert_eq!(laser.vert_offset, 21.5);
+/// assert_eq!(laser.dist_corr_x, 129.5);
+/// assert_eq!(laser.focal_dist, 2100.);
+/// assert_eq!(laser.max_intensity, 255);
+/// let sin = |deg: f32| deg.to_radians().sin();
+/// assert!((laser.vert_corr_sin - sin(-7.07)).abs() < 1e-6);
+/// assert!((laser.rot_corr_sin - sin(-4.4)).abs() < 1e-6);
+///
+/// let source = PcapSource::new("data/hdl64.pcap", false, false)?;
+/// let sensor_db = PointSource::hdl64_init(source)?.get_calib_db();
+/// assert!(db.diff(&sensor_db, 1e-4).is_empty());
+/// # Ok(()) }
+/// ```
+pub fn read_db_csv<P: AsRef<Path>>(path: P)
+    -> Result<CalibDb, &'static str>
+{
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_path(path)
+        .map_err(|_| "DB file not found")?;
+    let headers = reader.headers().map_err(|_| "Failed to read CSV header")?;
+    let layout = Layout::new(headers)?;
+
+    let mut db = CalibDb { dist_lsb: DEFAULT_DIST_LSB, ..Default::default() };
+    let mut dist_lsb = None;
+    let mut found = [false; 64];
+    for record in reader.records() {
+        let record = record.map_err(|_| "Failed to read CSV row")?;
+        let i: usize = layout.parse(&record, Column::Id,
+            "Failed to parse laser id")?;
+        let seen = found.get_mut(i).ok_or("Laser id is out of range")?;
+        if *seen { return Err("Duplicated laser id"); }
+        *seen = true;
+
+        let laser = &mut db.lasers[i];
+        let val: f32 = layout.parse(&record, Column::RotCorr,
+            "Failed to parse rot_correction")?;
+        let (sin, cos) = val.to_radians().sin_cos();
+        laser.rot_corr_sin = sin;
+        laser.rot_corr_cos = cos;
+        let val: f32 = layout.parse(&record, Column::VertCorr,
+            "Failed to parse vert_correction")?;
+        let (sin, cos) = val.to_radians().sin_cos();
+        laser.vert_corr_sin = sin;
+        laser.vert_corr_cos = cos;
+
+        laser.dist_correction = layout.parse(&record, Column::DistCorr,
+            "Failed to parse dist_correction")?;
+        laser.dist_corr_x = layout.parse(&record, Column::DistCorrX,
+            "Failed to parse dist_correction_x")?;
+        laser.dist_corr_y = layout.parse(&record, Column::DistCorrY,
+            "Failed to parse dist_correction_y")?;
+        laser.vert_offset = layout.parse(&record, Column::VertOffset,
+            "Failed to parse vert_offset_corr")?;
+        laser.horiz_offset = layout.parse(&record, Column::HorizOffset,
+            "Failed to parse horiz_offset_corr")?;
+        laser.focal_dist = layout.parse(&record, Column::FocalDist,
+            "Failed to parse focal_dist")?;
+        laser.focal_slope = layout.parse(&record, Column::FocalSlope,
+            "Failed to parse focal_slope")?;
+        laser.min_intensity = layout.parse(&record, Column::MinIntensity,
+            "Failed to parse min_intensity")?;
+        laser.max_intensity = layout.parse(&record, Column::MaxIntensity,
+            "Failed to parse max_intensity")?;
+
+        if layout.0[Column::DistLsb as usize].is_some() {
+            let val: f32 = layout.parse(&record, Column::DistLsb,
+                "Failed to parse dist_lsb")?;
+            // value is common for all lasers
+            match dist_lsb {
+                Some(v) if v != val => return Err("Inconsistent dist_lsb"),
+                _ => dist_lsb = Some(val),
+            }
+        }
+    }
+    if found.iter().any(|&f| !f) {
+        return Err("Missing laser in CSV table");
+    }
+    if let Some(v) = dist_lsb { db.dist_lsb = v; }
+    Ok(db)
+}
@@ -2,17 +2,34 @@
 //!
 //! If you want to read `CalibDb` from XML file, enable `xml` crate feature.
 //! This will add `read_db` function to this module.
+//! Similarly `csv` feature adds `read_db_csv` function for CSV tables.
+#[cfg(feature = "std")]
 mod status;
+#[cfg(feature = "std")]
 mod status_accum;
+#[cfg(feature = "std")]
 mod status_types;
 mod calib;
 mod convertor;
 #[cfg(feature = "xml")]
 mod xml;
+#[cfg(feature = "csv")]
+mod csv;
 
+#[cfg(feature = "std")]
 pub use self::status_types::*;
+#[cfg(feature = "std")]
 pub use self::status::StatusListener;
-pub use self::convertor::Hdl64Convertor;
-pub use self::calib::{CalibDb, LaserCalib};
+#[cfg(feature = "std")]
+pub use self::status_accum::RAW_CALIB_SIZE;
+#[cfg(not(feature = "std"))]
+pub use crate::ReturnType;
+pub use self::convertor::{
+    Hdl64Convertor, ClampStats, BloomFilter, DistanceCorrectionMode,
+    FiringTiming, BLOCK_HEADERS,
+};
+pub use self::calib::{CalibDb, LaserCalib, LaserDiff};
 #[cfg(feature = "xml")]
 pub use self::xml::read_db;
+#[cfg(feature = "csv")]
+pub use self::csv::read_db_csv;
@@ -18,23 +18,70 @@
 //! [`feed(&packet.status)`](struct.StatusListener.html#method.feed)
 //! method by passing packet's status into it.
 use crate::packet::{PacketSource, StatusBytes};
+use crate::Azimuth;
+use crate::azimuth::FULL_TURN;
 use std::io;
 
 use super::calib::CalibDb;
 
-use super::Status;
-use super::status_accum::StatusAccumulator;
+use super::{Status, Hdl64Variant, ReturnType};
+use super::status_accum::{StatusAccumulator, RAW_CALIB_SIZE};
 
 /// HDL-64 status listener
 ///
 /// Minimum number of statuses for successful initialization equals to 4160
 /// (~ 1 s). This number can be significantly higher if some packets are lost,
 /// as full sequence of 4160 status bytes is required for initialization.
+///
+/// Malformed status bytes never cause panics, listener logs a warning, discards
+/// partially accumulated data and resynchronizes on the following cycles.
+///
+/// Change of the return type or change of the rotation speed by more than 5%
+/// between full status cycles is reported as sensor reconfiguration.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::StatusListener;
+/// use velodyne::hdl64;
+/// use velodyne::packet::{PacketSource, PcapSource, StatusBytes, get_status};
+///
+/// let mut source = PcapSource::new("data/hdl64.pcap", false, true)?;
+/// let mut listener = hdl64::StatusListener::init(&mut source)?;
+/// let calib_db = listener.get_calib_db(0.2);
+///
+/// // feed pseudo-random garbage
+/// let mut state = 12345u32;
+/// for _ in 0..200_000 {
+///     state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+///     let [id, value, ..] = (state >> 8).to_le_bytes();
+///     listener.feed(StatusBytes { id, value });
+/// }
+///
+/// // listener recovers on valid data
+/// let mut updated = false;
+/// while !updated {
+///     let (_, packet) = source.next_packet()?.unwrap();
+///     updated = listener.feed_updated(get_status(packet));
+/// }
+/// let new_db = listener.get_calib_db(0.2);
+/// assert_eq!(new_db.lasers[5].focal_slope, calib_db.lasers[5].focal_slope);
+/// # Ok(()) }
+/// ```
 pub struct StatusListener {
     status: Status,
     calib_db: CalibDb,
 
     accum: StatusAccumulator,
+    // return type and RPM reported by the last full status cycle
+    config: (ReturnType, u16),
+    reconfigured: bool,
+}
+
+/// Returns `true` if sensor settings in status `b` differ from `a`
+fn is_reconfigured(a: (ReturnType, u16), b: (ReturnType, u16)) -> bool {
+    let rpm_diff = (a.1 as i32 - b.1 as i32).abs();
+    a.0 != b.0 || 20*rpm_diff > a.1 as i32
 }
 
 impl StatusListener {
@@ -44,6 +91,49 @@ impl StatusListener {
         calib_db.dist_lsb = dist_lsb;
         calib_db
     }
+
+    /// Set hardware variant used for decoding of the calibration data, by
+    /// default `Hdl64Variant::S3` is used
+    ///
+    /// Already received calibration data is decoded again, so variant can be
+    /// changed after initialization.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::StatusListener;
+    /// use velodyne::hdl64::{self, Hdl64Variant};
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let mut source = PcapSource::new("data/hdl64.pcap", false, false)?;
+    /// let mut listener = hdl64::StatusListener::init(&mut source)?;
+    /// let s3 = listener.get_calib_db(0.2);
+    /// listener.set_variant(Hdl64Variant::S2);
+    /// assert_eq!(listener.get_variant(), Hdl64Variant::S2);
+    /// let s2 = listener.get_calib_db(0.2);
+    /// for (l2, l3) in s2.lasers.iter().zip(s3.lasers.iter()) {
+    ///     assert_eq!(l2.vert_corr_sin, l3.vert_corr_sin);
+    ///     assert_eq!(l2.focal_slope, l3.focal_slope);
+    ///     // S2 transmits distances with 10 times finer resolution
+    ///     assert!((10.*l2.dist_correction - l3.dist_correction).abs() < 1e-3);
+    ///     assert!((10.*l2.vert_offset - l3.vert_offset).abs() < 1e-3);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn set_variant(&mut self, variant: Hdl64Variant) {
+        self.accum.set_variant(variant, &mut self.calib_db);
+    }
+
+    /// Get hardware variant used for decoding of the calibration data
+    pub fn get_variant(&self) -> Hdl64Variant {
+        self.accum.get_variant()
+    }
+
+    /// Get raw calibration data received from the sensor, which can be
+    /// decoded using `CalibDb::from_raw_bytes`
+    pub fn get_raw_calib(&self) -> &[u8; RAW_CALIB_SIZE] {
+        self.accum.get_raw_calib()
+    }
 }
 
 impl super::super::StatusListener for StatusListener {
@@ -52,16 +142,48 @@ impl super::super::StatusListener for StatusListener {
     fn init<T: PacketSource>(packet_source: &mut T) -> io::Result<Self> {
         let mut accum = StatusAccumulator::default();
         let (status, calib_db) = accum.init(packet_source)?;
-        Ok(StatusListener { status: status, calib_db: calib_db, accum: accum })
+        let config = (status.return_type, status.rpm);
+        Ok(StatusListener {
+            status, calib_db, accum, config, reconfigured: false,
+        })
     }
 
     fn feed(&mut self, status: StatusBytes) {
+        self.feed_updated(status);
+    }
+
+    fn feed_updated(&mut self, status: StatusBytes) -> bool {
         let sensor_status = &mut self.status;
         let calib_db = &mut self.calib_db;
-        self.accum.feed(status, sensor_status, calib_db);
+        let updated = self.accum.feed(status, sensor_status, calib_db);
+        if updated {
+            let config = (sensor_status.return_type, sensor_status.rpm);
+            self.reconfigured |= is_reconfigured(self.config, config);
+            self.config = config;
+        }
+        updated
     }
 
     fn get_status(&self) -> &Self::Status {
         &self.status
     }
+
+    fn fov(&self) -> Option<(Azimuth, Azimuth)> {
+        let (start, end) = (self.status.fov_start, self.status.fov_end);
+        // zero-width window is reported until status is fully received
+        if start % FULL_TURN == end % FULL_TURN { return None; }
+        Some((Azimuth::new(start), Azimuth::new(end)))
+    }
+
+    fn return_type(&self) -> Option<ReturnType> {
+        Some(self.status.return_type)
+    }
+
+    fn reset(&mut self) {
+        self.accum.reset();
+    }
+
+    fn take_reconfiguration(&mut self) -> bool {
+        std::mem::replace(&mut self.reconfigured, false)
+    }
 }
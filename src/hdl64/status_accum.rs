@@ -8,9 +8,16 @@ use log::{debug, info, warn};
 
 use super::calib::CalibDb;
 
-use super::{Status, ReturnType, GpsStatus, PowerLevel};
+use super::{
+    Status, ReturnType, GpsStatus, PowerLevel, Hdl64Variant, StatusByteKind,
+    classify_status_byte,
+};
 
 const INIT_TIMEOUT: u64 = 5;
+/// Size of raw calibration data of a single laser
+const LASER_CALIB_SIZE: usize = 21;
+/// Size of raw calibration data of all lasers
+pub const RAW_CALIB_SIZE: usize = 64*LASER_CALIB_SIZE;
 
 #[derive(Default)]
 pub(super) struct StatusAccumulator {
@@ -31,6 +38,8 @@ pub(super) struct StatusAccumulator {
 
     lasers: LasersCalib,
     sensor_state: [u8; 21],
+
+    variant: Hdl64Variant,
 }
 
 // TODO: CRC check, check radians/degrees
@@ -50,9 +59,10 @@ impl StatusAccumulator {
             }
             let status = packets.next_packet()?
                 .map(|(_, packet)| get_status(packet))
-                .ok_or(io::Error::new(ErrorKind::Other,
+                .ok_or_else(|| io::Error::other(
                     "Failed to get packet data from packet listener"))?;
 
+            if packets.take_discontinuity() { self.reset(); }
             self.feed(status, &mut sensor_status, &mut calib_db);
             if self.init { return Ok((sensor_status, calib_db)); }
         }
@@ -74,44 +84,36 @@ impl StatusAccumulator {
         status.gps_time = (b & 0b0100_0000) != 0;
     }
 
-    fn process_calib_db(&self, db: &mut CalibDb) {
-        let iter = self.lasers.0.iter().zip(db.lasers.iter_mut()).enumerate();
-        for (i, (data, dbl)) in iter {
-            assert_eq!(data[0] as usize, i, "wrong data for calibration db");
-            let mut rdr = Cursor::new(&data[1..19]);
-            let vert_corr = read_i16(&mut rdr) as f32 / 100.;
-            let rot_corr = read_i16(&mut rdr) as f32 / 100.;
-
-            let (vert_corr_sin, vert_corr_cos) = vert_corr.to_radians().sin_cos();
-            let (rot_corr_sin, rot_corr_cos) = rot_corr.to_radians().sin_cos();
-
-            dbl.rot_corr_sin = rot_corr_sin;
-            dbl.rot_corr_cos = rot_corr_cos;
-            dbl.vert_corr_sin = vert_corr_sin;
-            dbl.vert_corr_cos = vert_corr_cos;
-
-            dbl.dist_correction = read_i16(&mut rdr) as f32/10.;
-            dbl.dist_corr_x = read_i16(&mut rdr) as f32/10.;
-            dbl.dist_corr_y = read_i16(&mut rdr) as f32/10.;
-            dbl.vert_offset = read_i16(&mut rdr) as f32/10.;
-            dbl.horiz_offset = read_i16(&mut rdr) as f32/10.;
-            dbl.focal_dist = read_i16(&mut rdr) as f32/10.;
-            dbl.focal_slope = read_i16(&mut rdr) as f32/10.;
-
-            dbl.min_intensity = data[19];
-            dbl.max_intensity = data[20];
+    /// Set hardware variant and decode received calibration data using it
+    pub(super) fn set_variant(&mut self, variant: Hdl64Variant,
+        calib_db: &mut CalibDb)
+    {
+        self.variant = variant;
+        if self.init {
+            if let Err(s) = self.process_calib_db(calib_db) { warn!("{}", s); }
         }
     }
 
+    pub(super) fn get_variant(&self) -> Hdl64Variant {
+        self.variant
+    }
+
+    /// Decode accumulated calibration data into `db`
+    ///
+    /// On error `db` is left untouched.
+    fn process_calib_db(&self, db: &mut CalibDb) -> Result<(), &'static str> {
+        decode_calib_db(&self.lasers.0, self.variant, db)
+    }
+
+    /// Get raw calibration data accumulated from status cycles
+    pub(super) fn get_raw_calib(&self) -> &[u8; RAW_CALIB_SIZE] {
+        &self.lasers.0
+    }
+
     fn process_full_cycle(&mut self, status: &mut Status,
         calib_db: &mut CalibDb) -> Result<(), &'static str>
     {
         debug!("full cycle");
-        if !self.init {
-            info!("Initialization complete");
-            self.init = true;
-        }
-
         let d = self.sensor_state;
         let mut rdr = Cursor::new(&d[..]);
         status.rpm = read_u16(&mut rdr);
@@ -135,7 +137,15 @@ impl StatusAccumulator {
             _ => return Err("invalid power level")
         };
 
-        self.process_calib_db(calib_db);
+        if let Err(s) = self.process_calib_db(calib_db) {
+            // accumulate calibration data again
+            self.init = false;
+            return Err(s);
+        }
+        if !self.init {
+            info!("Initialization complete");
+            self.init = true;
+        }
         Ok(())
     }
 
@@ -158,7 +168,7 @@ impl StatusAccumulator {
                 if !(&ids[..5] == b"12345" && ids[5] == 0xf7 && ids[6] == 0xf6) {
                     return Ok(false);
                 }
-                if !(&vals[..5] == b"UNIT#") {
+                if &vals[..5] != b"UNIT#" {
                     return Ok(false);
                 }
                 status.upper_threshold = vals[5];
@@ -168,19 +178,19 @@ impl StatusAccumulator {
             CycleState::Lasers{ laser, part } => {
                 debug!("lasers {} {}", laser, part);
                 match part {
-                    0 | 1 | 2 => {
+                    0..=2 => {
                         if &ids != b"1234567" { return Ok(false); }
                         if part == 0 && vals[0] != laser as u8 {
                             return Ok(false);
                         }
-                        let s = 7*part;
+                        let s = LASER_CALIB_SIZE*laser + 7*part;
                         if !self.init {
-                            self.lasers.0[laser][s..s+7].copy_from_slice(&vals);
+                            self.lasers.0[s..s+7].copy_from_slice(&vals);
                         }
                         if laser == 63 && part == 2 {
                             CycleState::CalibrationDt
                         } else {
-                            CycleState::Lasers{ laser: laser, part: part + 1 }
+                            CycleState::Lasers{ laser, part: part + 1 }
                         }
                     },
                     3 => {
@@ -195,8 +205,11 @@ impl StatusAccumulator {
                 debug!("CalibrationDt");
                 if &ids != b"1234567" { return Ok(false); }
                 let dt = get_dt(vals[0], vals[1], vals[2],
-                                vals[3], vals[4], vals[5])?;
+                                vals[3], vals[4], vals[5]);
+                let valid = status.calib_dt_valid;
+                let (dt, valid) = check_dt(dt, valid, "calibration datetime");
                 status.calib_dt = dt;
+                status.calib_dt_valid = valid;
                 status.humidity = vals[6];
                 CycleState::SensorState{part: 0}
             },
@@ -234,7 +247,10 @@ impl StatusAccumulator {
         -> Result<(), &'static str>
     {
         let dt = self.dt;
-        status.dt = get_dt(dt[0], dt[1], dt[2], dt[3], dt[4], dt[5])?;
+        let dt = get_dt(dt[0], dt[1], dt[2], dt[3], dt[4], dt[5]);
+        let (dt, valid) = check_dt(dt, status.dt_valid, "sensor datetime");
+        status.dt = dt;
+        status.dt_valid = valid;
         status.gps = match self.gps_val {
             0x41 => GpsStatus::SyncNmea,
             0x56 => GpsStatus::NmeaOnly,
@@ -247,54 +263,41 @@ impl StatusAccumulator {
         Ok(())
     }
 
+    /// Discard partially accumulated cycle
+    pub(super) fn reset(&mut self) {
+        self.cycle_pos = 0;
+        self.cycle_state = CycleState::FirstCycle;
+    }
+
     /// See `StatusListener.feed(..)` docs
+    ///
+    /// Returns `true` if full status cycle was processed.
     pub(super) fn feed(&mut self, status: StatusBytes,
-        sensor_status: &mut Status, calib_db: &mut CalibDb)
+        sensor_status: &mut Status, calib_db: &mut CalibDb) -> bool
     {
-        let is_ok = match status.id as char {
-            'H' => {
-                self.dt[3] = status.value;
-                self.cycle_pos == 0
-            },
-            'M' => {
-                self.dt[4] = status.value;
-                self.cycle_pos == 1
-            },
-            'S' => {
-                self.dt[5] = status.value;
-                self.cycle_pos == 2
-            },
-            'D' => {
-                self.dt[2] = status.value;
-                self.cycle_pos == 3
-            },
-            'N' => {
-                self.dt[1] = status.value;
-                self.cycle_pos == 4
-            },
-            'Y' => {
-                self.dt[0] = status.value;
-                self.cycle_pos == 5            },
-            'G' => {
-                self.gps_val = status.value;
-                self.cycle_pos == 6
-            },
-            'T' => {
-                self.temp_val = status.value;
-                self.cycle_pos == 7
-            },
-            'V' => {
-                self.version_val = status.value;
-                self.cycle_pos == 8
-            },
-            _ => self.cycle_pos > 8 && self.cycle_pos < 16
+        let kind = classify_status_byte(status.id);
+        match kind {
+            StatusByteKind::Hour => self.dt[3] = status.value,
+            StatusByteKind::Minute => self.dt[4] = status.value,
+            StatusByteKind::Second => self.dt[5] = status.value,
+            StatusByteKind::Day => self.dt[2] = status.value,
+            StatusByteKind::Month => self.dt[1] = status.value,
+            StatusByteKind::Year => self.dt[0] = status.value,
+            StatusByteKind::Gps => self.gps_val = status.value,
+            StatusByteKind::Temperature => self.temp_val = status.value,
+            StatusByteKind::Version => self.version_val = status.value,
+            StatusByteKind::CyclePayload => (),
+        }
+        let is_ok = match kind.cycle_position() {
+            Some(pos) => self.cycle_pos == pos,
+            None => self.cycle_pos > 8 && self.cycle_pos < 16,
         };
 
         if !is_ok {
             let msg = "Wrong cycle position detected. Reseting.";
             if self.init { warn!("{}", msg); } else { debug!("{}", msg); }
             self.cycle_pos = 0;
-            return;
+            return false;
         }
 
         if self.cycle_pos == 8 {
@@ -306,13 +309,15 @@ impl StatusAccumulator {
 
         if self.cycle_pos <= 8 {
             self.cycle_pos += 1;
-            return;
+            return false;
         }
 
         self.cycle_ids[self.cycle_pos - 9] = status.id;
         self.cycle_values[self.cycle_pos - 9] = status.value;
 
+        let mut updated = false;
         if self.cycle_pos == 15 {
+            let prev_state = self.cycle_state;
             match self.consume_cycle(sensor_status, calib_db) {
                 Ok(is_ok) => {
                     if !is_ok {
@@ -323,6 +328,8 @@ impl StatusAccumulator {
                             debug!("{}", msg);
                         }
                         self.cycle_state = CycleState::FirstCycle;
+                    } else if let CycleState::SensorState{ part: 2 } = prev_state {
+                        updated = true;
                     }
                 },
                 Err(s) => {
@@ -334,13 +341,15 @@ impl StatusAccumulator {
         } else {
             self.cycle_pos += 1;
         }
+        updated
     }
 }
 
 fn default_sensor_status() -> Status {
     let dt = get_dt(0, 1, 1, 0, 0, 0).unwrap();
     Status {
-        dt: dt,
+        dt,
+        dt_valid: true,
         gps: GpsStatus::NotConnected,
         temperature: 0,
         version: 0,
@@ -361,6 +370,7 @@ fn default_sensor_status() -> Status {
         upper_threshold: 0,
         lower_threshold: 0,
         calib_dt: dt,
+        calib_dt_valid: true,
     }
 }
 
@@ -381,27 +391,150 @@ fn get_dt(year: u8, month: u8, day: u8, h: u8, m: u8, s: u8)
     let res = NaiveDate::from_ymd_opt(y, month as u32, day as u32)
         .and_then(|d| d.and_hms_opt(h as u32, m as u32, s as u32));
     if let Some(dt) = res {
-        Ok(DateTime::<Utc>::from_utc(dt, Utc))
+        Ok(DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc))
     } else {
         Err("Incorrect datetime")
     }
 }
 
-#[derive(Copy, Clone)]
+/// Replace invalid datetime with UNIX epoch, warning is logged only if
+/// datetime was valid previously to avoid flooding the log
+fn check_dt(
+    dt: Result<DateTime<Utc>, &'static str>, was_valid: bool, name: &str,
+) -> (DateTime<Utc>, bool) {
+    match dt {
+        Ok(dt) => (dt, true),
+        Err(s) => {
+            if was_valid { warn!("{}: {}", s, name); }
+            (DateTime::<Utc>::default(), false)
+        },
+    }
+}
+
+#[derive(Copy, Clone, Default)]
 enum CycleState {
+    #[default]
     FirstCycle,
     Lasers{ laser: usize, part: usize},
     CalibrationDt,
     SensorState{ part: usize },
 }
 
-impl Default for CycleState {
-    fn default() -> Self { CycleState::FirstCycle }
-}
-
 
-struct LasersCalib([[u8; 21]; 64]);
+struct LasersCalib([u8; RAW_CALIB_SIZE]);
 
 impl Default for LasersCalib {
-    fn default() -> Self { LasersCalib([[0u8; 21]; 64]) }
+    fn default() -> Self { LasersCalib([0u8; RAW_CALIB_SIZE]) }
+}
+
+/// Decode raw calibration data of all lasers into `db`
+///
+/// On error `db` is left untouched.
+fn decode_calib_db(
+    raw: &[u8; RAW_CALIB_SIZE], variant: Hdl64Variant, db: &mut CalibDb,
+) -> Result<(), &'static str> {
+    // scale of distance values relative to centimeters
+    let dist_scale = match variant {
+        Hdl64Variant::S2 => 100.,
+        Hdl64Variant::S3 => 10.,
+    };
+    let valid = raw.chunks_exact(LASER_CALIB_SIZE).enumerate()
+        .all(|(i, data)| data[0] as usize == i);
+    if !valid { return Err("Wrong laser index in calibration data"); }
+    let iter = raw.chunks_exact(LASER_CALIB_SIZE).zip(db.lasers.iter_mut());
+    for (data, dbl) in iter {
+        let mut rdr = Cursor::new(&data[1..19]);
+        let vert_corr = read_i16(&mut rdr) as f32 / 100.;
+        let rot_corr = read_i16(&mut rdr) as f32 / 100.;
+
+        let (vert_corr_sin, vert_corr_cos) = vert_corr.to_radians().sin_cos();
+        let (rot_corr_sin, rot_corr_cos) = rot_corr.to_radians().sin_cos();
+
+        dbl.rot_corr_sin = rot_corr_sin;
+        dbl.rot_corr_cos = rot_corr_cos;
+        dbl.vert_corr_sin = vert_corr_sin;
+        dbl.vert_corr_cos = vert_corr_cos;
+
+        dbl.dist_correction = read_i16(&mut rdr) as f32/dist_scale;
+        dbl.dist_corr_x = read_i16(&mut rdr) as f32/dist_scale;
+        dbl.dist_corr_y = read_i16(&mut rdr) as f32/dist_scale;
+        dbl.vert_offset = read_i16(&mut rdr) as f32/dist_scale;
+        dbl.horiz_offset = read_i16(&mut rdr) as f32/dist_scale;
+        dbl.focal_dist = read_i16(&mut rdr) as f32/10.;
+        dbl.focal_slope = read_i16(&mut rdr) as f32/10.;
+
+        dbl.min_intensity = data[19];
+        dbl.max_intensity = data[20];
+    }
+    Ok(())
+}
+
+impl CalibDb {
+    /// Decode calibration table from raw data transmitted by the sensor in
+    /// status cycles, e.g. saved using `StatusListener::get_raw_calib`
+    ///
+    /// Data contains 21 bytes for each laser, the first of which is laser
+    /// ID. Layout of distance values depends on the hardware `variant`.
+    /// Allows to skip reception of calibration from the sensor (which takes
+    /// about 1 s) by caching it.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::StatusListener;
+    /// use velodyne::hdl64::{self, CalibDb, Hdl64Variant};
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let mut source = PcapSource::new("data/hdl64.pcap", false, false)?;
+    /// let listener = hdl64::StatusListener::init(&mut source)?;
+    /// let raw = *listener.get_raw_calib();
+    ///
+    /// let db = CalibDb::from_raw_bytes(&raw, 0.2, Hdl64Variant::S3).unwrap();
+    /// assert!(db.diff(&listener.get_calib_db(0.2), 0.).is_empty());
+    /// assert_eq!(db.dist_lsb, 0.2);
+    ///
+    /// // data with wrong laser IDs is rejected
+    /// let mut bad = raw;
+    /// bad[21] = 0;
+    /// assert!(CalibDb::from_raw_bytes(&bad, 0.2, Hdl64Variant::S3).is_err());
+    ///
+    /// // S2 calibration cycle with the same table for all lasers
+    /// let mut raw = [0u8; hdl64::RAW_CALIB_SIZE];
+    /// for (i, laser) in raw.chunks_exact_mut(21).enumerate() {
+    ///     laser[0] = i as u8;
+    ///     let vals: [i16; 9] = [-707, -440, 12950, 13120, 13000, 2150, -260,
+    ///         21000, 12];
+    ///     for (b, v) in laser[1..19].chunks_exact_mut(2).zip(vals.iter()) {
+    ///         b.copy_from_slice(&v.to_le_bytes());
+    ///     }
+    ///     laser[19..].copy_from_slice(&[10, 255]);
+    /// }
+    /// let db = CalibDb::from_raw_bytes(&raw, 0.2, Hdl64Variant::S2).unwrap();
+    /// let sin = |deg: f32| deg.to_radians().sin();
+    /// for laser in db.lasers.iter() {
+    ///     assert!((laser.vert_corr_sin - sin(-7.07)).abs() < 1e-6);
+    ///     assert!((laser.rot_corr_sin - sin(-4.4)).abs() < 1e-6);
+    ///     // distances are transmitted in tenths of millimeter
+    ///     assert!((laser.dist_correction - 129.5).abs() < 1e-4);
+    ///     assert!((laser.dist_corr_x - 131.2).abs() < 1e-4);
+    ///     assert!((laser.dist_corr_y - 130.).abs() < 1e-4);
+    ///     assert!((laser.vert_offset - 21.5).abs() < 1e-4);
+    ///     assert!((laser.horiz_offset + 2.6).abs() < 1e-4);
+    ///     assert_eq!(laser.focal_dist, 2100.);
+    ///     assert!((laser.focal_slope - 1.2).abs() < 1e-6);
+    ///     assert_eq!((laser.min_intensity, laser.max_intensity), (10, 255));
+    /// }
+    /// // the same cycle decoded as S3 data gives 10 times larger distances
+    /// let s3 = CalibDb::from_raw_bytes(&raw, 0.2, Hdl64Variant::S3).unwrap();
+    /// assert!((s3.lasers[0].dist_correction - 1295.).abs() < 1e-3);
+    /// assert_eq!(s3.lasers[0].focal_dist, 2100.);
+    /// # Ok(()) }
+    /// ```
+    pub fn from_raw_bytes(
+        data: &[u8; RAW_CALIB_SIZE], dist_lsb: f32, variant: Hdl64Variant,
+    ) -> Result<Self, &'static str> {
+        let mut db = CalibDb { dist_lsb, ..Default::default() };
+        decode_calib_db(data, variant, &mut db)?;
+        Ok(db)
+    }
 }
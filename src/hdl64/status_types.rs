@@ -1,6 +1,26 @@
 use std::net::Ipv4Addr;
 use chrono::{DateTime, Utc};
 
+pub use crate::ReturnType;
+
+/// HDL-64 hardware revision, which determines layout of the calibration data
+/// transmitted in the status cycles
+///
+/// Both revisions transmit angles in hundredths of degree, focal distance in
+/// millimeters and focal slope in tenths. Units of the remaining distance
+/// values follow the calibration data tables in the status section of the
+/// HDL-64E S2/S2.1 and HDL-64E S3 user's manuals.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Hdl64Variant {
+    /// HDL-64E S2, distance corrections and offsets are transmitted in
+    /// tenths of millimeter
+    S2,
+    /// HDL-64E S3, distance corrections and offsets are transmitted in
+    /// millimeters
+    #[default]
+    S3,
+}
+
 /// Possible statuses of external GPS sensor connection
 #[derive(Copy, Clone, Debug)]
 pub enum GpsStatus {
@@ -14,18 +34,6 @@ pub enum GpsStatus {
     NotConnected,
 }
 
-/// Multiple return modes
-#[derive(Debug, Clone, Copy)]
-pub enum ReturnType {
-    /// Strongest return only (default)
-    Strongest,
-    /// Last return only
-    Last,
-    /// Both strongest and last returns. If the strongest return is equal to the
-    /// last return, the next strongest return is reported.
-    Both,
-}
-
 /// Power level status
 #[derive(Debug, Clone, Copy)]
 pub enum PowerLevel {
@@ -42,10 +50,53 @@ pub enum PowerLevel {
 
 
 /// HDL-64 Status Type Calibration and Unit Parameters
+///
+/// Invalid datetimes (e.g. reported by a sensor with dead RTC) do not prevent
+/// status reception, they are replaced by UNIX epoch and marked as invalid.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io;
+/// use std::net::SocketAddrV4;
+/// use velodyne::StatusListener;
+/// use velodyne::hdl64;
+/// use velodyne::packet::{PacketSource, PcapSource, RawPacket};
+///
+/// /// Replaces month in all status cycles with 13
+/// struct BadMonth { inner: PcapSource, packet: RawPacket }
+///
+/// impl PacketSource for BadMonth {
+///     fn next_packet(&mut self)
+///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+///     {
+///         let addr = match self.inner.next_packet()? {
+///             Some((addr, packet)) => { self.packet = *packet; addr },
+///             None => return Ok(None),
+///         };
+///         if self.packet[1204] == b'N' { self.packet[1205] = 13; }
+///         Ok(Some((addr, &self.packet)))
+///     }
+/// }
+///
+/// let inner = PcapSource::new("data/hdl64.pcap", false, true)?;
+/// let mut source = BadMonth { inner, packet: [0; 1206] };
+/// let listener = hdl64::StatusListener::init(&mut source)?;
+/// let status = listener.get_status();
+/// assert!(!status.dt_valid);
+/// assert_eq!(status.dt.timestamp(), 0);
+/// // other fields are received as usual
+/// assert!(status.calib_dt_valid);
+/// assert!(status.rpm > 0);
+/// # Ok(()) }
+/// ```
 #[derive(Debug, Clone, Copy)]
 pub struct Status {
-    /// Current sensor datetime
+    /// Current sensor datetime, UNIX epoch if sensor has reported invalid
+    /// datetime
     pub dt: DateTime<Utc>,
+    /// `false` if sensor has reported invalid datetime
+    pub dt_valid: bool,
     /// Status of GPS sensor connection
     pub gps: GpsStatus,
     /// Inner sensor temperature in Celsius
@@ -92,6 +143,99 @@ pub struct Status {
     /// Noise threshold for lower lasers block
     pub lower_threshold: u8,
 
-    /// Date and time when sensor calibration was performed
+    /// Date and time when sensor calibration was performed, UNIX epoch if
+    /// sensor has reported invalid datetime
     pub calib_dt: DateTime<Utc>,
+    /// `false` if sensor has reported invalid calibration datetime
+    pub calib_dt_valid: bool,
+}
+
+/// Meaning of a status byte determined by its ID, see
+/// `classify_status_byte`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatusByteKind {
+    /// GPS hour (`'H'`)
+    Hour,
+    /// GPS minute (`'M'`)
+    Minute,
+    /// GPS second (`'S'`)
+    Second,
+    /// GPS day (`'D'`)
+    Day,
+    /// GPS month (`'N'`)
+    Month,
+    /// GPS year (`'Y'`)
+    Year,
+    /// GPS status (`'G'`)
+    Gps,
+    /// Sensor temperature (`'T'`)
+    Temperature,
+    /// Firmware version (`'V'`)
+    Version,
+    /// One of the 7 bytes of the cycle payload, which contains calibration
+    /// and unit parameters
+    CyclePayload,
+}
+
+impl StatusByteKind {
+    /// Position of the byte in the status cycle, `None` for the payload
+    /// bytes, which occupy positions from 9 to 15
+    pub fn cycle_position(self) -> Option<usize> {
+        use self::StatusByteKind::*;
+        Some(match self {
+            Hour => 0,
+            Minute => 1,
+            Second => 2,
+            Day => 3,
+            Month => 4,
+            Year => 5,
+            Gps => 6,
+            Temperature => 7,
+            Version => 8,
+            CyclePayload => return None,
+        })
+    }
+}
+
+/// Classify HDL-64 status byte using its ID
+///
+/// Status cycle consists of 16 bytes: 9 bytes with datetime, GPS status,
+/// temperature and firmware version, followed by 7 bytes of the payload.
+/// IDs of the payload bytes are not fixed, so all unknown IDs are classified
+/// as `CyclePayload`.
+///
+/// # Example
+/// ```
+/// use velodyne::hdl64::{classify_status_byte, StatusByteKind};
+///
+/// let ids = b"HMSDNYGTV";
+/// let kinds = [
+///     StatusByteKind::Hour, StatusByteKind::Minute, StatusByteKind::Second,
+///     StatusByteKind::Day, StatusByteKind::Month, StatusByteKind::Year,
+///     StatusByteKind::Gps, StatusByteKind::Temperature,
+///     StatusByteKind::Version,
+/// ];
+/// for (pos, (&id, &kind)) in ids.iter().zip(kinds.iter()).enumerate() {
+///     assert_eq!(classify_status_byte(id), kind);
+///     assert_eq!(kind.cycle_position(), Some(pos));
+/// }
+/// for &id in b"1234567".iter() {
+///     let kind = classify_status_byte(id);
+///     assert_eq!(kind, StatusByteKind::CyclePayload);
+///     assert_eq!(kind.cycle_position(), None);
+/// }
+/// ```
+pub fn classify_status_byte(id: u8) -> StatusByteKind {
+    match id {
+        b'H' => StatusByteKind::Hour,
+        b'M' => StatusByteKind::Minute,
+        b'S' => StatusByteKind::Second,
+        b'D' => StatusByteKind::Day,
+        b'N' => StatusByteKind::Month,
+        b'Y' => StatusByteKind::Year,
+        b'G' => StatusByteKind::Gps,
+        b'T' => StatusByteKind::Temperature,
+        b'V' => StatusByteKind::Version,
+        _ => StatusByteKind::CyclePayload,
+    }
 }
@@ -13,7 +13,7 @@ fn consume_start<R: Read>(parser: &mut EventReader<R>, node_name: &str)
     match parser.next() {
         Ok(XmlEvent::StartElement { ref name, .. })
             if name.local_name == node_name => Ok(()),
-        _ => return Err("Expected node start"),
+        _ => Err("Expected node start"),
     }
 }
 
@@ -23,7 +23,7 @@ fn consume_end<R: Read>(parser: &mut EventReader<R>, node_name: &str)
     match parser.next() {
         Ok(XmlEvent::EndElement{ref name, .. })
             if name.local_name == node_name => Ok(()),
-        _ => return Err("Expected node end"),
+        _ => Err("Expected node end"),
     }
 }
 
@@ -129,8 +129,8 @@ fn parse_point_item<R: Read>(parser: &mut EventReader<R>, db: &mut CalibDb)
     let val: f32 = get_node_val(parser, "vertCorrection_")?
         .parse().map_err(|_| "Failed to parse vert_correction")?;
     let (sin, cos) = val.to_radians().sin_cos();
-    db.lasers[i].rot_corr_sin = sin;
-    db.lasers[i].rot_corr_cos = cos;
+    db.lasers[i].vert_corr_sin = sin;
+    db.lasers[i].vert_corr_cos = cos;
 
     db.lasers[i].dist_correction = get_node_val(parser, "distCorrection_")?
         .parse().map_err(|_| "Failed to parse dist_correction")?;
@@ -171,6 +171,29 @@ fn parse_points<R: Read>(parser: &mut EventReader<R>, db: &mut CalibDb)
 // TODO: replace error with io::Error
 
 /// Read calibration XML file and parse data into `CalibDb` struct
+///
+/// File is expected to be in the format used by VeloView, values are stored
+/// in the same units as in the table read from the sensor, so tables from
+/// both sources are interchangeable.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::PointSource;
+/// use velodyne::hdl64::read_db;
+/// use velodyne::packet::PcapSource;
+///
+/// // the file contains calibration table of the sensor used for recording
+/// // of `hdl64.pcap`
+/// let db = read_db("data/hdl64_db.xml").unwrap();
+/// assert_eq!(db.lasers[0].vert_offset, 21.5);
+///
+/// let source = PcapSource::new("data/hdl64.pcap", false, false)?;
+/// let sensor_db = PointSource::hdl64_init(source)?.get_calib_db();
+/// assert_eq!(sensor_db.lasers[0].vert_offset, 21.5);
+/// assert!(db.diff(&sensor_db, 1e-4).is_empty());
+/// # Ok(()) }
+/// ```
 pub fn read_db<P: AsRef<Path>>(path: P) -> Result<CalibDb, &'static str> {
     let file = File::open(path).map_err(|_| "DB file not found")?;
     let file = BufReader::new(file);
@@ -190,7 +213,7 @@ pub fn read_db<P: AsRef<Path>>(path: P) -> Result<CalibDb, &'static str> {
                 if name.local_name == "DB" =>
             {
                 db.dist_lsb = get_node_val(parser, "distLSB_")?
-                    .parse().map_err(|_| "Failed to parse dist_lsb")?;;
+                    .parse().map_err(|_| "Failed to parse dist_lsb")?;
             },
             /*
             Ok(XmlEvent::StartElement { ref name, .. })
@@ -0,0 +1,101 @@
+//! Static kd-tree used for neighbors search in point clouds
+/// Static kd-tree stored as a permutation of point indices
+///
+/// Median of each index sub-slice splits it along the axis selected by
+/// the sub-slice depth, so the tree does not need any additional storage.
+pub(crate) struct KdTree<'a> {
+    points: &'a [[f32; 3]],
+    idx: Vec<usize>,
+}
+
+impl<'a> KdTree<'a> {
+    /// Build tree over points with indices `idx`
+    pub(crate) fn new(points: &'a [[f32; 3]], mut idx: Vec<usize>) -> Self {
+        Self::build(points, &mut idx, 0);
+        Self { points, idx }
+    }
+
+    fn build(points: &[[f32; 3]], idx: &mut [usize], depth: usize) {
+        if idx.len() <= 1 { return; }
+        let mid = idx.len()/2;
+        let axis = depth % 3;
+        idx.select_nth_unstable_by(mid, |&a, &b| {
+            points[a][axis].total_cmp(&points[b][axis])
+        });
+        let (left, right) = idx.split_at_mut(mid);
+        Self::build(points, left, depth + 1);
+        Self::build(points, &mut right[1..], depth + 1);
+    }
+
+    /// Fill `res` with indices of `k` nearest neighbors of `q` sorted by
+    /// squared distance
+    #[cfg(feature = "normals")]
+    pub(crate) fn nearest(
+        &self, q: [f32; 3], k: usize, res: &mut Vec<(f32, usize)>,
+    ) {
+        res.clear();
+        if k != 0 { self.search(&self.idx, 0, q, k, res); }
+    }
+
+    #[cfg(feature = "normals")]
+    fn search(
+        &self, idx: &[usize], depth: usize, q: [f32; 3], k: usize,
+        res: &mut Vec<(f32, usize)>,
+    ) {
+        if idx.is_empty() { return; }
+        let mid = idx.len()/2;
+        let p = self.points[idx[mid]];
+        let d2 = dist2(p, q);
+        if res.len() < k || d2 < res[res.len() - 1].0 {
+            let pos = res.iter().position(|&(d, _)| d > d2).unwrap_or(res.len());
+            res.insert(pos, (d2, idx[mid]));
+            res.truncate(k);
+        }
+
+        let axis = depth % 3;
+        let diff = q[axis] - p[axis];
+        let (near, far) = if diff < 0. {
+            (&idx[..mid], &idx[mid + 1..])
+        } else {
+            (&idx[mid + 1..], &idx[..mid])
+        };
+        self.search(near, depth + 1, q, k, res);
+        if res.len() < k || diff*diff < res[res.len() - 1].0 {
+            self.search(far, depth + 1, q, k, res);
+        }
+    }
+
+    /// Fill `res` with indices of points within squared distance `r2` of `q`
+    /// in arbitrary order
+    #[cfg(feature = "perception")]
+    pub(crate) fn within(&self, q: [f32; 3], r2: f32, res: &mut Vec<usize>) {
+        res.clear();
+        self.search_within(&self.idx, 0, q, r2, res);
+    }
+
+    #[cfg(feature = "perception")]
+    fn search_within(
+        &self, idx: &[usize], depth: usize, q: [f32; 3], r2: f32,
+        res: &mut Vec<usize>,
+    ) {
+        if idx.is_empty() { return; }
+        let mid = idx.len()/2;
+        let p = self.points[idx[mid]];
+        if dist2(p, q) <= r2 { res.push(idx[mid]); }
+
+        // points on both sides can be equal to the median along the axis
+        let axis = depth % 3;
+        let diff = q[axis] - p[axis];
+        if diff <= 0. || diff*diff <= r2 {
+            self.search_within(&idx[..mid], depth + 1, q, r2, res);
+        }
+        if diff >= 0. || diff*diff <= r2 {
+            self.search_within(&idx[mid + 1..], depth + 1, q, r2, res);
+        }
+    }
+}
+
+fn dist2(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    d[0]*d[0] + d[1]*d[1] + d[2]*d[2]
+}
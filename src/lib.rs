@@ -1,16 +1,107 @@
+//! Crate for processing Velodyne LiDAR data
+//!
+//! # Crate features
+//! - `std` (enabled by default): packet sources, status listeners, point
+//!   sources and other functionality which requires standard library.
+//!   Without it crate is `no_std` and provides only decoding of packets and
+//!   conversion of points using convertors, which do not allocate.
+//! - `xml`: reading of HDL-64 calibration tables from XML files.
+//! - `csv`: reading of HDL-64 calibration tables from CSV files exported by
+//!   VeloView.
+//! - `simd`: SIMD-accelerated computation of HDL-32E points coordinates.
+//! - `serde`: serialization of configuration types.
+//! - `normals`: estimation of surface normals.
+//! - `perception`: segmentation of point clouds into objects and ground.
+//! - `af_packet`: capture of packets using raw `AF_PACKET` sockets, available
+//!   only on Linux.
+#![cfg_attr(not(feature = "std"), no_std)]
 pub mod packet;
 
 pub mod hdl64;
 pub mod hdl32;
-
-use std::{io, fmt};
-use std::cmp::max;
-use std::marker::PhantomData;
-use std::net::SocketAddrV4;
-
-use crate::packet::{PacketSource, RawPacket, StatusBytes, PacketMeta};
+pub mod vlp16;
+#[cfg(feature = "std")]
+pub mod model;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod scan;
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod geo;
+#[cfg(feature = "normals")]
+pub mod normals;
+#[cfg(feature = "perception")]
+pub mod cluster;
+#[cfg(feature = "perception")]
+pub mod ground;
+mod point;
+mod azimuth;
+mod math;
+mod filter;
+#[cfg(any(feature = "normals", feature = "perception"))]
+mod kdtree;
+#[cfg(feature = "std")]
+mod time_window;
+#[cfg(feature = "std")]
+mod turn_history;
+#[cfg(feature = "std")]
+mod accumulator;
+#[cfg(feature = "std")]
+mod timed;
+#[cfg(feature = "std")]
+mod source;
+
+pub use crate::point::{PackedXYZI, FloatPoint, FullPointF64};
+#[cfg(feature = "std")]
+pub use crate::point::convert_packed;
+pub use crate::azimuth::{
+    Azimuth, AzimuthScale, azimuth_to_column, column_to_azimuth,
+};
+pub use crate::filter::FilteredConvertor;
+#[cfg(feature = "std")]
+pub use crate::time_window::TimeWindowIterator;
+#[cfg(feature = "std")]
+pub use crate::turn_history::TurnHistory;
+#[cfg(feature = "std")]
+pub use crate::accumulator::{TurnAccumulator, Transform, IDENTITY_TRANSFORM};
+#[cfg(feature = "std")]
+pub use crate::timed::{TimedPoint, TimeBase};
+#[cfg(feature = "std")]
+pub use crate::source::{
+    StatusListener, DummyStatusListener, PointSource, TurnIterator,
+    TurnCoverage, ConversionErrorPolicy, PointSourceConfig, Echo,
+};
+
+use core::fmt;
+use core::ops::ControlFlow;
+
+use crate::packet::{RawPacket, RawPoint, PacketMeta};
 
 /// 3D point with additionall data
+///
+/// # Example
+/// ```
+/// use velodyne::Convertor;
+/// use velodyne::hdl32::Hdl32Convertor;
+///
+/// // 8-bit intensity is reported unchanged in both intensity fields
+/// let mut packet = [0u8; 1206];
+/// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///     block[2..4].copy_from_slice(&(1000*i as u16).to_le_bytes());
+///     for (j, point) in block[4..].chunks_exact_mut(3).enumerate() {
+///         point.copy_from_slice(&[0xE8, 0x03, (32*i + j) as u8]);
+///     }
+/// }
+/// let mut intensities = vec![];
+/// Hdl32Convertor::default().convert(&packet, |p: velodyne::FullPoint| {
+///     assert_eq!(p.intensity16, p.intensity as u16);
+///     intensities.push(p.intensity);
+/// }).unwrap();
+/// let expected: Vec<u8> = (0..384).map(|i| i as u8).collect();
+/// assert_eq!(intensities, expected);
+/// ```
 #[derive(Default, Copy, Clone, Debug)]
 pub struct FullPoint {
     /// XYZ coordinates of the point
@@ -19,240 +110,358 @@ pub struct FullPoint {
     pub laser_id: u8,
     /// Intensity value
     pub intensity: u8,
+    /// Intensity value with extended range
+    ///
+    /// HDL-32E and VLP-16 report 8-bit intensity, so this field is equal to
+    /// `intensity`. For HDL-64 it contains calibrated intensity clamped to
+    /// `[0, 65535]` instead of `[0, 255]`, i.e. values which are saturated in
+    /// `intensity` can still be distinguished.
+    pub intensity16: u16,
     /// Point measurment timestamp. This value represents microseconds from the
     /// top of the hour.
     pub timestamp: u32,
+    /// Return number of the point, 1 for the only or the nearer (strongest)
+    /// return and 2 for the last return in the dual return mode, i.e. returns
+    /// are numbered by distance as in LAS
+    pub return_number: u8,
+    /// Number of distinct returns measured by the laser in the same firing
+    pub number_of_returns: u8,
+    /// Sensor azimuth at which the point was measured in hundredths of degree
+    pub azimuth: u16,
+    /// ID of the sensor which has measured the point, assigned by
+    /// `PointSource::set_sensor_id` (0 by default)
+    pub sensor_id: u8,
+}
+
+impl FullPoint {
+    /// Distance from the sensor origin to the point in meters
+    ///
+    /// Computed from `xyz`, so it's NaN for points without return.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::FullPoint;
+    ///
+    /// let p = FullPoint { xyz: [2., -3., 6.], ..Default::default() };
+    /// assert_eq!(p.range(), 7.);
+    /// let p = FullPoint { xyz: [f32::NAN; 3], ..Default::default() };
+    /// assert!(p.range().is_nan());
+    /// ```
+    pub fn range(&self) -> f32 {
+        let [x, y, z] = self.xyz;
+        math::sqrt(x*x + y*y + z*z)
+    }
 }
 
 impl From<FullPoint> for [f32; 3] {
     fn from(p: FullPoint) -> Self { p.xyz }
 }
 
+/// Multiple return modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnType {
+    /// Strongest return only (default)
+    Strongest,
+    /// Last return only
+    Last,
+    /// Both strongest and last returns. If the strongest return is equal to the
+    /// last return, the next strongest return is reported.
+    Both,
+}
+
 /// Erros ehich indicates failed point conversion
 ///
 /// Usually means that header bytes in a packet were invalid.
-#[derive(Copy, Clone, Debug)]
-pub struct ConversionError;
+///
+/// # Example
+/// ```
+/// use velodyne::{Convertor, ConversionError, FullPoint};
+/// use velodyne::hdl32::Hdl32Convertor;
+/// use velodyne::hdl64::{CalibDb, Hdl64Convertor};
+/// use velodyne::vlp16::Vlp16Convertor;
+///
+/// let mut packet = [0u8; 1206];
+/// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+///     let header = if i % 2 == 0 { 0xEE } else { 0xDD };
+///     block[..2].copy_from_slice(&[0xFF, header]);
+///     block[4..7].copy_from_slice(&[0xE8, 0x03, 0]);
+/// }
+/// // corrupted azimuth of the third block
+/// packet[202..204].copy_from_slice(&40000u16.to_le_bytes());
+///
+/// let mut n = 0;
+/// let mut count = |_: FullPoint| n += 1;
+/// let err = Some(ConversionError::InvalidAzimuth);
+/// let mut db = CalibDb::default();
+/// db.dist_lsb = 0.2;
+/// let hdl64 = Hdl64Convertor::new(db);
+/// assert_eq!(hdl64.convert(&packet, &mut count).err(), err);
+/// packet[101] = 0xEE;
+/// let hdl32 = Hdl32Convertor::default();
+/// assert_eq!(hdl32.convert(&packet, &mut count).err(), err);
+/// let vlp16 = Vlp16Convertor::default();
+/// assert_eq!(vlp16.convert(&packet, &mut count).err(), err);
+/// // no points with garbage angles are produced
+/// assert!(n <= 3*2*32);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// Block header bytes are not supported by convertor
+    InvalidHeader,
+    /// Block azimuth is out of the `[0, 36000)` range
+    InvalidAzimuth,
+}
 
 impl fmt::Display for ConversionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(self, f)
+        f.write_str(match self {
+            ConversionError::InvalidHeader => "invalid block header",
+            ConversionError::InvalidAzimuth => "invalid block azimuth",
+        })
     }
 }
 
-impl std::error::Error for ConversionError {}
+impl core::error::Error for ConversionError {}
 
 /// Trait for converting raw lidar points fo `FullPoint`.
 ///
 /// Implementors can use calibration tables under the hood.
 pub trait Convertor {
-    /// Converts `RawPoint`s from packet to `P` and for every resulting points
-    /// calls `f` using it as an input argument.
-    fn convert<F, P>(&self, raw_point: &RawPacket, f: F)
-        -> Result<PacketMeta, ConversionError>
-        where F: FnMut(P), P: From<FullPoint>;
-}
-
-/// Trait for tracking sensor status
-///
-/// In case if sensor does not emits meaningful status data or you don't need
-/// status information, use `DummyStatusListener`.
-pub trait StatusListener: Sized {
-    /// Type of the status information
-    type Status: Clone;
-
-    /// Initialize listener from packet source
-    fn init<T: PacketSource>(packet_source: &mut T) -> io::Result<Self>;
-
-    /// Feed status from the parsed packet.
+    /// Converts `RawPoint`s from packet to `FullPoint`s and for every
+    /// resulting point calls `f` using it and the `RawPoint` from which it
+    /// was computed as input arguments.
     ///
-    /// This method will update internall state of the listener, updating sensor
-    /// status (which is accessible through `get_status` method) when possible
-    fn feed(&mut self, status: StatusBytes);
-
-    /// Get current status state
-    fn get_status(&self) -> &Self::Status;
-}
-
-/// Dummy status listener which does nothing
-#[derive(Copy, Clone, Debug, Default)]
-pub struct DummyStatusListener;
-
-impl StatusListener for DummyStatusListener {
-    type Status = ();
-
-    fn init<T: PacketSource>(_source: &mut T) -> io::Result<Self> {
-        Ok(DummyStatusListener)
-    }
-
-    fn feed(&mut self, _status: StatusBytes) { }
-    fn get_status(&self) -> &Self::Status { &() }
-}
-
-
-/// This struct listens to the `packet_source` and converts packets data to
-/// points
-pub struct PointSource<T, C, S>
-    where T: PacketSource, C: Convertor, S: StatusListener
-{
-    packet_source: T,
-    status_lst: S,
-    convertor: C,
-}
-
-impl<T, C, S> PointSource<T, C, S>
-    where T: PacketSource, C: Convertor, S: StatusListener
-{
-    /// Create new `PointSource`
-    pub fn new(mut packet_source: T, convertor: C) -> io::Result<Self> {
-        let status_lst = S::init(&mut packet_source)?;
-        Ok(Self { packet_source, status_lst, convertor })
-    }
-
-    /// Get current sensor status
-    pub fn get_status(&self) -> &S::Status {
-        self.status_lst.get_status()
-    }
-
-    /// Process points in the next recieved packet
-    pub fn process_points<F, P>(&mut self, process_point: F)
-        -> io::Result<Option<(SocketAddrV4, PacketMeta)>>
-        where P: From<FullPoint>, F: FnMut(P)
+    /// Conversion stops early if `f` returns `ControlFlow::Break`. On success
+    /// returns packet meta information and number of points passed to `f`.
+    ///
+    /// Implementors must override either this method or `convert`. Default
+    /// implementation adapts `convert` for convertors which implement only
+    /// it: points after `ControlFlow::Break` are still computed, but not
+    /// passed to `f`, and `RawPoint` contains only laser ID and intensity
+    /// of the point, its distance is set to 0.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use velodyne::{Convertor, ConversionError, FullPoint};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketMeta, RawPacket};
+    ///
+    /// /// Convertor which implements only `convert`
+    /// struct Legacy(Hdl32Convertor);
+    ///
+    /// impl Convertor for Legacy {
+    ///     fn convert<F, P>(&self, raw_packet: &RawPacket, f: F)
+    ///         -> Result<PacketMeta, ConversionError>
+    ///         where F: FnMut(P), P: From<FullPoint>
+    ///     {
+    ///         self.0.convert(raw_packet, f)
+    ///     }
+    /// }
+    ///
+    /// let mut packet = [0u8; 1206];
+    /// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+    ///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///     block[2..4].copy_from_slice(&(20*i as u16).to_le_bytes());
+    ///     for point in block[4..].chunks_exact_mut(3) {
+    ///         point.copy_from_slice(&[0xE8, 0x03, 42]);
+    ///     }
+    /// }
+    /// let convertor = Legacy(Hdl32Convertor::default());
+    /// let mut points = vec![];
+    /// let (_, n) = convertor.try_convert_with_raw(&packet, |p, raw| {
+    ///     assert_eq!((raw.laser, raw.intensity), (p.laser_id, 42));
+    ///     points.push(p);
+    ///     if points.len() == 5 { ControlFlow::Break(()) }
+    ///     else { ControlFlow::Continue(()) }
+    /// }).unwrap();
+    /// assert_eq!((n, points.len()), (5, 5));
+    ///
+    /// let mut n = 0;
+    /// convertor.convert(&packet, |_: FullPoint| n += 1).unwrap();
+    /// assert_eq!(n, 12*32);
+    /// ```
+    fn try_convert_with_raw<F>(&self, raw_packet: &RawPacket, mut f: F)
+        -> Result<(PacketMeta, usize), ConversionError>
+        where F: FnMut(FullPoint, RawPoint) -> ControlFlow<()>
     {
-        let packets = &mut self.packet_source;
-        let convertor = &self.convertor;
-
-        let (addr, packet) = match packets.next_packet()? {
-            Some(val) => val,
-            None => return Ok(None),
-        };
-
-        let meta = convertor.convert(packet, process_point)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData,
-                "invalid block header"))?;
-        self.status_lst.feed(meta.status);
-
-        Ok(Some((addr, meta)))
-    }
-}
-
-impl<T: PacketSource> PointSource<T, hdl64::Hdl64Convertor, hdl64::StatusListener> {
-    /// Initialize HDL-64 packet source
-    pub fn hdl64_init(mut packet_source: T) -> io::Result<Self> {
-        let status_lst = hdl64::StatusListener::init(&mut packet_source)?;
-        let db = status_lst.get_calib_db(0.2);
-        let convertor = hdl64::Hdl64Convertor::new(db);
-        Ok(Self { packet_source, status_lst, convertor })
-    }
-
-    /// Update HDL-64 calibration table
-    pub fn hdl64_set_calib_db(&mut self, calib_db: hdl64::CalibDb) {
-        self.convertor = hdl64::Hdl64Convertor::new(calib_db);
-    }
-
-    pub fn get_calib_db(&self) -> hdl64::CalibDb {
-        self.convertor.db.clone()
-    }
-}
-
-impl<T: PacketSource> PointSource<T, hdl32::Hdl32Convertor, DummyStatusListener> {
-    /// Initialize HDL-32E point source
-    pub fn hdl32_init(packet_source: T) -> Self {
-        Self {
-            packet_source,
-            status_lst: Default::default(),
-            convertor: Default::default(),
-        }
+        let mut n = 0;
+        let mut stopped = false;
+        let meta = self.convert(raw_packet, |p: FullPoint| {
+            if stopped { return; }
+            let raw = RawPoint {
+                distance: 0, intensity: p.intensity, laser: p.laser_id,
+            };
+            n += 1;
+            stopped = f(p, raw).is_break();
+        })?;
+        Ok((meta, n))
     }
-}
-
-
-/// Iterator which returns points for each sensor rotation
-pub struct TurnIterator<T, C, S, P>
-    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
-{
-    point_source: PointSource<T, C, S>,
-    cap: usize,
-    prev_azimuth: u16,
-    split_azimuth: u16,
-    _p: PhantomData<P>,
-}
 
-impl<T, C, S, P> TurnIterator<T, C, S, P>
-    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
-{
-    /// Create new `TurnIterator`
-    pub fn new(packet_source: T, convertor: C) -> io::Result<Self> {
-        let point_source = PointSource::new(packet_source, convertor)?;
-        Ok(Self {
-            point_source, cap: 0, prev_azimuth: 0, split_azimuth: 0,
-            _p: Default::default(),
-        })
+    /// Number of lasers of the sensor, laser IDs of produced points are
+    /// always less than this value.
+    ///
+    /// Default implementation returns 256, i.e. the upper bound for all
+    /// possible laser IDs, convertors which know their sensor should
+    /// override it.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::Convertor;
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::vlp16::Vlp16Convertor;
+    /// use velodyne::hdl64::{Hdl64Convertor, CalibDb};
+    ///
+    /// let hdl64 = Hdl64Convertor::new(CalibDb::default());
+    /// assert_eq!(hdl64.laser_count(), 64);
+    /// assert_eq!(Hdl32Convertor::default().laser_count(), 32);
+    /// assert_eq!(Vlp16Convertor::default().laser_count(), 16);
+    /// ```
+    fn laser_count(&self) -> usize {
+        u8::MAX as usize + 1
     }
 
-    /// Set azimuth at which next turn will begin in `degrees*100`,
-    pub fn set_split_azimuth(&mut self, val: u16) {
-        self.split_azimuth = val % 36000;
-    }
-}
+    /// Notify convertor that accumulation of a new turn has started, called
+    /// by `TurnIterator`.
+    ///
+    /// Allows to reset per-turn diagnostics (e.g. clamp stats of
+    /// `Hdl64Convertor`), default implementation does nothing.
+    fn start_turn(&self) {}
 
-impl<T, P> TurnIterator<T, hdl64::Hdl64Convertor, hdl64::StatusListener, P>
-    where T: PacketSource, P: From<FullPoint>
-{
-    /// Initialize `TurnIterator` for HDL-64
-    pub fn hdl64_init(packet_source: T) -> io::Result<Self> {
-        let point_source = PointSource::hdl64_init(packet_source)?;
-        Ok(Self {
-            point_source, cap: 0, prev_azimuth: 0, split_azimuth: 0,
-            _p: Default::default(),
-        })
+    /// Converts `RawPoint`s from packet to `P` and for every resulting points
+    /// calls `f` using it as an input argument.
+    ///
+    /// Conversion stops early if `f` returns `ControlFlow::Break`. On success
+    /// returns packet meta information and number of points passed to `f`.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::ops::ControlFlow;
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// let convertor = Hdl32Convertor::default();
+    /// let mut all: Vec<FullPoint> = vec![];
+    /// convertor.convert(packet, |p| all.push(p)).unwrap();
+    /// assert!(all.len() > 10);
+    ///
+    /// // bounded sink which accepts only 10 points
+    /// let mut buf: Vec<FullPoint> = Vec::with_capacity(10);
+    /// let (_, n) = convertor.try_convert(packet, |p| {
+    ///     buf.push(p);
+    ///     if buf.len() == 10 { ControlFlow::Break(()) }
+    ///     else { ControlFlow::Continue(()) }
+    /// }).unwrap();
+    /// assert_eq!(n, 10);
+    /// assert_eq!(buf.len(), 10);
+    /// assert_eq!(buf[9].xyz, all[9].xyz);
+    ///
+    /// // without early termination all points are processed
+    /// let (_, n) = convertor
+    ///     .try_convert(packet, |_: FullPoint| ControlFlow::Continue(()))
+    ///     .unwrap();
+    /// assert_eq!(n, all.len());
+    /// # Ok(()) }
+    /// ```
+    fn try_convert<F, P>(&self, raw_packet: &RawPacket, mut f: F)
+        -> Result<(PacketMeta, usize), ConversionError>
+        where F: FnMut(P) -> ControlFlow<()>, P: From<FullPoint>
+    {
+        self.try_convert_with_raw(raw_packet, |p, _| f(p.into()))
     }
 
-    /// Update HDL-64 calibration table
-    pub fn hdl64_set_calib_db(&mut self, calib_db: hdl64::CalibDb) {
-        self.point_source.hdl64_set_calib_db(calib_db);
+    /// Converts `RawPoint`s from packet to `P` and for every resulting points
+    /// calls `f` using it as an input argument.
+    ///
+    /// Default implementation uses `try_convert_with_raw`.
+    fn convert<F, P>(&self, raw_packet: &RawPacket, mut f: F)
+        -> Result<PacketMeta, ConversionError>
+        where F: FnMut(P), P: From<FullPoint>
+    {
+        self.try_convert(raw_packet, |p| {
+            f(p);
+            ControlFlow::Continue(())
+        }).map(|(meta, _)| meta)
     }
-}
 
-impl<T, P> TurnIterator<T, hdl32::Hdl32Convertor, DummyStatusListener, P>
-    where T: PacketSource, P: From<FullPoint>
-{
-    /// Initialize `TurnIterator` for HDL-32E
-    pub fn hdl32_init(packet_source: T) -> Self {
-        let point_source = PointSource::hdl32_init(packet_source);
-        Self {
-            point_source, cap: 0, prev_azimuth: 0, split_azimuth: 0,
-            _p: Default::default(),
-        }
+    /// Converts `RawPoint`s from packet to `FullPoint`s and for every
+    /// resulting point calls `f` using it and the `RawPoint` from which it
+    /// was computed as input arguments.
+    ///
+    /// Useful for debugging of calibration.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::Convertor;
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// Hdl32Convertor::default().convert_with_raw(packet, |point, raw| {
+    ///     // HDL-32E distance LSB is 2 mm
+    ///     let [x, y, z] = point.xyz;
+    ///     let range = (x*x + y*y + z*z).sqrt();
+    ///     assert!((range - raw.distance as f32*0.002).abs() < 1e-3);
+    ///     assert_eq!(point.laser_id, raw.laser);
+    /// }).unwrap();
+    /// # Ok(()) }
+    /// ```
+    fn convert_with_raw<F>(&self, raw_packet: &RawPacket, mut f: F)
+        -> Result<PacketMeta, ConversionError>
+        where F: FnMut(FullPoint, RawPoint)
+    {
+        self.try_convert_with_raw(raw_packet, |p, raw| {
+            f(p, raw);
+            ControlFlow::Continue(())
+        }).map(|(meta, _)| meta)
     }
-}
 
-impl<T, C, S, P> Iterator for TurnIterator<T, C, S, P>
-   where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
-{
-    type Item = io::Result<(S::Status, Vec<P>)>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut buf = Vec::with_capacity(self.cap);
-        loop {
-            let res = self.point_source.process_points(|point| buf.push(point));
-            let azimuth = match res {
-                Ok(Some((_, meta))) => meta.azimuth,
-                Ok(None) => return None,
-                Err(err) => return Some(Err(err)),
-            };
-            let sa = self.split_azimuth;
-            // assumes that `azimuth` is never equal to `self.prev_azimuth`
-            let flag = if self.prev_azimuth > azimuth {
-                !(self.prev_azimuth >= sa &&  sa > azimuth)
-            } else {
-                azimuth >= sa &&  sa > self.prev_azimuth
-            };
-            self.prev_azimuth = azimuth;
-            if flag { break; }
-        }
-        self.cap = max(self.cap, (11*buf.len())/10);
-        let status = self.point_source.get_status().clone();
-        Some(Ok((status, buf)))
+    /// Wrap convertor into `FilteredConvertor`, which passes only points
+    /// for which `predicate` returns `true`.
+    ///
+    /// Filters can be chained, in this case point is passed only if it's
+    /// accepted by all predicates.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let range = |p: &FullPoint| {
+    ///     let [x, y, z] = p.xyz;
+    ///     (x*x + y*y + z*z).sqrt()
+    /// };
+    /// let convertor = Hdl32Convertor::default();
+    /// let filtered = convertor
+    ///     .filter(|p| range(p) < 10.)
+    ///     .filter(|p| p.laser_id != 5);
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// let mut all = vec![];
+    /// convertor.convert(packet, |p: FullPoint| all.push(p)).unwrap();
+    /// let mut points = vec![];
+    /// filtered.convert(packet, |p: FullPoint| points.push(p)).unwrap();
+    ///
+    /// assert!(!points.is_empty() && points.len() < all.len());
+    /// assert!(points.iter().all(|p| range(p) < 10. && p.laser_id != 5));
+    /// let expected = all.iter()
+    ///     .filter(|p| range(p) < 10. && p.laser_id != 5)
+    ///     .count();
+    /// assert_eq!(points.len(), expected);
+    /// # Ok(()) }
+    /// ```
+    fn filter<F>(self, predicate: F) -> FilteredConvertor<Self, F>
+        where Self: Sized, F: Fn(&FullPoint) -> bool
+    {
+        FilteredConvertor::new(self, predicate)
     }
 }
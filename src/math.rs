@@ -0,0 +1,70 @@
+//! Floating point functions which are not available in `core`
+//!
+//! Without `std` feature implementations from `libm` are used.
+
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn sin_cos(x: f32) -> (f32, f32) {
+    x.sin_cos()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+pub(crate) fn sin_cos(x: f32) -> (f32, f32) {
+    libm::sincosf(x)
+}
+
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn sin_cos_f64(x: f64) -> (f64, f64) {
+    x.sin_cos()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+pub(crate) fn sin_cos_f64(x: f64) -> (f64, f64) {
+    libm::sincos(x)
+}
+
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn round(x: f32) -> f32 {
+    x.round()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+pub(crate) fn round(x: f32) -> f32 {
+    libm::roundf(x)
+}
+
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn asin(x: f32) -> f32 {
+    x.asin()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+pub(crate) fn asin(x: f32) -> f32 {
+    libm::asinf(x)
+}
+
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn sqrt(x: f32) -> f32 {
+    x.sqrt()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline(always)]
+pub(crate) fn sqrt(x: f32) -> f32 {
+    libm::sqrtf(x)
+}
+
+/// Least non-negative remainder of `x` divided by positive `m`
+#[inline(always)]
+pub(crate) fn rem_euclid(x: f32, m: f32) -> f32 {
+    let r = x % m;
+    if r < 0. { r + m } else { r }
+}
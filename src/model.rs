@@ -0,0 +1,310 @@
+//! Unified handling of the supported sensor models
+//!
+//! # Example
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use std::io;
+//! use std::net::SocketAddrV4;
+//! use velodyne::{PointSource, FullPoint, ReturnType};
+//! use velodyne::model::{SensorModel, SensorConfig, AnyStatus};
+//! use velodyne::packet::{PacketSource, PcapSource, RawPacket};
+//!
+//! let source = PcapSource::new("data/hdl32.pcap", false, false)?;
+//! let config = SensorConfig::default();
+//! let mut point_source = PointSource::for_model(
+//!     source, SensorModel::Hdl32, config)?;
+//! let mut n = 0;
+//! point_source.process_points(|_: FullPoint| n += 1)?;
+//! assert!(n > 0);
+//! match point_source.get_status() {
+//!     AnyStatus::Hdl32(status) => assert!(status.is_some()),
+//!     _ => unreachable!(),
+//! }
+//!
+//! /// Emits a single VLP-16 packet in the strongest return mode with all
+//! /// points at 2 m
+//! struct Vlp16Packet { packet: RawPacket, done: bool }
+//!
+//! impl PacketSource for Vlp16Packet {
+//!     fn next_packet(&mut self)
+//!         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+//!     {
+//!         if std::mem::replace(&mut self.done, true) { return Ok(None); }
+//!         let blocks = self.packet[..1200].chunks_exact_mut(100);
+//!         for (i, block) in blocks.enumerate() {
+//!             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+//!             block[2..4].copy_from_slice(&(40*i as u16).to_le_bytes());
+//!             for point in block[4..].chunks_exact_mut(3) {
+//!                 point.copy_from_slice(&[0xE8, 0x03, 10]);
+//!             }
+//!         }
+//!         self.packet[1204..].copy_from_slice(&[0x37, 0x22]);
+//!         let addr = SocketAddrV4::new([192, 168, 1, 201].into(), 2368);
+//!         Ok(Some((addr, &self.packet)))
+//!     }
+//! }
+//!
+//! let source = Vlp16Packet { packet: [0; 1206], done: false };
+//! let config = SensorConfig::default();
+//! let mut point_source = PointSource::for_model(
+//!     source, SensorModel::Vlp16, config)?;
+//! assert_eq!(point_source.get_convertor().model(), SensorModel::Vlp16);
+//! let mut points = vec![];
+//! point_source.process_points(|p: FullPoint| points.push(p))?;
+//! // two firing sequences of 16 lasers in each block
+//! assert_eq!(points.len(), 12*32);
+//! assert!(points.iter().all(|p| p.laser_id < 16));
+//! assert!(points.iter().all(|p| (p.range() - 2.).abs() < 1e-5));
+//! match point_source.get_status() {
+//!     AnyStatus::Vlp16(Some(status)) => {
+//!         assert_eq!(status.return_mode, ReturnType::Strongest);
+//!         assert_eq!(status.model, 0x22);
+//!     },
+//!     _ => unreachable!(),
+//! }
+//!
+//! let source = PcapSource::new("data/hdl64.pcap", false, false)?;
+//! let config = SensorConfig::default();
+//! let point_source = PointSource::for_model(
+//!     source, SensorModel::Hdl64, config)?;
+//! assert_eq!(point_source.get_convertor().model(), SensorModel::Hdl64);
+//! # Ok(()) }
+//! ```
+use std::io;
+use std::ops::ControlFlow;
+
+use crate::{
+    hdl32, hdl64, vlp16, FullPoint, Convertor, ConversionError, StatusListener,
+    Azimuth, ReturnType,
+};
+use crate::packet::{PacketSource, RawPacket, RawPoint, PacketMeta, StatusBytes};
+
+/// Supported sensor models
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SensorModel {
+    /// Velodyne HDL-64E
+    Hdl64,
+    /// Velodyne HDL-32E
+    Hdl32,
+    /// Velodyne VLP-16
+    Vlp16,
+}
+
+/// Configuration used for initialization of model-specific types
+#[derive(Clone, Debug)]
+pub struct SensorConfig {
+    /// Distance LSB in centimeters used for HDL-64 calibration table read
+    /// from the sensor, default value is `0.2`
+    pub hdl64_dist_lsb: f32,
+    /// HDL-64 calibration table, if `None` table read from the sensor is used
+    pub hdl64_calib_db: Option<hdl64::CalibDb>,
+    /// Keep points without return, see `Hdl32Convertor::set_keep_no_return`
+    pub keep_no_return: bool,
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self { hdl64_dist_lsb: 0.2, hdl64_calib_db: None, keep_no_return: false }
+    }
+}
+
+/// Convertor for any of the supported sensor models
+pub enum AnyConvertor {
+    /// HDL-64E convertor, boxed because of the large calibration table
+    Hdl64(Box<hdl64::Hdl64Convertor>),
+    /// HDL-32E convertor
+    Hdl32(Box<hdl32::Hdl32Convertor>),
+    /// VLP-16 convertor
+    Vlp16(vlp16::Vlp16Convertor),
+}
+
+impl AnyConvertor {
+    /// Get model of the inner convertor
+    pub fn model(&self) -> SensorModel {
+        match self {
+            AnyConvertor::Hdl64(_) => SensorModel::Hdl64,
+            AnyConvertor::Hdl32(_) => SensorModel::Hdl32,
+            AnyConvertor::Vlp16(_) => SensorModel::Vlp16,
+        }
+    }
+}
+
+impl Convertor for AnyConvertor {
+    fn laser_count(&self) -> usize {
+        match self {
+            AnyConvertor::Hdl64(c) => c.laser_count(),
+            AnyConvertor::Hdl32(c) => c.laser_count(),
+            AnyConvertor::Vlp16(c) => c.laser_count(),
+        }
+    }
+
+    fn start_turn(&self) {
+        match self {
+            AnyConvertor::Hdl64(c) => c.start_turn(),
+            AnyConvertor::Hdl32(c) => c.start_turn(),
+            AnyConvertor::Vlp16(c) => c.start_turn(),
+        }
+    }
+
+    fn try_convert_with_raw<F>(&self, raw_packet: &RawPacket, f: F)
+        -> Result<(PacketMeta, usize), ConversionError>
+        where F: FnMut(FullPoint, RawPoint) -> ControlFlow<()>
+    {
+        match self {
+            AnyConvertor::Hdl64(c) => c.try_convert_with_raw(raw_packet, f),
+            AnyConvertor::Hdl32(c) => c.try_convert_with_raw(raw_packet, f),
+            AnyConvertor::Vlp16(c) => c.try_convert_with_raw(raw_packet, f),
+        }
+    }
+}
+
+/// Status of any of the supported sensor models
+#[derive(Copy, Clone, Debug)]
+pub enum AnyStatus {
+    /// Status accumulated from the HDL-64E status cycles
+    Hdl64(hdl64::Status),
+    /// HDL-32E factory bytes, `None` until the first packet with valid ones
+    Hdl32(Option<hdl32::FactoryBytes>),
+    /// VLP-16 factory bytes, `None` until the first packet with valid ones
+    Vlp16(Option<vlp16::FactoryBytes>),
+}
+
+enum AnyListener {
+    Hdl64(Box<hdl64::StatusListener>),
+    Hdl32(hdl32::StatusListener),
+    Vlp16(vlp16::StatusListener),
+}
+
+/// Status listener for any of the supported sensor models
+///
+/// Since sensor model can not be known in advance, `StatusListener::init`
+/// always returns an error, use `AnyStatusListener::for_model` instead.
+pub struct AnyStatusListener {
+    listener: AnyListener,
+    // copy of the inner listener status
+    status: AnyStatus,
+}
+
+impl AnyStatusListener {
+    /// Initialize status listener for the given sensor `model`
+    pub fn for_model<T: PacketSource>(packet_source: &mut T, model: SensorModel)
+        -> io::Result<Self>
+    {
+        let listener = match model {
+            SensorModel::Hdl64 => AnyListener::Hdl64(Box::new(
+                hdl64::StatusListener::init(packet_source)?)),
+            SensorModel::Hdl32 => AnyListener::Hdl32(
+                hdl32::StatusListener::init(packet_source)?),
+            SensorModel::Vlp16 => AnyListener::Vlp16(
+                vlp16::StatusListener::init(packet_source)?),
+        };
+        let status = Self::read_status(&listener);
+        Ok(Self { listener, status })
+    }
+
+    /// Get model of the inner listener
+    pub fn model(&self) -> SensorModel {
+        match self.listener {
+            AnyListener::Hdl64(_) => SensorModel::Hdl64,
+            AnyListener::Hdl32(_) => SensorModel::Hdl32,
+            AnyListener::Vlp16(_) => SensorModel::Vlp16,
+        }
+    }
+
+    fn read_status(listener: &AnyListener) -> AnyStatus {
+        match listener {
+            AnyListener::Hdl64(l) => AnyStatus::Hdl64(*l.get_status()),
+            AnyListener::Hdl32(l) => AnyStatus::Hdl32(*l.get_status()),
+            AnyListener::Vlp16(l) => AnyStatus::Vlp16(*l.get_status()),
+        }
+    }
+}
+
+impl StatusListener for AnyStatusListener {
+    type Status = AnyStatus;
+
+    fn init<T: PacketSource>(_source: &mut T) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "sensor model is unknown, use `AnyStatusListener::for_model`"))
+    }
+
+    fn feed(&mut self, status: StatusBytes) {
+        self.feed_updated(status);
+    }
+
+    fn feed_updated(&mut self, status: StatusBytes) -> bool {
+        let updated = match &mut self.listener {
+            AnyListener::Hdl64(l) => l.feed_updated(status),
+            AnyListener::Hdl32(l) => l.feed_updated(status),
+            AnyListener::Vlp16(l) => l.feed_updated(status),
+        };
+        // HDL-64 listener can update status partially without reporting it
+        self.status = Self::read_status(&self.listener);
+        updated
+    }
+
+    fn get_status(&self) -> &Self::Status {
+        &self.status
+    }
+
+    fn fov(&self) -> Option<(Azimuth, Azimuth)> {
+        match &self.listener {
+            AnyListener::Hdl64(l) => l.fov(),
+            AnyListener::Hdl32(l) => l.fov(),
+            AnyListener::Vlp16(l) => l.fov(),
+        }
+    }
+
+    fn reset(&mut self) {
+        match &mut self.listener {
+            AnyListener::Hdl64(l) => l.reset(),
+            AnyListener::Hdl32(l) => l.reset(),
+            AnyListener::Vlp16(l) => l.reset(),
+        }
+    }
+
+    fn return_type(&self) -> Option<ReturnType> {
+        match &self.listener {
+            AnyListener::Hdl64(l) => l.return_type(),
+            AnyListener::Hdl32(l) => l.return_type(),
+            AnyListener::Vlp16(l) => l.return_type(),
+        }
+    }
+
+    fn take_reconfiguration(&mut self) -> bool {
+        match &mut self.listener {
+            AnyListener::Hdl64(l) => l.take_reconfiguration(),
+            AnyListener::Hdl32(l) => l.take_reconfiguration(),
+            AnyListener::Vlp16(l) => l.take_reconfiguration(),
+        }
+    }
+}
+
+/// Create convertor for the model of `status_lst`
+///
+/// Calibration table for HDL-64 is taken from the listener if `config` does
+/// not contain it.
+pub(crate) fn convertor_for_model(
+    config: SensorConfig, status_lst: &AnyStatusListener,
+) -> AnyConvertor {
+    match &status_lst.listener {
+        AnyListener::Hdl64(l) => {
+            let dist_lsb = config.hdl64_dist_lsb;
+            let db = config.hdl64_calib_db
+                .unwrap_or_else(|| l.get_calib_db(dist_lsb));
+            let mut c = hdl64::Hdl64Convertor::new(db);
+            c.set_keep_no_return(config.keep_no_return);
+            AnyConvertor::Hdl64(Box::new(c))
+        },
+        AnyListener::Hdl32(_) => {
+            let mut c = hdl32::Hdl32Convertor::default();
+            c.set_keep_no_return(config.keep_no_return);
+            AnyConvertor::Hdl32(Box::new(c))
+        },
+        AnyListener::Vlp16(_) => {
+            let mut c = vlp16::Vlp16Convertor::default();
+            c.set_keep_no_return(config.keep_no_return);
+            AnyConvertor::Vlp16(c)
+        },
+    }
+}
@@ -0,0 +1,113 @@
+//! Estimation of surface normals for point clouds
+//!
+//! Normals are computed by fitting a plane to the nearest neighbors of each
+//! point, neighbors are searched using a kd-tree built over the whole cloud.
+use crate::FullPoint;
+use crate::kdtree::KdTree;
+
+/// Compute eigenvector of the symmetric matrix `a` corresponding to its
+/// smallest eigenvalue using Jacobi eigenvalue algorithm
+fn smallest_eigenvector(mut a: [[f64; 3]; 3]) -> [f64; 3] {
+    let mut v = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+    for _ in 0..32 {
+        // select the largest off-diagonal element
+        let (p, q) = [(0, 1), (0, 2), (1, 2)].iter().copied()
+            .max_by(|&(i, j), &(k, l)| a[i][j].abs().total_cmp(&a[k][l].abs()))
+            .expect("array is not empty");
+        let scale = a[0][0].abs() + a[1][1].abs() + a[2][2].abs();
+        if a[p][q].abs() <= 1e-12*scale { break; }
+
+        let theta = (a[q][q] - a[p][p])/(2.*a[p][q]);
+        let t = theta.signum()/(theta.abs() + (theta*theta + 1.).sqrt());
+        let c = 1./(t*t + 1.).sqrt();
+        let s = t*c;
+        for row in a.iter_mut().chain(v.iter_mut()) {
+            let (rp, rq) = (row[p], row[q]);
+            row[p] = c*rp - s*rq;
+            row[q] = s*rp + c*rq;
+        }
+        let (ap, aq) = (a[p], a[q]);
+        for k in 0..3 {
+            a[p][k] = c*ap[k] - s*aq[k];
+            a[q][k] = s*ap[k] + c*aq[k];
+        }
+    }
+    let i = (0..3).min_by(|&i, &j| a[i][i].total_cmp(&a[j][j]))
+        .expect("range is not empty");
+    [v[0][i], v[1][i], v[2][i]]
+}
+
+/// Estimate unit normal for each point using its `k` nearest neighbors
+///
+/// Neighbors include the point itself. Normal is computed as a normal of the
+/// least squares plane fitted to the neighbors and is oriented towards the
+/// sensor origin. Points with NaN coordinates do not participate in the
+/// neighbors search and get NaN normals, same goes for all points if cloud
+/// contains less than 3 valid points or `k` is less than 3.
+///
+/// Requires `normals` feature.
+///
+/// # Example
+/// ```
+/// use velodyne::FullPoint;
+/// use velodyne::normals::estimate_normals;
+///
+/// // ground plane below the sensor
+/// let mut points = vec![];
+/// for i in 0..20 {
+///     for j in 0..20 {
+///         let (x, y) = (0.1*i as f32 - 1., 0.1*j as f32 - 1.);
+///         let z = 0.1*x + 0.2*y - 2.;
+///         points.push(FullPoint { xyz: [x, y, z], ..Default::default() });
+///     }
+/// }
+/// points.push(FullPoint { xyz: [f32::NAN; 3], ..Default::default() });
+///
+/// let normals = estimate_normals(&points, 8);
+/// assert_eq!(normals.len(), points.len());
+/// let n = (0.01f32 + 0.04 + 1.).sqrt();
+/// let expected = [-0.1/n, -0.2/n, 1./n];
+/// for normal in &normals[..400] {
+///     let dot: f32 = (0..3).map(|i| normal[i]*expected[i]).sum();
+///     assert!(dot > 0.9999, "{:?}", normal);
+/// }
+/// assert!(normals[400].iter().all(|v| v.is_nan()));
+/// ```
+pub fn estimate_normals(points: &[FullPoint], k: usize) -> Vec<[f32; 3]> {
+    let mut res = vec![[f32::NAN; 3]; points.len()];
+    let xyz: Vec<[f32; 3]> = points.iter().map(|p| p.xyz).collect();
+    let valid: Vec<usize> = (0..xyz.len())
+        .filter(|&i| !xyz[i].iter().any(|v| v.is_nan()))
+        .collect();
+    if k < 3 || valid.len() < 3 { return res; }
+
+    let tree = KdTree::new(&xyz, valid.clone());
+    let mut neighbors = Vec::with_capacity(k + 1);
+    for i in valid {
+        tree.nearest(xyz[i], k, &mut neighbors);
+
+        // accumulate in f64 to reduce cancellation errors
+        let mut mean = [0f64; 3];
+        for &(_, j) in neighbors.iter() {
+            for a in 0..3 { mean[a] += xyz[j][a] as f64; }
+        }
+        let n = neighbors.len() as f64;
+        for m in mean.iter_mut() { *m /= n; }
+        let mut cov = [[0f64; 3]; 3];
+        for &(_, j) in neighbors.iter() {
+            let mut d = [0f64; 3];
+            for a in 0..3 { d[a] = xyz[j][a] as f64 - mean[a]; }
+            for a in 0..3 {
+                for b in 0..3 { cov[a][b] += d[a]*d[b]; }
+            }
+        }
+
+        let normal = smallest_eigenvector(cov);
+        let norm = normal.iter().map(|v| v*v).sum::<f64>().sqrt();
+        // vector from the point to the origin is equal to `-xyz`
+        let dot: f64 = (0..3).map(|a| normal[a]*xyz[i][a] as f64).sum();
+        let sign = if dot > 0. { -1. } else { 1. };
+        for a in 0..3 { res[i][a] = (sign*normal[a]/norm) as f32; }
+    }
+    res
+}
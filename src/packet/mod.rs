@@ -22,18 +22,43 @@
 //! }
 //! # Ok(()) }
 //! ```
-use std::io;
+use core::fmt;
+use core::convert::TryFrom;
 use byteorder::{ByteOrder, LE};
+use core::ops::ControlFlow;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
 use std::net::SocketAddrV4;
 
+use crate::{Azimuth, Convertor, ConversionError, FullPoint};
+
+#[cfg(feature = "std")]
 mod udp;
-pub use self::udp::UdpSource;
+#[cfg(feature = "std")]
+pub use self::udp::{UdpSource, UdpSourceBuilder, RecvSocket};
+#[cfg(feature = "std")]
 mod pcap;
-pub use self::pcap::PcapSource;
+#[cfg(feature = "std")]
+pub use self::pcap::{PcapSource, PcapIndex};
+#[cfg(feature = "std")]
+mod pcapng;
+#[cfg(feature = "std")]
+pub use self::pcapng::PcapngSource;
+#[cfg(feature = "std")]
+mod multi;
+#[cfg(feature = "std")]
+pub use self::multi::MultiSource;
+#[cfg(all(feature = "af_packet", target_os = "linux"))]
+mod raw_socket;
+#[cfg(all(feature = "af_packet", target_os = "linux"))]
+pub use self::raw_socket::RawSocketSource;
+mod return_mode;
+pub use self::return_mode::ReturnModeDetector;
 
 /// Size in bytes of raw UDP packet data
 const PACKET_SIZE: usize = 1206;
-const BLOCKS: usize = 12;
+pub(crate) const BLOCKS: usize = 12;
 const LASERS: usize = 32;
 const POINT_SIZE: usize = 3;
 const HEADER_SIZE: usize = 2;
@@ -42,8 +67,46 @@ const BLOCK_SIZE: usize = HEADER_SIZE + AZIMUTH_SIZE + POINT_SIZE*LASERS; // 100
 const BLOCKS_SIZE: usize = BLOCKS*BLOCK_SIZE; // 1200
 const STATUS_ID: usize = 1204;
 const STATUS_VALUE: usize = 1205;
+/// Azimuth increment between blocks of synthetic packet used by
+/// `convert_block`
+const SYNTHETIC_AZIMUTH_STEP: u16 = 20;
+/// Block headers accepted by `Packet`
+const VALID_HEADERS: [[u8; 2]; 2] = [[0xFF, 0xEE], [0xFF, 0xDD]];
+
+/// Mapping of block header bytes to laser banks of a sensor model
+///
+/// Each block contains 32 points, sensors with more lasers use different
+/// headers for blocks of different banks. Each entry of the mapping contains
+/// header bytes and offset which is added to laser positions in the block
+/// to get laser IDs.
+///
+/// # Example
+/// ```
+/// use velodyne::{hdl32, hdl64, vlp16};
+///
+/// let (upper, lower) = ([0xFF, 0xEE], [0xFF, 0xDD]);
+/// assert_eq!(hdl64::BLOCK_HEADERS.laser_offset(upper), Some(0));
+/// assert_eq!(hdl64::BLOCK_HEADERS.laser_offset(lower), Some(32));
+/// assert_eq!(hdl64::BLOCK_HEADERS.laser_offset([0, 0]), None);
+/// for headers in [hdl32::BLOCK_HEADERS, vlp16::BLOCK_HEADERS].iter() {
+///     assert_eq!(headers.laser_offset(upper), Some(0));
+///     assert_eq!(headers.laser_offset(lower), None);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct BlockHeaders(pub &'static [([u8; 2], u8)]);
+
+impl BlockHeaders {
+    /// Get offset of laser IDs in the block with the given `header`, `None`
+    /// if header is not used by the sensor
+    pub fn laser_offset(&self, header: [u8; 2]) -> Option<u8> {
+        self.0.iter().find(|(h, _)| *h == header).map(|&(_, offset)| offset)
+    }
+}
 
 /// Raw UDP packet data
+///
+/// See also `Packet` which provides validated typed access to the data.
 pub type RawPacket = [u8; PACKET_SIZE];
 
 /// Status id and value bytes incorporated into each packet
@@ -64,6 +127,26 @@ pub struct RawPoint {
     pub laser: u8,
 }
 
+/// Counters of packets dropped by packet source
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DropStats {
+    /// Packets which are too short to contain sensor data (e.g. unidentified
+    /// packets recorded by VeloView)
+    pub short: u64,
+    /// Packets which data was truncated during recording
+    pub truncated: u64,
+    /// Packets sent to unexpected port, counted only by sources which filter
+    /// packets by port
+    pub wrong_port: u64,
+    /// Received UDP datagrams with size not equal to the expected one (1206
+    /// bytes by default)
+    pub size_mismatch: u64,
+    /// Packets from senders rejected by the source address filter
+    pub wrong_source: u64,
+    /// Recorded packets which are not IPv4 UDP datagrams
+    pub non_udp: u64,
+}
+
 /// Meta information associated with the recieved packet
 #[derive(Debug, Copy, Clone)]
 pub struct PacketMeta {
@@ -77,44 +160,537 @@ pub fn get_status(data: &RawPacket) -> StatusBytes {
     StatusBytes { id: data[STATUS_ID], value: data[STATUS_VALUE] }
 }
 
+/// Read raw distance of the `laser` point in the `block`
+pub(crate) fn raw_distance(data: &RawPacket, block: usize, laser: u8) -> u16 {
+    let pos = block*BLOCK_SIZE + HEADER_SIZE + AZIMUTH_SIZE
+        + POINT_SIZE*(laser as usize);
+    LE::read_u16(&data[pos..pos + 2])
+}
+
+/// Read raw intensity of the `laser` point in the `block`
+pub(crate) fn raw_intensity(data: &RawPacket, block: usize, laser: u8) -> u8 {
+    let pos = block*BLOCK_SIZE + HEADER_SIZE + AZIMUTH_SIZE
+        + POINT_SIZE*(laser as usize);
+    data[pos + 2]
+}
+
+/// Read header of the `block`
+pub(crate) fn block_header(data: &RawPacket, block: usize) -> [u8; 2] {
+    let pos = block*BLOCK_SIZE;
+    [data[pos], data[pos + 1]]
+}
+
+/// Read the `laser` point in the `block`
+pub(crate) fn raw_point(data: &RawPacket, block: usize, laser: u8) -> RawPoint {
+    let distance = raw_distance(data, block, laser);
+    let intensity = raw_intensity(data, block, laser);
+    RawPoint { distance, intensity, laser }
+}
+
+/// Returns `true` if `point` from the second block of a dual return pair
+/// repeats the point from the first block.
+///
+/// If the strongest return is equal to the last return, the sensor reports
+/// the next strongest return, so the second block contains a duplicate only
+/// if the firing had a single return, in which case both distance and
+/// intensity are identical. The next strongest return at the same distance
+/// is kept.
+pub(crate) fn is_duplicate_return(
+    data: &RawPacket, block: usize, partner: Option<usize>, point: RawPoint,
+) -> bool {
+    match partner {
+        Some(p) if p < block && point.distance != 0 => {
+            raw_distance(data, p, point.laser) == point.distance
+                && raw_intensity(data, p, point.laser) == point.intensity
+        },
+        _ => false,
+    }
+}
+
+/// Read azimuths of all blocks in the packet in hundredths of degree
+///
+/// Points are not parsed, so it can be used for cheap validation of the
+/// azimuth sequence. In the dual return mode consecutive blocks have equal
+/// azimuths.
+///
+/// # Example
+/// ```
+/// use velodyne::packet::packet_azimuths;
+///
+/// let mut packet = [0u8; 1206];
+/// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///     let azimuth = 35_900 + 20*i as u16;
+///     block[2..4].copy_from_slice(&(azimuth % 36000).to_le_bytes());
+/// }
+/// assert_eq!(packet_azimuths(&packet), [
+///     35_900, 35_920, 35_940, 35_960, 35_980, 0, 20, 40, 60, 80, 100, 120,
+/// ]);
+/// ```
+pub fn packet_azimuths(data: &RawPacket) -> [u16; BLOCKS] {
+    let mut res = [0u16; BLOCKS];
+    for (a, block) in res.iter_mut().zip(block_iter(data)) {
+        *a = block.azimuth();
+    }
+    res
+}
+
+/// Find blocks which contain other return of the same firing in the dual
+/// return mode.
+///
+/// Blocks are considered paired if they have identical header and azimuth,
+/// in the single return mode all elements of the returned array are `None`.
+pub(crate) fn dual_return_partners(data: &RawPacket) -> [Option<usize>; BLOCKS] {
+    let mut keys = [([0u8; 2], 0u16); BLOCKS];
+    let blocks = data[..BLOCKS_SIZE].chunks_exact(BLOCK_SIZE);
+    for (key, block) in keys.iter_mut().zip(blocks) {
+        *key = ([block[0], block[1]], LE::read_u16(&block[2..4]));
+    }
+    let mut res = [None; BLOCKS];
+    for (i, r) in res.iter_mut().enumerate() {
+        // search the closest block with the same key
+        for d in 1..BLOCKS {
+            let before = i.checked_sub(d);
+            let after = Some(i + d).filter(|&j| j < BLOCKS);
+            let found = before.into_iter().chain(after)
+                .find(|&j| keys[j] == keys[i]);
+            if found.is_some() {
+                *r = found;
+                break;
+            }
+        }
+    }
+    res
+}
+
+/// Compute return number and number of returns for the point in the `block`
+/// using block which contains other return of the same firing.
+///
+/// The first block of a dual return pair contains the last return and the
+/// second one contains the strongest return (or the next strongest if it is
+/// equal to the last one), which is always nearer than the last return. So
+/// as in LAS the nearer return gets number 1 and the last one gets number 2.
+pub(crate) fn return_info(
+    data: &RawPacket, block: usize, partner: Option<usize>, point: RawPoint,
+) -> (u8, u8) {
+    let partner = match partner {
+        Some(p) => p,
+        None => return (1, 1),
+    };
+    let other = raw_distance(data, partner, point.laser);
+    let other_intensity = raw_intensity(data, partner, point.laser);
+    let duplicate = other == point.distance
+        && other_intensity == point.intensity;
+    if other == 0 || duplicate {
+        (1, 1)
+    } else if block < partner {
+        (2, 2)
+    } else {
+        (1, 2)
+    }
+}
+
 /// Parse Velodyne UDP packet data
+///
+/// Returned iterator yields header, azimuth and points of each block, use
+/// `packet_azimuths` if only block azimuths are needed and `blocks` for raw
+/// access to the block data.
 pub fn parse_packet<'a>(data: &'a RawPacket) -> (
     PacketMeta,
     impl Iterator<Item=([u8; 2], u16, impl Iterator<Item=RawPoint> + 'a)> + 'a,
 ) {
-    let timestamp = LE::read_u32(&data[BLOCKS_SIZE..BLOCKS_SIZE + 4]);
+    parse_packet_with(data, false)
+}
 
-    // initial azimuth of the packet
-    let a0 = LE::read_u16(&data[HEADER_SIZE..HEADER_SIZE+AZIMUTH_SIZE]);
-
-    let iter = data[..1200]
-        .chunks_exact(100)
-        .map(|block| {
-            let header = [block[0], block[1]];
-            let azimuth = LE::read_u16(&block[2..4]);
-
-            let block_iter = block[4..100]
-                .chunks_exact(3)
-                .enumerate()
-                .map(|(laser, chunk)| {
-                    let distance = LE::read_u16(&chunk[..2]);
-                    let intensity = chunk[2];
-                    let laser = laser as u8;
-                    RawPoint { distance, intensity, laser }
-                })
-                .filter(|point| point.distance != 0);
-            (header, azimuth, block_iter)
-        });
+/// Parse packet data, if `keep_no_return` is `true`, points with zero
+/// distance are not filtered out
+pub(crate) fn parse_packet_with<'a>(data: &'a RawPacket, keep_no_return: bool)
+    -> (
+        PacketMeta,
+        impl Iterator<Item=([u8; 2], u16, impl Iterator<Item=RawPoint> + 'a)> + 'a,
+    )
+{
+    let iter = block_iter(data).map(move |block| {
+        let points = block.all_points()
+            .filter(move |p| keep_no_return || p.distance != 0);
+        (block.header(), block.azimuth(), points)
+    });
+    (packet_meta(data), iter)
+}
 
+pub(crate) fn packet_meta(data: &RawPacket) -> PacketMeta {
+    let timestamp = LE::read_u32(&data[BLOCKS_SIZE..BLOCKS_SIZE + 4]);
+    // initial azimuth of the packet
+    let azimuth = LE::read_u16(&data[HEADER_SIZE..HEADER_SIZE+AZIMUTH_SIZE]);
     let status = get_status(data);
-    let meta = PacketMeta { azimuth: a0, timestamp, status };
-    (meta, iter)
+    PacketMeta { azimuth, timestamp, status }
+}
+
+fn block_iter(data: &RawPacket) -> impl Iterator<Item=Block<'_>> {
+    data[..BLOCKS_SIZE].chunks_exact(BLOCK_SIZE).map(Block)
+}
+
+/// Iterate over header, azimuth and raw payload of each block in the packet
+///
+/// Payload contains 32 points of 3 bytes each which are not interpreted in
+/// any way, so it can be used for decoding of custom block layouts. Headers
+/// are not validated.
+///
+/// # Example
+/// ```
+/// use velodyne::packet::blocks;
+///
+/// let mut packet = [0u8; 1206];
+/// for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+///     let header = if i % 2 == 0 { [0xFF, 0xEE] } else { [0xFF, 0xDD] };
+///     block[..2].copy_from_slice(&header);
+///     block[2..4].copy_from_slice(&(100*i as u16).to_le_bytes());
+///     block[4] = i as u8;
+///     block[99] = 0xAA;
+/// }
+///
+/// assert_eq!(blocks(&packet).count(), 12);
+/// for (i, (header, azimuth, payload)) in blocks(&packet).enumerate() {
+///     let expected = if i % 2 == 0 { [0xFF, 0xEE] } else { [0xFF, 0xDD] };
+///     assert_eq!(*header, expected);
+///     assert_eq!(azimuth, 100*i as u16);
+///     assert_eq!(payload.len(), 96);
+///     assert_eq!((payload[0], payload[95]), (i as u8, 0xAA));
+/// }
+/// ```
+pub fn blocks(packet: &RawPacket)
+    -> impl Iterator<Item=(&[u8; 2], u16, &[u8; 96])>
+{
+    block_iter(packet).map(|block| {
+        let (header, rest) = block.0.split_at(HEADER_SIZE);
+        let payload = &rest[AZIMUTH_SIZE..];
+        (
+            <&[u8; 2]>::try_from(header)
+                .expect("slice has HEADER_SIZE length"),
+            block.azimuth(),
+            <&[u8; 96]>::try_from(payload)
+                .expect("slice has block payload length"),
+        )
+    })
+}
+
+/// Error returned on attempt to create `Packet` from invalid data
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InvalidPacket {
+    /// Data length is not equal to `PACKET_SIZE`
+    InvalidLength,
+    /// Some of the blocks has header other than `0xFFEE` or `0xFFDD`
+    InvalidHeader,
+}
+
+impl fmt::Display for InvalidPacket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            InvalidPacket::InvalidLength => "invalid packet length",
+            InvalidPacket::InvalidHeader => "invalid block header",
+        })
+    }
+}
+
+impl core::error::Error for InvalidPacket {}
+
+/// Validated packet data with typed accessors
+///
+/// Unlike `RawPacket` it's guaranteed that all blocks in the packet have
+/// valid headers.
+///
+/// # Example
+/// ```
+/// use std::convert::TryFrom;
+/// use velodyne::packet::{Packet, InvalidPacket};
+///
+/// let mut data = [0u8; 1206];
+/// for block in data[..1200].chunks_exact_mut(100) {
+///     block[..4].copy_from_slice(&[0xFF, 0xEE, 0x10, 0x27]);
+/// }
+/// // distance and intensity of the second laser in the first block
+/// data[7..10].copy_from_slice(&[0xE8, 0x03, 42]);
+/// data[1200..1204].copy_from_slice(&123_456u32.to_le_bytes());
+/// data[1204..].copy_from_slice(&[0x37, 0x21]);
+///
+/// let packet = Packet::try_from(&data[..]).unwrap();
+/// assert_eq!(packet.azimuth(), 10000);
+/// assert_eq!(packet.timestamp(), 123_456);
+/// assert_eq!((packet.status().id, packet.status().value), (0x37, 0x21));
+/// assert_eq!(packet.blocks().count(), 12);
+///
+/// let block = packet.blocks().next().unwrap();
+/// assert_eq!(block.header(), [0xFF, 0xEE]);
+/// let points: Vec<_> = block.points().collect();
+/// assert_eq!(points.len(), 1);
+/// assert_eq!(points[0].laser, 1);
+/// assert_eq!(points[0].distance, 1000);
+/// assert_eq!(points[0].intensity, 42);
+///
+/// assert_eq!(Packet::try_from(&data[1..]).err(), Some(InvalidPacket::InvalidLength));
+/// data[100] = 0;
+/// assert_eq!(Packet::try_from(&data[..]).err(), Some(InvalidPacket::InvalidHeader));
+/// ```
+#[repr(transparent)]
+#[derive(Clone)]
+pub struct Packet(RawPacket);
+
+impl Packet {
+    /// Validate raw packet data without copying it
+    pub fn from_raw(data: &RawPacket) -> Result<&Packet, InvalidPacket> {
+        if !block_iter(data).all(|b| VALID_HEADERS.contains(&b.header())) {
+            return Err(InvalidPacket::InvalidHeader);
+        }
+        // `Packet` is a transparent wrapper around `RawPacket`
+        Ok(unsafe { &*(data as *const RawPacket as *const Packet) })
+    }
+
+    /// Get underlying raw packet data
+    pub fn as_raw(&self) -> &RawPacket {
+        &self.0
+    }
+
+    /// Get packet meta information
+    pub fn meta(&self) -> PacketMeta {
+        packet_meta(&self.0)
+    }
+
+    /// Azimuth of the first block in hundredths of degree
+    pub fn azimuth(&self) -> u16 {
+        self.meta().azimuth
+    }
+
+    /// Packet timestamp in microseconds from the top of the hour
+    pub fn timestamp(&self) -> u32 {
+        self.meta().timestamp
+    }
+
+    /// Status bytes incorporated into the packet
+    pub fn status(&self) -> StatusBytes {
+        get_status(&self.0)
+    }
+
+    /// Iterate over packet firing blocks
+    pub fn blocks(&self) -> impl Iterator<Item=Block<'_>> {
+        block_iter(&self.0)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Packet {
+    type Error = InvalidPacket;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let data = <&RawPacket>::try_from(data)
+            .map_err(|_| InvalidPacket::InvalidLength)?;
+        Packet::from_raw(data).cloned()
+    }
+}
+
+impl fmt::Debug for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("meta", &self.meta())
+            .finish()
+    }
+}
+
+/// Firing block of the packet
+#[derive(Copy, Clone, Debug)]
+pub struct Block<'a>(&'a [u8]);
+
+impl<'a> Block<'a> {
+    /// Block header bytes
+    pub fn header(&self) -> [u8; 2] {
+        [self.0[0], self.0[1]]
+    }
+
+    /// Block azimuth in hundredths of degree
+    pub fn azimuth(&self) -> u16 {
+        LE::read_u16(&self.0[HEADER_SIZE..HEADER_SIZE+AZIMUTH_SIZE])
+    }
+
+    /// Iterate over block points, points with zero distance are skipped
+    pub fn points(&self) -> impl Iterator<Item=RawPoint> + 'a {
+        self.all_points().filter(|point| point.distance != 0)
+    }
+
+    /// Iterate over all 32 block points including points with zero distance
+    /// (i.e. without return)
+    pub fn all_points(&self) -> impl Iterator<Item=RawPoint> + 'a {
+        self.0[HEADER_SIZE+AZIMUTH_SIZE..]
+            .chunks_exact(POINT_SIZE)
+            .enumerate()
+            .map(|(laser, chunk)| {
+                let distance = LE::read_u16(&chunk[..2]);
+                let intensity = chunk[2];
+                let laser = laser as u8;
+                RawPoint { distance, intensity, laser }
+            })
+    }
+}
+
+/// Convert single block of packet data using `convertor`
+///
+/// Block is converted as the first block of a synthetic packet, in which
+/// azimuth increases by 0.2° with each block. This increment is used by
+/// convertors which interpolate azimuth (e.g. `Vlp16Convertor`). Since other
+/// returns of the same firing are not available, all points are reported as
+/// single returns.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::convert::TryFrom;
+/// use velodyne::{Convertor, FullPoint};
+/// use velodyne::hdl64::{CalibDb, Hdl64Convertor};
+/// use velodyne::packet::{PacketSource, PcapSource, convert_block};
+///
+/// let mut db = CalibDb::default();
+/// db.dist_lsb = 0.2;
+/// let convertor = Hdl64Convertor::new(db);
+///
+/// let mut source = PcapSource::new("data/hdl64.pcap", false, false)?;
+/// let (_, packet) = source.next_packet()?.unwrap();
+/// let mut points = vec![];
+/// convertor.convert(packet, |p: FullPoint| points.push(p)).unwrap();
+///
+/// // upper and lower blocks
+/// for i in 0..2 {
+///     let block = &packet[100*i..100*(i + 1)];
+///     let block = <&[u8; 100]>::try_from(block).unwrap();
+///     let block_points: Vec<_> = convert_block(block, &convertor)
+///         .unwrap()
+///         .collect();
+///     // file is recorded in the dual return mode, so skip strongest
+///     // returns stored in the paired blocks
+///     let azimuth = u16::from_le_bytes([block[2], block[3]]);
+///     let expected = points.iter().filter(|p| {
+///         p.laser_id/32 == i as u8 && p.azimuth == azimuth
+///             && p.return_number == p.number_of_returns
+///     });
+///     assert_eq!(block_points.len(), expected.clone().count());
+///     for (a, b) in block_points.iter().zip(expected) {
+///         assert_eq!(a.xyz, b.xyz);
+///         assert_eq!(a.laser_id, b.laser_id);
+///         assert_eq!(a.intensity, b.intensity);
+///     }
+/// }
+///
+/// let mut block = [0u8; 100];
+/// block[..2].copy_from_slice(&[0xAB, 0xCD]);
+/// assert!(convert_block(&block, &convertor).is_err());
+/// # Ok(()) }
+/// ```
+pub fn convert_block<C: Convertor>(
+    block: &[u8; BLOCK_SIZE], convertor: &C,
+) -> Result<impl Iterator<Item=FullPoint>, ConversionError> {
+    let header = [block[0], block[1]];
+    if !VALID_HEADERS.contains(&header) {
+        return Err(ConversionError::InvalidHeader);
+    }
+    let azimuth = Azimuth::new(LE::read_u16(&block[2..4]));
+    let step = Azimuth::new(SYNTHETIC_AZIMUTH_STEP);
+
+    let mut packet = [0u8; PACKET_SIZE];
+    let mut a = azimuth;
+    for chunk in packet[..BLOCKS_SIZE].chunks_exact_mut(BLOCK_SIZE) {
+        chunk[..2].copy_from_slice(&header);
+        chunk[2..4].copy_from_slice(&a.raw().to_le_bytes());
+        a = a + step;
+    }
+    packet[..BLOCK_SIZE].copy_from_slice(block);
+
+    let mut points = [FullPoint::default(); LASERS];
+    let mut n = 0;
+    convertor.try_convert(&packet, |p: FullPoint| {
+        // points of the next blocks have azimuth shifted at least by `step`
+        if Azimuth::new(p.azimuth) - azimuth >= step || n == LASERS {
+            return ControlFlow::Break(());
+        }
+        points[n] = p;
+        n += 1;
+        ControlFlow::Continue(())
+    })?;
+    Ok(IntoIterator::into_iter(points).take(n))
 }
 
 /// Source of raw sensor packets and basic parser.
+#[cfg(feature = "std")]
 pub trait PacketSource {
     /// Get next raw packet.
     ///
     /// Will return `Ok(None)` if source is exhausted.
     fn next_packet(&mut self) -> io::Result<Option<(SocketAddrV4, &RawPacket)>>;
+
+    /// Returns `true` if packet stream was interrupted (e.g. source has
+    /// looped to the start of a file or has skipped packets) since the last
+    /// call of this method.
+    ///
+    /// Used by `PointSource` to reset state accumulated by status listeners.
+    /// Default implementation always returns `false`.
+    fn take_discontinuity(&mut self) -> bool { false }
+
+    /// Returns `true` if `Ok(None)` returned by `next_packet` means that
+    /// source is exhausted and will not produce packets anymore.
+    ///
+    /// Sources which return `Ok(None)` on transient conditions (e.g.
+    /// `UdpSource` on timeout) should return `false`. Default implementation
+    /// always returns `true`.
+    fn is_exhausted(&self) -> bool { true }
+
+    /// Create iterator over packets of the source, see `Packets`
+    fn packets(&mut self) -> Packets<'_, Self> where Self: Sized {
+        Packets { source: self, done: false }
+    }
+}
+
+/// Iterator over packets of `PacketSource` created by
+/// `PacketSource::packets`
+///
+/// Packet returned by `next_packet` borrows the source, so it can not be
+/// yielded by `Iterator` directly and instead is copied into a `Box`. This
+/// costs an allocation per packet, for hot loops prefer calling
+/// `next_packet` directly. Iteration stops at the first `Ok(None)` returned
+/// by the source, errors are yielded and do not stop iteration.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::packet::{PacketSource, PcapSource, get_status};
+///
+/// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let statuses = source.packets()
+///     .take(3)
+///     .map(|res| res.map(|(_, packet)| get_status(&packet)))
+///     .collect::<std::io::Result<Vec<_>>>()?;
+/// assert_eq!(statuses.len(), 3);
+///
+/// let mut pcap = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let n = pcap.packets().count();
+/// assert!(n > 3);
+/// // source is exhausted, but can still be used after iteration
+/// assert!(pcap.next_packet()?.is_none());
+/// # Ok(()) }
+/// ```
+#[cfg(feature = "std")]
+pub struct Packets<'a, T: PacketSource> {
+    source: &'a mut T,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<T: PacketSource> Iterator for Packets<'_, T> {
+    type Item = io::Result<(SocketAddrV4, Box<RawPacket>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None; }
+        match self.source.next_packet() {
+            Ok(Some((addr, packet))) => Some(Ok((addr, Box::new(*packet)))),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(err) => Some(Err(err)),
+        }
+    }
 }
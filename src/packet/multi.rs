@@ -0,0 +1,111 @@
+use std::io;
+use std::net::SocketAddrV4;
+
+use super::{PacketSource, RawPacket, PACKET_SIZE};
+
+/// Combines several packet sources into one
+///
+/// Sources are polled in a round-robin fashion, so packets from different
+/// sensors are interleaved and can be distinguished by the returned source
+/// address (see `PointSource::set_sensor_id_for`). If polled source does not
+/// have a packet (e.g. UDP source timed out), the next one is tried.
+/// `Ok(None)` is returned only if all sources did not return a packet in one
+/// round.
+///
+/// Note that each idle `UdpSource` blocks for its timeout, so it's recommended
+/// to use short timeouts for combined UDP sources.
+///
+/// # Example
+/// ```
+/// use std::io;
+/// use std::net::SocketAddrV4;
+/// use velodyne::packet::{MultiSource, PacketSource, RawPacket};
+///
+/// /// Emits packets filled with the given bytes, zero emulates timeout
+/// struct Queue { port: u16, bytes: Vec<u8>, packet: RawPacket }
+///
+/// impl PacketSource for Queue {
+///     fn next_packet(&mut self)
+///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+///     {
+///         if self.bytes.is_empty() { return Ok(None); }
+///         match self.bytes.remove(0) {
+///             0 => Ok(None),
+///             b => {
+///                 self.packet.fill(b);
+///                 let ip = [10, 0, 0, 1].into();
+///                 Ok(Some((SocketAddrV4::new(ip, self.port), &self.packet)))
+///             },
+///         }
+///     }
+/// }
+///
+/// let queue = |port, bytes| Queue { port, bytes, packet: [0; 1206] };
+/// let mut source = MultiSource::new(vec![
+///     queue(2368, vec![1, 2, 3]),
+///     // the second sensor is idle during the first round
+///     queue(2369, vec![0, 4]),
+/// ]);
+/// let mut received = vec![];
+/// while let Some((addr, packet)) = source.next_packet().unwrap() {
+///     received.push((addr.port(), packet[0]));
+/// }
+/// assert_eq!(received, [(2368, 1), (2368, 2), (2369, 4), (2368, 3)]);
+/// ```
+pub struct MultiSource<T: PacketSource> {
+    sources: Vec<T>,
+    next: usize,
+    buf: RawPacket,
+}
+
+impl<T: PacketSource> MultiSource<T> {
+    /// Create new combined source
+    pub fn new(sources: Vec<T>) -> Self {
+        Self { sources, next: 0, buf: [0u8; PACKET_SIZE] }
+    }
+
+    /// Get reference to the combined sources
+    pub fn sources(&self) -> &[T] {
+        &self.sources
+    }
+
+    /// Get mutable reference to the combined sources
+    pub fn sources_mut(&mut self) -> &mut [T] {
+        &mut self.sources
+    }
+
+    /// Destruct combined source and return inner sources
+    pub fn into_inner(self) -> Vec<T> {
+        self.sources
+    }
+}
+
+impl<T: PacketSource> PacketSource for MultiSource<T> {
+    fn next_packet(&mut self)
+        -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    {
+        let n = self.sources.len();
+        for _ in 0..n {
+            let i = self.next;
+            self.next = (i + 1) % n;
+            if let Some((addr, packet)) = self.sources[i].next_packet()? {
+                self.buf.copy_from_slice(packet);
+                return Ok(Some((addr, &self.buf)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn take_discontinuity(&mut self) -> bool {
+        // flags of all sources must be cleared, so no short-circuiting
+        let mut res = false;
+        for source in self.sources.iter_mut() {
+            res |= source.take_discontinuity();
+        }
+        res
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.sources.iter().all(|s| s.is_exhausted())
+    }
+}
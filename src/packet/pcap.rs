@@ -1,40 +1,273 @@
-use byteorder::{ReadBytesExt, LE};
+use byteorder::{ByteOrder, ReadBytesExt, BE, LE};
 use std::time::{Instant, Duration};
 use std::fs::File;
 use std::path::Path;
 use std::io;
-use std::io::{SeekFrom, Seek, Read, ErrorKind, Cursor};
+use std::io::{SeekFrom, Seek, ErrorKind, Cursor};
+use std::convert::TryFrom;
 use std::thread::sleep;
 use std::net::{SocketAddrV4, Ipv4Addr};
 use log::warn;
+use chrono::{DateTime, Utc};
 
 use memmap::Mmap;
 
-use super::{PacketSource, RawPacket, PACKET_SIZE};
+use super::{PacketSource, RawPacket, DropStats, PACKET_SIZE};
+use super::pcapng::PCAPNG_MAGIC;
 
 const NS_IN_SEC: u32 = 1_000_000_000;
 
 // tcpdump -s 1248 -i enp2s0 -w out.pcap port 2368
 
+/// Size of pcap global header
+const GLOBAL_HEADER_SIZE: u64 = 24;
+/// Size of pcap record header
+const RECORD_HEADER_SIZE: u64 = 16;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+/// 802.1Q VLAN tag
+const ETHERTYPE_VLAN: u16 = 0x8100;
+/// 802.1ad (QinQ) service VLAN tag
+const ETHERTYPE_QINQ: u16 = 0x88A8;
+const IP_PROTO_UDP: u8 = 17;
+
+/// Parse Ethernet frame headers and find payload of the UDP datagram
+///
+/// Returns payload offset in the frame and sender address, or `None` if frame
+/// is not an IPv4 UDP datagram with at least `PACKET_SIZE` bytes of payload.
+/// VLAN tags and IP options are skipped.
+pub(super) fn parse_frame(frame: &[u8]) -> Option<(usize, SocketAddrV4)> {
+    // skip destination and source MAC addresses
+    let mut pos = 12;
+    let mut ethertype = BE::read_u16(frame.get(pos..pos + 2)?);
+    while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+        pos += 4;
+        ethertype = BE::read_u16(frame.get(pos..pos + 2)?);
+    }
+    if ethertype != ETHERTYPE_IPV4 { return None; }
+    let ip = frame.get(pos + 2..)?;
+    if ip.len() < 20 || ip[0] >> 4 != 4 || ip[9] != IP_PROTO_UDP {
+        return None;
+    }
+    // IHL field stores header length in 32-bit words
+    let ihl = 4*(ip[0] & 0x0F) as usize;
+    if ihl < 20 { return None; }
+    let udp = ip.get(ihl..)?;
+    if udp.len() < 8 + PACKET_SIZE { return None; }
+    let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+    let port = BE::read_u16(&udp[..2]);
+    Some((pos + 2 + ihl + 8, SocketAddrV4::new(src, port)))
+}
+
+/// Index of packet records in pcap file
+///
+/// Stores timestamp (seconds and nanoseconds since UNIX epoch) and file offset
+/// of each record sorted by timestamp.
+#[derive(Clone, Debug, Default)]
+pub struct PcapIndex {
+    entries: Vec<((u32, u32), u64)>,
+}
+
+impl PcapIndex {
+    fn build(data: &[u8], is_nano: bool) -> Self {
+        let mut entries = vec![];
+        let mut pos = GLOBAL_HEADER_SIZE;
+        let mut rdr = Cursor::new(data);
+        loop {
+            rdr.set_position(pos);
+            let mut meta = [0u32; 4];
+            if rdr.read_u32_into::<LE>(&mut meta).is_err() { break; }
+            let [t_s, t_us, incl_len, _] = meta;
+            let t = (t_s, t_us * if is_nano { 1 } else { 1000 });
+            entries.push((t, pos));
+            pos += RECORD_HEADER_SIZE + incl_len as u64;
+        }
+        // stable sort keeps file order for equal timestamps
+        entries.sort_by_key(|e| e.0);
+        Self { entries }
+    }
+
+    /// Get indexed records as `(timestamp, file_offset)` pairs
+    pub fn entries(&self) -> &[((u32, u32), u64)] {
+        &self.entries
+    }
+
+    /// Number of indexed records
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if file does not contain any records
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find first record with timestamp greater or equal to `t`
+    pub fn find(&self, t: (u32, u32)) -> Option<((u32, u32), u64)> {
+        let i = self.entries.partition_point(|e| e.0 < t);
+        self.entries.get(i).cloned()
+    }
+}
+
 /// Acquires and processes packets from pre-recorded pcap file
+///
+/// Ethernet and IP headers of each record are parsed, so captures with VLAN
+/// tags or IP options are supported. Records which are not IPv4 UDP datagrams
+/// are skipped.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::net::SocketAddrV4;
+/// use velodyne::packet::{PcapSource, PacketSource};
+///
+/// // the file contains the first two packets of `hdl32.pcap`, the first one
+/// // has VLAN tag and the second one has IP options and source port 2369,
+/// // records are separated by a TCP segment
+/// let mut source = PcapSource::new("data/vlan.pcap", false, false)?;
+/// let mut pcap = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// for &port in [2368, 2369].iter() {
+///     let (addr, packet) = source.next_packet()?.unwrap();
+///     assert_eq!(addr, SocketAddrV4::new([192, 168, 1, 200].into(), port));
+///     let packet = *packet;
+///     assert_eq!(&packet[..], &pcap.next_packet()?.unwrap().1[..]);
+/// }
+/// assert!(source.next_packet()?.is_none());
+/// assert_eq!(source.drop_stats().non_udp, 1);
+/// # Ok(()) }
+/// ```
+///
+/// Record with data cut off by the end of file (e.g. if recording was
+/// interrupted) is treated as the end of file:
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::packet::{PcapSource, PacketSource};
+///
+/// // keep two packets and the first 100 bytes of the third one
+/// let data = std::fs::read("data/hdl32.pcap")?;
+/// let mut pos = 24;
+/// for _ in 0..2 {
+///     let incl_len = u32::from_le_bytes([
+///         data[pos + 8], data[pos + 9], data[pos + 10], data[pos + 11],
+///     ]);
+///     pos += 16 + incl_len as usize;
+/// }
+/// let path = std::env::temp_dir().join("velodyne_truncated.pcap");
+/// std::fs::write(&path, &data[..pos + 16 + 100])?;
+///
+/// let mut source = PcapSource::new(&path, false, false)?;
+/// assert!(source.next_packet()?.is_some());
+/// assert!(source.next_packet()?.is_some());
+/// assert!(source.next_packet()?.is_none());
+/// assert_eq!(source.byte_position(), source.byte_len());
+/// std::fs::remove_file(&path)?;
+/// # Ok(()) }
+/// ```
 pub struct PcapSource {
     file: Cursor<Mmap>,
     is_nano: bool,
     do_sync: bool,
     do_loop: bool,
-    packet_t0: (u32, u32),
-    t0: Instant,
+    speed: f32,
+    clock: SyncClock,
+    discontinuity: bool,
+    index: Option<PcapIndex>,
+    drop_stats: DropStats,
 }
 
 impl PcapSource {
     /// Initialize source with the given `path`.
     ///
     /// If `do_sync` is `true` will emulate arrival of packets using recorded
-    /// timings, otherwise it will emit packets as fast as it can.
+    /// timings, otherwise it will emit packets as fast as it can. Packets
+    /// with timestamps older than the latest emitted one (e.g. in captures
+    /// merged from several interfaces) are emitted without delay.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::time::Instant;
+    /// use velodyne::packet::{PcapSource, PacketSource};
+    ///
+    /// // Ethernet, IPv4 and UDP headers followed by zeroed packet data
+    /// let mut frame = [0; 1248];
+    /// frame[12..14].copy_from_slice(&[0x08, 0x00]);
+    /// frame[14] = 0x45;
+    /// frame[23] = 17;
+    ///
+    /// // microsecond pcap with out-of-order record at 100 ms
+    /// let mut data = vec![];
+    /// data.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    /// data.extend_from_slice(&[2, 0, 4, 0]);
+    /// data.extend_from_slice(&[0; 12]);
+    /// data.extend_from_slice(&1u32.to_le_bytes());
+    /// for &t_us in [0u32, 200_000, 100_000, 300_000].iter() {
+    ///     for &v in [1000, t_us, 1248, 1248].iter() {
+    ///         data.extend_from_slice(&u32::to_le_bytes(v));
+    ///     }
+    ///     data.extend_from_slice(&frame);
+    /// }
+    /// let path = std::env::temp_dir().join("velodyne_out_of_order.pcap");
+    /// std::fs::write(&path, data)?;
+    ///
+    /// let mut source = PcapSource::new(&path, true, false)?;
+    /// let t = Instant::now();
+    /// let mut n = 0;
+    /// while source.next_packet()?.is_some() { n += 1; }
+    /// let elapsed = t.elapsed().as_secs_f32();
+    /// assert_eq!(n, 4);
+    /// // replay takes at least as long as the recording, out-of-order
+    /// // record does not stop synchronization of the following ones
+    /// assert!(elapsed >= 0.3, "{}", elapsed);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `InvalidData` error if file does not contain any records,
+    /// e.g. if it's empty or contains only the global header.
+    ///
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::io::ErrorKind;
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let mut header = vec![];
+    /// header.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    /// header.extend_from_slice(&[2, 0, 4, 0]);
+    /// header.extend_from_slice(&[0; 12]);
+    /// header.extend_from_slice(&1u32.to_le_bytes());
+    /// assert_eq!(header.len(), 24);
+    ///
+    /// let path = std::env::temp_dir().join("velodyne_empty.pcap");
+    /// for data in [&header[..], &[]].iter() {
+    ///     std::fs::write(&path, data)?;
+    ///     let err = PcapSource::new(&path, false, true).err().unwrap();
+    ///     assert_eq!(err.kind(), ErrorKind::InvalidData);
+    ///     assert_eq!(err.to_string(), "pcap contains no packets");
+    /// }
+    ///
+    /// // file with a single short record is exhausted even in loop mode
+    /// let mut data = header.clone();
+    /// for &v in [0, 0, 60, 60].iter() {
+    ///     data.extend_from_slice(&u32::to_le_bytes(v));
+    /// }
+    /// data.extend_from_slice(&[0; 60]);
+    /// std::fs::write(&path, data)?;
+    /// let mut source = PcapSource::new(&path, false, true)?;
+    /// assert!(source.next_packet()?.is_none());
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(()) }
+    /// ```
     pub fn new<P: AsRef<Path>>(path: P, do_sync: bool, do_loop: bool)
         -> io::Result<Self>
     {
         let file = File::open(path)?;
+        // memory map can not be created for an empty file
+        if file.metadata()?.len() <= GLOBAL_HEADER_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData,
+                "pcap contains no packets"));
+        }
         let mmap = unsafe { Mmap::map(&file)? };
         let mut f = Cursor::new(mmap);
 
@@ -43,6 +276,8 @@ impl PcapSource {
             0xa1b23c4d => (true, true),
             0xd4c3b2a1 => (false, false),
             0x4d3cb2a1 => (false, true),
+            PCAPNG_MAGIC => return Err(io::Error::new(ErrorKind::InvalidInput,
+                "file is in pcapng format, use `PcapngSource` instead")),
             _ => return Err(io::Error::new(ErrorKind::InvalidInput,
                 "invalid pcap magic number")),
         };
@@ -66,22 +301,229 @@ impl PcapSource {
         // Check LINKTYPE_ETHERNET
         assert_eq!(network, 1, "expected LINKTYPE_ETHERNET");
 
-        // time from UNIX_EPOCH
-        // note that this time is not Y2038 safe
-        let packet_t0 = (
-            file.read_u32::<LE>()?,
-            file.read_u32::<LE>()? * if is_nano { 1 } else { 1000 },
-        );
-        // seek back from peeking into start time
-        file.seek(SeekFrom::Current(-8))?;
+        Ok(Self {
+            file, is_nano, do_sync, do_loop, speed: 1.,
+            clock: SyncClock::new(), discontinuity: false, index: None,
+            drop_stats: Default::default(),
+        })
+    }
 
-        let t0 = Instant::now();
-        Ok(Self { file, is_nano, do_sync, do_loop, packet_t0, t0 })
+    /// Set replay speed multiplier used when `do_sync` is `true`.
+    ///
+    /// Delays between packets are scaled by `1/speed`, i.e. `2.0` replays
+    /// file twice as fast as it was recorded and `0.5` twice as slow. Default
+    /// value is `1.0`. Non-positive (and NaN) values are rejected.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::time::Instant;
+    /// use velodyne::packet::{PcapSource, PacketSource};
+    ///
+    /// // Ethernet, IPv4 and UDP headers followed by zeroed packet data
+    /// let mut frame = [0; 1248];
+    /// frame[12..14].copy_from_slice(&[0x08, 0x00]);
+    /// frame[14] = 0x45;
+    /// frame[23] = 17;
+    ///
+    /// // microsecond pcap with two records recorded 100 ms apart
+    /// let mut data = vec![];
+    /// data.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    /// data.extend_from_slice(&[2, 0, 4, 0]);
+    /// data.extend_from_slice(&[0; 12]);
+    /// data.extend_from_slice(&1u32.to_le_bytes());
+    /// for &t_us in [0u32, 100_000].iter() {
+    ///     for &v in [1000, t_us, 1248, 1248].iter() {
+    ///         data.extend_from_slice(&u32::to_le_bytes(v));
+    ///     }
+    ///     data.extend_from_slice(&frame);
+    /// }
+    /// let path = std::env::temp_dir().join("velodyne_speed.pcap");
+    /// std::fs::write(&path, data)?;
+    ///
+    /// let mut source = PcapSource::new(&path, true, false)?;
+    /// assert!(source.set_speed(0.).is_err());
+    /// assert!(source.set_speed(f32::NAN).is_err());
+    /// assert_eq!(source.get_speed(), 1.);
+    /// source.set_speed(2.)?;
+    /// assert!(source.next_packet()?.is_some());
+    /// let t = Instant::now();
+    /// assert!(source.next_packet()?.is_some());
+    /// // the packets are emitted about 50 ms apart
+    /// let dt = t.elapsed().as_secs_f32();
+    /// assert!(dt > 0.04 && dt < 0.075, "{}", dt);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn set_speed(&mut self, speed: f32) -> io::Result<()> {
+        if speed.is_nan() || speed <= 0. {
+            return Err(io::Error::new(ErrorKind::InvalidInput,
+                "replay speed must be positive"));
+        }
+        self.speed = speed;
+        Ok(())
+    }
+
+    /// Get counters of packets dropped since source creation
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::packet::{PcapSource, PacketSource};
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// while source.next_packet()?.is_some() {}
+    /// // the file contains 584 GPS position packets
+    /// assert_eq!(source.drop_stats().short, 584);
+    /// # Ok(()) }
+    /// ```
+    pub fn drop_stats(&self) -> DropStats {
+        self.drop_stats
+    }
+
+    /// Get current replay speed multiplier
+    pub fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Get capture start time, i.e. timestamp of the first record in the
+    /// file, or `None` if file does not contain records.
+    ///
+    /// Note that pcap stores seconds in a 32-bit field, so the time is not
+    /// Y2038 safe.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// // nanosecond pcap with a single record
+    /// let mut data = vec![];
+    /// data.extend_from_slice(&0xa1b23c4du32.to_le_bytes());
+    /// data.extend_from_slice(&[2, 0, 4, 0]);
+    /// data.extend_from_slice(&[0; 12]);
+    /// data.extend_from_slice(&1u32.to_le_bytes());
+    /// for &v in [1_542_188_632, 710_507_123, 1248, 1248].iter() {
+    ///     data.extend_from_slice(&u32::to_le_bytes(v));
+    /// }
+    /// data.extend_from_slice(&[0; 1248]);
+    /// let path = std::env::temp_dir().join("velodyne_start_time.pcap");
+    /// std::fs::write(&path, data)?;
+    ///
+    /// let source = PcapSource::new(&path, false, false)?;
+    /// let t = source.start_time().unwrap();
+    /// assert_eq!(t.to_rfc3339(), "2018-11-14T09:43:52.710507123+00:00");
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn start_time(&self) -> Option<DateTime<Utc>> {
+        let data: &[u8] = self.file.get_ref();
+        let start = GLOBAL_HEADER_SIZE as usize;
+        let mut header = data.get(start..start + 8)?;
+        let t_s = header.read_u32::<LE>().ok()?;
+        let t_frac = header.read_u32::<LE>().ok()?;
+        let t_ns = t_frac * if self.is_nano { 1 } else { 1000 };
+        DateTime::from_timestamp(t_s as i64, t_ns)
+    }
+
+    /// Get current read position in the file in bytes
+    pub fn byte_position(&self) -> u64 {
+        self.file.position()
+    }
+
+    /// Get length of the file in bytes
+    pub fn byte_len(&self) -> u64 {
+        self.file.get_ref().len() as u64
+    }
+
+    /// Get fraction of the file which was replayed, from 0 to 1
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::packet::{PacketSource, PcapSource};
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let mut prev = source.progress();
+    /// assert!(prev < 0.01);
+    /// while source.next_packet()?.is_some() {
+    ///     let progress = source.progress();
+    ///     assert!(progress > prev);
+    ///     prev = progress;
+    /// }
+    /// assert_eq!(source.progress(), 1.);
+    /// assert_eq!(source.byte_position(), source.byte_len());
+    /// # Ok(()) }
+    /// ```
+    pub fn progress(&self) -> f32 {
+        let len = self.byte_len();
+        if len == 0 { return 1.; }
+        (self.byte_position() as f64 / len as f64) as f32
     }
 
     pub fn reset(&mut self) {
-        self.file.set_position(24);
-        self.t0 = Instant::now();
+        self.file.set_position(GLOBAL_HEADER_SIZE);
+        self.clock = SyncClock::new();
+        self.discontinuity = true;
+    }
+
+    /// Get index of the file records, index is built on the first call
+    pub fn index(&mut self) -> &PcapIndex {
+        let (file, is_nano) = (&self.file, self.is_nano);
+        self.index.get_or_insert_with(|| {
+            PcapIndex::build(file.get_ref().as_ref(), is_nano)
+        })
+    }
+
+    /// Seek to the first packet with timestamp greater or equal to `t`
+    /// (seconds and nanoseconds since UNIX epoch).
+    ///
+    /// If all packets are older than `t`, source will be positioned at the end
+    /// of file. Returns timestamp of the packet at which source was positioned.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::packet::{PcapSource, PacketSource};
+    ///
+    /// // read the last two packets sequentially
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let mut last = vec![];
+    /// while let Some((_, packet)) = source.next_packet()? {
+    ///     last.push(*packet);
+    ///     if last.len() > 2 { last.remove(0); }
+    /// }
+    ///
+    /// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let entries = source.index().entries().to_vec();
+    /// let (t, _) = entries[entries.len() - 2];
+    /// assert_eq!(source.seek_to_time(t), Some(t));
+    /// assert!(source.take_discontinuity());
+    /// for expected in last.iter() {
+    ///     assert_eq!(&source.next_packet()?.unwrap().1[..], &expected[..]);
+    /// }
+    /// assert!(source.next_packet()?.is_none());
+    ///
+    /// // seeking past the last packet positions source at the end of file
+    /// assert_eq!(source.seek_to_time((t.0 + 1, 0)), None);
+    /// assert!(source.next_packet()?.is_none());
+    /// // seeking before the first packet rewinds source
+    /// assert_eq!(source.seek_to_time((0, 0)), Some(entries[0].0));
+    /// assert_eq!(source.byte_position(), entries[0].1);
+    /// # Ok(()) }
+    /// ```
+    pub fn seek_to_time(&mut self, t: (u32, u32)) -> Option<(u32, u32)> {
+        let res = self.index().find(t);
+        match res {
+            Some((_, pos)) => self.file.set_position(pos),
+            None => {
+                let len = self.file.get_ref().len() as u64;
+                self.file.set_position(len);
+            },
+        }
+        self.clock = SyncClock::new();
+        self.discontinuity = true;
+        res.map(|(t, _)| t)
     }
 
     fn read_packet(&mut self) -> io::Result<(u64, SocketAddrV4)> {
@@ -94,12 +536,14 @@ impl PcapSource {
         // 20 bytes for IP header (without options)
         // 8 bytes for UDP header
         if orig_len < PACKET_SIZE as u32 + 42 {
-            // VeloView records unindentified short packets which we ignore
-            warn!("unindentified short packet");
+            // VeloView records unidentified short packets which we ignore
+            warn!("unidentified short packet");
+            self.drop_stats.short += 1;
             self.file.set_position(eth_start + incl_len as u64);
             return self.read_packet();
         }
         if orig_len > incl_len {
+            self.drop_stats.truncated += 1;
             self.file.set_position(eth_start + incl_len as u64);
             Err(io::Error::new(io::ErrorKind::InvalidData,
                 "UDP packet was truncated"))?;
@@ -107,49 +551,85 @@ impl PcapSource {
 
         let t = (t_s, t_us * if self.is_nano { 1 } else { 1000 } );
 
-        let delta: i64 = orig_len as i64 - PACKET_SIZE as i64 - 16;
+        let eth_end = eth_start + incl_len as u64;
+        let file_len = self.file.get_ref().len() as u64;
+        self.file.set_position(eth_end);
+        // record header can be intact while its data was cut off (e.g. if
+        // recording was interrupted), so we treat it as the end of file
+        if eth_end > file_len {
+            self.file.set_position(file_len);
+            warn!("last packet in the file is truncated");
+            self.drop_stats.truncated += 1;
+            Err(io::Error::new(ErrorKind::UnexpectedEof,
+                "packet data is out of file bounds"))?;
+        }
+
+        let frame = &self.file.get_ref()[eth_start as usize..eth_end as usize];
+        let (offset, addr) = match parse_frame(frame) {
+            Some(res) => res,
+            None => {
+                warn!("packet is not an IPv4 UDP datagram");
+                self.drop_stats.non_udp += 1;
+                return self.read_packet();
+            },
+        };
 
-        // Skip Ethernet headers
-        self.file.seek(SeekFrom::Current(delta))?;
+        if self.do_sync { self.clock.sync(t, self.speed); }
 
-        let mut h = [0u8; 16];
-        self.file.read_exact(&mut h)?;
-        let port = ((h[12] as u16) << 8) + (h[13] as u16);
-        let addr = SocketAddrV4::new(Ipv4Addr::new(h[0], h[1], h[2], h[3]), port);
+        Ok((eth_start + offset as u64, addr))
+    }
 
-        let udp_pos = self.file.position();
-        self.file.set_position(eth_start + incl_len as u64);
+}
 
-        if self.do_sync { self.time_sync(t); }
+/// Emulation of the recorded packet timings
+///
+/// Delays are computed from differences between timestamps of consecutive
+/// packets, so timestamps older than the latest seen one (e.g. in captures
+/// merged from several interfaces) do not cause any delay and do not affect
+/// timing of the following packets.
+pub(super) struct SyncClock {
+    // time at which the first packet was emitted
+    t0: Instant,
+    // emulated time since `t0` at which the latest packet should be emitted
+    elapsed: Duration,
+    // the latest timestamp among emitted packets
+    latest: Option<(u32, u32)>,
+}
 
-        Ok((udp_pos, addr))
+impl SyncClock {
+    pub(super) fn new() -> Self {
+        Self { t0: Instant::now(), elapsed: Duration::ZERO, latest: None }
     }
 
-    fn time_sync(&self, t: (u32, u32)) {
+    /// Sleep until packet with timestamp `t` (seconds and nanoseconds since
+    /// UNIX epoch) should be emitted, delays are scaled by `1/speed`
+    pub(super) fn sync(&mut self, t: (u32, u32), speed: f32) {
+        let latest = match self.latest {
+            Some(latest) => latest,
+            None => {
+                self.t0 = Instant::now();
+                self.latest = Some(t);
+                return;
+            },
+        };
+        if t <= latest { return; }
+        self.latest = Some(t);
+
+        let mut dt_s = t.0 - latest.0;
+        let dt_ns = if t.1 >= latest.1 {
+            t.1 - latest.1
+        } else {
+            dt_s -= 1;
+            t.1 + NS_IN_SEC - latest.1
+        };
+        self.elapsed += Duration::new(dt_s as u64, dt_ns)
+            .div_f64(speed as f64);
+
         // realtime time difference
         let rt_dt = self.t0.elapsed();
-        let (rt_s, rt_ns) = (rt_dt.as_secs(), rt_dt.subsec_nanos());
-        // time difference between packets
-        let t0 = self.packet_t0;
-        let mut dt_s = (t.0 as i64) -  (t0.0 as i64);
-        let mut dt_ns = (t.1 as i32) - (t0.1 as i32);
-        if dt_ns < 0 {
-            dt_s -= 1;
-            dt_ns += NS_IN_SEC as i32;
+        if let Some(delay) = self.elapsed.checked_sub(rt_dt) {
+            sleep(delay);
         }
-        if dt_s < 0 { return; }
-        assert!(dt_ns >= 0 && dt_ns < NS_IN_SEC as i32,
-            "nanoseconds out of range");
-        let p_s = dt_s as u64;
-        let p_ns = dt_ns as u32;
-
-        sleep(if p_s >= rt_s && p_ns > rt_ns {
-            Duration::new(p_s - rt_s, p_ns - rt_ns )
-        } else if p_s > rt_s && p_ns <= rt_ns {
-            Duration::new(p_s - rt_s - 1, (NS_IN_SEC + p_ns) - rt_ns )
-        } else {
-            return;
-        })
     }
 }
 
@@ -157,26 +637,30 @@ impl PacketSource for PcapSource {
     fn next_packet(&mut self)
         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
     {
-        match self.read_packet() {
+        let is_eof = |res: &io::Result<_>| matches!(res,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof);
+        let mut res = self.read_packet();
+        // file can be exhausted right after reset if it contains only
+        // skipped records, so we reset only once
+        if self.do_loop && is_eof(&res) {
+            self.reset();
+            res = self.read_packet();
+        }
+        match res {
             Ok((pos, addr)) => {
-                let buf = self.file.get_ref();
-                // we rely on `read_packet` to return correct `pos`
-                debug_assert!(buf.len() > (pos as usize) + PACKET_SIZE);
-                let packet = unsafe {
-                    &*(buf.as_ref().as_ptr().offset(pos as isize)
-                        as *const [u8; PACKET_SIZE])
-                };
+                let buf: &[u8] = self.file.get_ref().as_ref();
+                let pos = pos as usize;
+                // `read_packet` checks that packet fits into the file
+                let packet = <&RawPacket>::try_from(&buf[pos..pos + PACKET_SIZE])
+                    .expect("slice has PACKET_SIZE length");
                 Ok(Some((addr, packet)))
             },
-            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => {
-                if self.do_loop {
-                    self.reset();
-                    self.next_packet()
-                } else {
-                    Ok(None)
-                }
-            },
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
             Err(e) => Err(e),
         }
     }
+
+    fn take_discontinuity(&mut self) -> bool {
+        std::mem::replace(&mut self.discontinuity, false)
+    }
 }
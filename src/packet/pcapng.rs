@@ -0,0 +1,384 @@
+use byteorder::{ByteOrder, LE};
+use std::fs::File;
+use std::path::Path;
+use std::io;
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::convert::TryFrom;
+use std::net::SocketAddrV4;
+use log::warn;
+
+use memmap::Mmap;
+
+use super::{PacketSource, RawPacket, DropStats, PACKET_SIZE};
+use super::pcap::{SyncClock, parse_frame};
+
+/// Block type of Section Header Block, also used as file magic number
+pub(super) const PCAPNG_MAGIC: u32 = 0x0A0D0D0A;
+/// Block type of Interface Description Block
+const IDB_TYPE: u32 = 0x00000001;
+/// Block type of Enhanced Packet Block
+const EPB_TYPE: u32 = 0x00000006;
+/// Byte-order magic stored in Section Header Block
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_IF_TSRESOL: u16 = 9;
+
+const LINKTYPE_ETHERNET: u16 = 1;
+
+/// Default timestamp resolution (microseconds) used if interface does not
+/// have `if_tsresol` option
+const DEFAULT_UNITS_PER_SEC: u64 = 1_000_000;
+const NS_IN_SEC: u64 = 1_000_000_000;
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Interface described by Interface Description Block
+#[derive(Copy, Clone, Debug)]
+struct Interface {
+    linktype: u16,
+    /// Number of timestamp units in a second
+    units_per_sec: u64,
+}
+
+impl Interface {
+    fn parse(body: &[u8]) -> io::Result<Self> {
+        if body.len() < 8 {
+            return Err(invalid_data(
+                "Interface Description Block is too short"));
+        }
+        let linktype = LE::read_u16(&body[..2]);
+        let mut units_per_sec = DEFAULT_UNITS_PER_SEC;
+        // skip linktype, reserved field and snaplen
+        let mut pos = 8;
+        while pos + 4 <= body.len() {
+            let code = LE::read_u16(&body[pos..]);
+            let len = LE::read_u16(&body[pos + 2..]) as usize;
+            if code == OPT_ENDOFOPT { break; }
+            let value = body.get(pos + 4..pos + 4 + len)
+                .ok_or_else(|| invalid_data("option is out of block bounds"))?;
+            if code == OPT_IF_TSRESOL && len == 1 {
+                // MSB selects between negative powers of 10 and 2
+                let exp = value[0] & 0x7F;
+                let units = if value[0] & 0x80 == 0 {
+                    10u64.checked_pow(exp as u32)
+                } else {
+                    1u64.checked_shl(exp as u32)
+                };
+                units_per_sec = units
+                    .ok_or_else(|| invalid_data("unsupported if_tsresol"))?;
+            }
+            // option values are padded to 32 bits
+            pos += 4 + ((len + 3) & !3);
+        }
+        Ok(Self { linktype, units_per_sec })
+    }
+
+    /// Convert timestamp in interface units to seconds and nanoseconds
+    fn timestamp(&self, ts: u64) -> (u32, u32) {
+        let units = self.units_per_sec;
+        let ns = (ts % units) as u128 * NS_IN_SEC as u128 / units as u128;
+        // note that this time is not Y2038 safe
+        ((ts / units) as u32, ns as u32)
+    }
+}
+
+/// Acquires and processes packets from pre-recorded pcapng file
+///
+/// Only little-endian files with Ethernet interfaces are supported. Packets
+/// are read from Enhanced Packet Blocks, timestamps are converted using
+/// resolution of the interface on which packet was captured.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::packet::{PcapngSource, PcapSource, PacketSource};
+///
+/// // the file contains the first 5 records of `hdl32.pcap` with timestamps
+/// // stored in nanoseconds
+/// let mut source = PcapngSource::new("data/hdl32.pcapng", false, false)?;
+/// let mut pcap = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let mut n = 0;
+/// while let Some((_, packet)) = source.next_packet()? {
+///     let packet = *packet;
+///     assert_eq!(&packet[..], &pcap.next_packet()?.unwrap().1[..]);
+///     n += 1;
+/// }
+/// assert_eq!(n, 4);
+/// assert_eq!(source.drop_stats().short, 1);
+/// assert_eq!(source.last_timestamp(), Some((1542188632, 710_507_000)));
+/// # Ok(()) }
+/// ```
+///
+/// Malformed blocks are reported as `InvalidData` errors:
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::io::ErrorKind;
+/// use velodyne::packet::{PcapngSource, PacketSource};
+///
+/// // Section Header Block without body, its trailing length field happens
+/// // to match the byte-order magic
+/// let mut data = vec![];
+/// for v in &[0x0A0D0D0Au32, 12, 0x1A2B3C4D] {
+///     data.extend_from_slice(&v.to_le_bytes());
+/// }
+/// let path = std::env::temp_dir().join("velodyne_short_shb.pcapng");
+/// std::fs::write(&path, data)?;
+///
+/// let mut source = PcapngSource::new(&path, false, false)?;
+/// let err = source.next_packet().err().unwrap();
+/// assert_eq!(err.kind(), ErrorKind::InvalidData);
+/// std::fs::remove_file(&path)?;
+/// # Ok(()) }
+/// ```
+pub struct PcapngSource {
+    data: Mmap,
+    pos: usize,
+    interfaces: Vec<Interface>,
+    do_sync: bool,
+    do_loop: bool,
+    speed: f32,
+    clock: SyncClock,
+    discontinuity: bool,
+    last_timestamp: Option<(u32, u32)>,
+    drop_stats: DropStats,
+}
+
+impl PcapngSource {
+    /// Initialize source with the given `path`.
+    ///
+    /// If `do_sync` is `true` will emulate arrival of packets using recorded
+    /// timings, otherwise it will emit packets as fast as it can.
+    pub fn new<P: AsRef<Path>>(path: P, do_sync: bool, do_loop: bool)
+        -> io::Result<Self>
+    {
+        let file = File::open(path)?;
+        let data = unsafe { Mmap::map(&file)? };
+        if data.len() < 12 || LE::read_u32(&data[..4]) != PCAPNG_MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidInput,
+                "invalid pcapng magic number"));
+        }
+        if LE::read_u32(&data[8..12]) != BYTE_ORDER_MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidInput,
+                "big-endian pcapng files currently not supported"));
+        }
+        Ok(Self {
+            data, pos: 0, interfaces: vec![], do_sync, do_loop, speed: 1.,
+            clock: SyncClock::new(), discontinuity: false,
+            last_timestamp: None, drop_stats: Default::default(),
+        })
+    }
+
+    /// Set replay speed multiplier used when `do_sync` is `true`, see
+    /// `PcapSource::set_speed`.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::time::Instant;
+    /// use velodyne::packet::{PcapngSource, PacketSource};
+    ///
+    /// // Ethernet, IPv4 and UDP headers followed by zeroed packet data
+    /// let mut frame = [0; 1248];
+    /// frame[12..14].copy_from_slice(&[0x08, 0x00]);
+    /// frame[14] = 0x45;
+    /// frame[23] = 17;
+    ///
+    /// // section header, Ethernet interface with microsecond timestamps
+    /// // and two packets recorded 100 ms apart
+    /// fn push(data: &mut Vec<u8>, vals: &[u32]) {
+    ///     for v in vals { data.extend_from_slice(&v.to_le_bytes()); }
+    /// }
+    /// let mut data = vec![];
+    /// push(&mut data, &[0x0A0D0D0A, 28, 0x1A2B3C4D, 1, !0, !0, 28]);
+    /// push(&mut data, &[1, 20, 1, 0, 20]);
+    /// for &t_us in [0u32, 100_000].iter() {
+    ///     push(&mut data, &[6, 1280, 0, 0, t_us, 1248, 1248]);
+    ///     data.extend_from_slice(&frame);
+    ///     push(&mut data, &[1280]);
+    /// }
+    /// let path = std::env::temp_dir().join("velodyne_speed.pcapng");
+    /// std::fs::write(&path, data)?;
+    ///
+    /// let mut source = PcapngSource::new(&path, true, false)?;
+    /// assert!(source.set_speed(0.).is_err());
+    /// assert!(source.set_speed(f32::NAN).is_err());
+    /// source.set_speed(2.)?;
+    /// assert!(source.next_packet()?.is_some());
+    /// let t = Instant::now();
+    /// assert!(source.next_packet()?.is_some());
+    /// // the packets are emitted about 50 ms apart
+    /// let dt = t.elapsed().as_secs_f32();
+    /// assert!(dt > 0.04 && dt < 0.075, "{}", dt);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn set_speed(&mut self, speed: f32) -> io::Result<()> {
+        if speed.is_nan() || speed <= 0. {
+            return Err(io::Error::new(ErrorKind::InvalidInput,
+                "replay speed must be positive"));
+        }
+        self.speed = speed;
+        Ok(())
+    }
+
+    /// Get current replay speed multiplier
+    pub fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Get counters of packets dropped since source creation
+    pub fn drop_stats(&self) -> DropStats {
+        self.drop_stats
+    }
+
+    /// Get timestamp (seconds and nanoseconds since UNIX epoch) of the last
+    /// packet returned by the source
+    pub fn last_timestamp(&self) -> Option<(u32, u32)> {
+        self.last_timestamp
+    }
+
+    pub fn reset(&mut self) {
+        self.pos = 0;
+        self.interfaces.clear();
+        self.clock = SyncClock::new();
+        self.discontinuity = true;
+    }
+
+    /// Read next block and return its type and range of its body
+    fn next_block(&mut self) -> io::Result<Option<(u32, Range<usize>)>> {
+        let len = self.data.len();
+        if self.pos + 12 > len {
+            if self.pos != len {
+                warn!("last block in the file is truncated");
+                self.pos = len;
+            }
+            return Ok(None);
+        }
+        let block_type = LE::read_u32(&self.data[self.pos..]);
+        let block_len = LE::read_u32(&self.data[self.pos + 4..]) as usize;
+        if block_len < 12 || block_len & 3 != 0 {
+            return Err(invalid_data("invalid pcapng block length"));
+        }
+        if self.pos + block_len > len {
+            // recording was interrupted, treat it as the end of file
+            warn!("last block in the file is truncated");
+            if block_type == EPB_TYPE { self.drop_stats.truncated += 1; }
+            self.pos = len;
+            return Ok(None);
+        }
+        let body = self.pos + 8..self.pos + block_len - 4;
+        self.pos += block_len;
+        Ok(Some((block_type, body)))
+    }
+
+    /// Read blocks until the next Velodyne packet and return its position
+    fn read_packet(&mut self) -> io::Result<Option<(usize, SocketAddrV4)>> {
+        loop {
+            let (block_type, body) = match self.next_block()? {
+                Some(block) => block,
+                None => return Ok(None),
+            };
+            match block_type {
+                PCAPNG_MAGIC => {
+                    // interface IDs are local to the section
+                    self.interfaces.clear();
+                    // byte-order magic, version and section length
+                    if body.len() < 16 {
+                        return Err(invalid_data(
+                            "Section Header Block is too short"));
+                    }
+                    if LE::read_u32(&self.data[body]) != BYTE_ORDER_MAGIC {
+                        return Err(invalid_data(
+                            "big-endian pcapng sections not supported"));
+                    }
+                },
+                IDB_TYPE => {
+                    let iface = Interface::parse(&self.data[body])?;
+                    self.interfaces.push(iface);
+                },
+                EPB_TYPE => if let Some(res) = self.read_epb(body)? {
+                    return Ok(Some(res));
+                },
+                // skip statistics, name resolution and other blocks
+                _ => (),
+            }
+        }
+    }
+
+    fn read_epb(&mut self, body: Range<usize>)
+        -> io::Result<Option<(usize, SocketAddrV4)>>
+    {
+        if body.len() < 20 {
+            return Err(invalid_data("Enhanced Packet Block is too short"));
+        }
+        let mut meta = [0u32; 5];
+        LE::read_u32_into(&self.data[body.start..body.start + 20], &mut meta);
+        let [iface_id, ts_high, ts_low, incl_len, orig_len] = meta;
+        let iface = *self.interfaces.get(iface_id as usize)
+            .ok_or_else(|| invalid_data("packet refers to unknown interface"))?;
+        if iface.linktype != LINKTYPE_ETHERNET {
+            return Err(invalid_data("expected LINKTYPE_ETHERNET"));
+        }
+        let eth_start = body.start + 20;
+        if eth_start + incl_len as usize > body.end {
+            return Err(invalid_data("packet data is out of block bounds"));
+        }
+
+        // 14 bytes for Ethernet header
+        // 20 bytes for IP header (without options)
+        // 8 bytes for UDP header
+        if orig_len < PACKET_SIZE as u32 + 42 {
+            warn!("unidentified short packet");
+            self.drop_stats.short += 1;
+            return Ok(None);
+        }
+        if orig_len > incl_len {
+            self.drop_stats.truncated += 1;
+            return Err(invalid_data("UDP packet was truncated"));
+        }
+
+        let frame = &self.data[eth_start..eth_start + incl_len as usize];
+        let (offset, addr) = match parse_frame(frame) {
+            Some(res) => res,
+            None => {
+                warn!("packet is not an IPv4 UDP datagram");
+                self.drop_stats.non_udp += 1;
+                return Ok(None);
+            },
+        };
+        let udp_pos = eth_start + offset;
+
+        let t = iface.timestamp(((ts_high as u64) << 32) | ts_low as u64);
+        self.last_timestamp = Some(t);
+        if self.do_sync { self.clock.sync(t, self.speed); }
+
+        Ok(Some((udp_pos, addr)))
+    }
+}
+
+impl PacketSource for PcapngSource {
+    fn next_packet(&mut self)
+        -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    {
+        let mut res = self.read_packet()?;
+        if res.is_none() && self.do_loop {
+            self.reset();
+            res = self.read_packet()?;
+        }
+        let data: &[u8] = &self.data;
+        Ok(res.map(|(pos, addr)| {
+            // `read_epb` checks that packet fits into the block
+            let packet = <&RawPacket>::try_from(&data[pos..pos + PACKET_SIZE])
+                .expect("slice has PACKET_SIZE length");
+            (addr, packet)
+        }))
+    }
+
+    fn take_discontinuity(&mut self) -> bool {
+        std::mem::replace(&mut self.discontinuity, false)
+    }
+}
@@ -0,0 +1,215 @@
+use byteorder::{ByteOrder, BE};
+use std::io;
+use std::mem;
+use std::ffi::CString;
+use std::time::Duration;
+use std::convert::TryFrom;
+use std::net::SocketAddrV4;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+use super::{PacketSource, RawPacket, DropStats, PACKET_SIZE};
+use super::pcap::parse_frame;
+
+/// Size of the frame buffer, enough for Velodyne packets with headers
+const BUF_SIZE: usize = 2048;
+
+/// Protocol value which captures frames of all protocols
+fn eth_p_all() -> u16 {
+    (libc::ETH_P_ALL as u16).to_be()
+}
+
+/// Acquires packets from raw `AF_PACKET` socket bound to a network interface
+///
+/// Unlike `UdpSource` it does not require binding of the UDP port, so it can
+/// capture packets sent to a port owned by another process or not owned at
+/// all. Ethernet, IPv4 and UDP headers of the received frames are parsed in
+/// the same way as for pcap files, only IPv4 UDP datagrams with 1206 bytes of
+/// payload sent to the selected port are returned. Frames sent by the host
+/// itself are skipped.
+///
+/// Opening the socket requires `CAP_NET_RAW` capability. Frames are read
+/// using `recvfrom`, `PACKET_MMAP` ring buffer is not used.
+///
+/// Requires `af_packet` feature and available only on Linux.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use std::net::{SocketAddr, UdpSocket};
+/// use std::time::Duration;
+/// use velodyne::packet::{PacketSource, RawSocketSource};
+///
+/// // nobody listens on the port, but packets are still captured
+/// let port = 52368;
+/// let timeout = Some(Duration::from_secs(1));
+/// // requires `CAP_NET_RAW` capability
+/// let mut source = RawSocketSource::new("lo", port, timeout)?;
+///
+/// let sender = UdpSocket::bind("127.0.0.1:0")?;
+/// let mut packet = [0u8; 1206];
+/// packet[1200..1204].copy_from_slice(&42u32.to_le_bytes());
+/// sender.send_to(&packet, ("127.0.0.1", port + 1))?;
+/// sender.send_to(&packet[..100], ("127.0.0.1", port))?;
+/// sender.send_to(&packet, ("127.0.0.1", port))?;
+///
+/// let (addr, received) = source.next_packet()?.unwrap();
+/// assert_eq!(SocketAddr::V4(addr), sender.local_addr()?);
+/// assert_eq!(&received[..], &packet[..]);
+/// assert!(source.drop_stats().wrong_port >= 1);
+/// # Ok(()) }
+/// ```
+pub struct RawSocketSource {
+    fd: OwnedFd,
+    port: u16,
+    buf: [u8; BUF_SIZE],
+    drop_stats: DropStats,
+}
+
+impl RawSocketSource {
+    /// Capture packets sent to UDP `port` on the network `interface` (e.g.
+    /// `eth0`)
+    ///
+    /// If `timeout` is `None` `next_packet` blocks until a packet is
+    /// received, otherwise it returns `Ok(None)` after the timeout.
+    pub fn new(interface: &str, port: u16, timeout: Option<Duration>)
+        -> io::Result<Self>
+    {
+        let name = CString::new(interface).map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidInput, "interface name contains zero byte"))?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 { return Err(io::Error::last_os_error()); }
+
+        let fd = unsafe {
+            libc::socket(libc::AF_PACKET, libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                eth_p_all() as libc::c_int)
+        };
+        if fd < 0 { return Err(io::Error::last_os_error()); }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = eth_p_all();
+        addr.sll_ifindex = index as i32;
+        let res = unsafe {
+            libc::bind(fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t)
+        };
+        if res < 0 { return Err(io::Error::last_os_error()); }
+
+        let source = Self {
+            fd, port, buf: [0u8; BUF_SIZE], drop_stats: Default::default(),
+        };
+        source.set_read_timeout(timeout)?;
+        Ok(source)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        // zero `timeval` disables the timeout
+        let tv = match timeout {
+            Some(t) if t.as_nanos() == 0 => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    "cannot set a 0 duration timeout"));
+            },
+            Some(t) => {
+                let sec = t.as_secs().min(libc::time_t::MAX as u64);
+                let mut usec = t.subsec_micros();
+                // round up, so sub-microsecond timeouts do not become zero
+                if sec == 0 && usec == 0 { usec = 1; }
+                libc::timeval {
+                    tv_sec: sec as libc::time_t,
+                    tv_usec: usec as libc::suseconds_t,
+                }
+            },
+            None => libc::timeval { tv_sec: 0, tv_usec: 0 },
+        };
+        let res = unsafe {
+            libc::setsockopt(self.fd.as_raw_fd(), libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO, &tv as *const libc::timeval as *const _,
+                mem::size_of::<libc::timeval>() as libc::socklen_t)
+        };
+        if res < 0 { return Err(io::Error::last_os_error()); }
+        Ok(())
+    }
+
+    /// Get UDP port of the captured packets
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    /// Get counters of packets dropped since source creation
+    pub fn drop_stats(&self) -> DropStats {
+        self.drop_stats
+    }
+
+    /// Receive the next frame, returns its length or `None` on timeout
+    fn recv_frame(&mut self) -> io::Result<Option<usize>> {
+        loop {
+            let mut addr: libc::sockaddr_ll = unsafe { mem::zeroed() };
+            let mut addr_len =
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+            // with `MSG_TRUNC` real frame length is returned
+            let n = unsafe {
+                libc::recvfrom(self.fd.as_raw_fd(),
+                    self.buf.as_mut_ptr() as *mut libc::c_void, BUF_SIZE,
+                    libc::MSG_TRUNC,
+                    &mut addr as *mut libc::sockaddr_ll as *mut libc::sockaddr,
+                    &mut addr_len)
+            };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                        Ok(None)
+                    },
+                    io::ErrorKind::Interrupted => continue,
+                    _ => Err(err),
+                };
+            }
+            if addr.sll_pkttype == libc::PACKET_OUTGOING { continue; }
+            if n as usize > BUF_SIZE {
+                // too large to be a Velodyne packet
+                continue;
+            }
+            return Ok(Some(n as usize));
+        }
+    }
+}
+
+impl PacketSource for RawSocketSource {
+    fn next_packet(&mut self)
+        -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    {
+        let (pos, addr) = loop {
+            let n = match self.recv_frame()? {
+                Some(n) => n,
+                None => return Ok(None),
+            };
+            let frame = &self.buf[..n];
+            let (pos, addr) = match parse_frame(frame) {
+                Some(res) => res,
+                None => continue,
+            };
+            // UDP header precedes the payload
+            let udp = &frame[pos - 8..pos];
+            if BE::read_u16(&udp[2..4]) != self.port {
+                self.drop_stats.wrong_port += 1;
+                continue;
+            }
+            // UDP length includes the header
+            if BE::read_u16(&udp[4..6]) as usize != PACKET_SIZE + 8 {
+                self.drop_stats.size_mismatch += 1;
+                continue;
+            }
+            break (pos, addr);
+        };
+        let packet = <&RawPacket>::try_from(&self.buf[pos..pos + PACKET_SIZE])
+            .expect("slice has PACKET_SIZE length");
+        Ok(Some((addr, packet)))
+    }
+
+    fn is_exhausted(&self) -> bool {
+        // `Ok(None)` is returned only on timeout
+        false
+    }
+}
@@ -0,0 +1,91 @@
+use crate::ReturnType;
+
+use super::{RawPacket, dual_return_partners};
+
+/// Detector of the sensor return mode based on layout of the packets
+///
+/// In the dual return mode each firing is reported in two blocks with the
+/// same header and azimuth, while in the single return mode azimuth always
+/// advances between blocks with the same header. Detector classifies each
+/// fed packet and updates detected mode once per `window` packets using
+/// the majority of them.
+///
+/// Note that the strongest and the last return modes can not be
+/// distinguished using packet layout, so the single return mode is reported
+/// as `ReturnType::Strongest`.
+///
+/// # Example
+/// ```
+/// use velodyne::ReturnType;
+/// use velodyne::packet::ReturnModeDetector;
+///
+/// let packet = |dual: bool| {
+///     let mut packet = [0u8; 1206];
+///     for (i, block) in packet[..1200].chunks_exact_mut(100).enumerate() {
+///         let step = if dual { i/2 } else { i };
+///         let azimuth = 20*step as u16;
+///         block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///         block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+///     }
+///     packet
+/// };
+///
+/// let mut detector = ReturnModeDetector::new(4);
+/// for _ in 0..3 { detector.feed(&packet(false)); }
+/// // window is not filled yet
+/// assert_eq!(detector.detected_mode(), None);
+/// detector.feed(&packet(false));
+/// assert_eq!(detector.detected_mode(), Some(ReturnType::Strongest));
+///
+/// for _ in 0..4 { detector.feed(&packet(true)); }
+/// assert_eq!(detector.detected_mode(), Some(ReturnType::Both));
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ReturnModeDetector {
+    window: usize,
+    // number of packets and dual return packets in the current window
+    packets: usize,
+    dual: usize,
+    detected: Option<ReturnType>,
+}
+
+impl ReturnModeDetector {
+    /// Create new detector which updates detected mode once per `window`
+    /// packets
+    ///
+    /// # Panics
+    /// If `window` is equal to zero.
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "window must be positive");
+        Self { window, packets: 0, dual: 0, detected: None }
+    }
+
+    /// Classify `packet` and update detected mode if window is filled
+    pub fn feed(&mut self, packet: &RawPacket) {
+        let partners = dual_return_partners(packet);
+        if partners.iter().all(|p| p.is_some()) {
+            self.dual += 1;
+        }
+        self.packets += 1;
+        if self.packets == self.window {
+            self.detected = Some(if 2*self.dual > self.window {
+                ReturnType::Both
+            } else {
+                ReturnType::Strongest
+            });
+            self.packets = 0;
+            self.dual = 0;
+        }
+    }
+
+    /// Get mode detected in the last filled window, `None` if not enough
+    /// packets were fed
+    pub fn detected_mode(&self) -> Option<ReturnType> {
+        self.detected
+    }
+
+    /// Forget detected mode and packets of the current window
+    pub fn reset(&mut self) {
+        *self = Self::new(self.window);
+    }
+}
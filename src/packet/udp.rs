@@ -1,15 +1,101 @@
 use std::io;
-use std::net::{UdpSocket, ToSocketAddrs, SocketAddrV4, SocketAddr};
+use std::net::{UdpSocket, ToSocketAddrs, SocketAddrV4, SocketAddr, Ipv4Addr};
 use std::time::Duration;
+use std::convert::TryFrom;
+use log::warn;
+use socket2::SockRef;
 
-use super::{PacketSource, RawPacket, PACKET_SIZE};
+use super::{PacketSource, RawPacket, DropStats, PACKET_SIZE};
 
-const DEFAULT_ADDR: &'static str = "0.0.0.0:2368";
+const DEFAULT_ADDR: &str = "0.0.0.0:2368";
+const DEFAULT_PORT: u16 = 2368;
+/// Size of packet without status bytes
+const MIN_PACKET_SIZE: usize = PACKET_SIZE - 2;
+/// Maximum size of datagram which can be received by the source
+const MAX_PACKET_SIZE: usize = 2048;
+
+/// Returns `true` for errors after which socket stays usable, e.g.
+/// `ConnectionReset` caused by ICMP "port unreachable" response to a
+/// previously sent datagram (reported on Windows)
+fn is_transient(err: &io::Error) -> bool {
+    matches!(err.kind(),
+        io::ErrorKind::ConnectionReset | io::ErrorKind::Interrupted)
+}
+
+/// Socket used by `UdpSource` for reception of datagrams
+///
+/// Implemented for `UdpSocket`, other implementations allow to run
+/// `UdpSource` on top of custom transports or mocks.
+pub trait RecvSocket {
+    /// Receive a single datagram, see `UdpSocket::recv_from`
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+
+    /// Switch socket into or out of the non-blocking mode, see
+    /// `UdpSocket::set_nonblocking`
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl RecvSocket for UdpSocket {
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UdpSocket::set_nonblocking(self, nonblocking)
+    }
+}
 
 /// Acquires and processes packets from the network
-pub struct UdpSource {
-    socket: UdpSocket,
-    buf: RawPacket,
+///
+/// Transient socket errors (`ConnectionReset` and `Interrupted`) are logged
+/// and skipped by `next_packet`, other errors are returned.
+///
+/// # Example
+/// ```
+/// use std::cell::RefCell;
+/// use std::io;
+/// use std::net::SocketAddr;
+/// use velodyne::packet::{PacketSource, RecvSocket, UdpSource};
+///
+/// /// Returns queued errors before each datagram
+/// struct Flaky(RefCell<Vec<io::ErrorKind>>);
+///
+/// impl RecvSocket for Flaky {
+///     fn recv_from(&self, buf: &mut [u8])
+///         -> io::Result<(usize, SocketAddr)>
+///     {
+///         if let Some(kind) = self.0.borrow_mut().pop() {
+///             return Err(kind.into());
+///         }
+///         buf[..1206].fill(7);
+///         Ok((1206, ([10, 0, 0, 1], 2368).into()))
+///     }
+///
+///     fn set_nonblocking(&self, _: bool) -> io::Result<()> { Ok(()) }
+/// }
+///
+/// use io::ErrorKind::{Interrupted, ConnectionReset, PermissionDenied};
+/// let socket = Flaky(RefCell::new(vec![ConnectionReset, Interrupted]));
+/// let mut source = UdpSource::new_custom_socket(socket);
+/// // transient errors are skipped
+/// let (_, packet) = source.next_packet().unwrap().unwrap();
+/// assert_eq!(packet[0], 7);
+/// assert!(source.get_socket().0.borrow().is_empty());
+///
+/// // other errors are returned
+/// source.get_socket().0.borrow_mut().push(PermissionDenied);
+/// let err = source.next_packet().unwrap_err();
+/// assert_eq!(err.kind(), PermissionDenied);
+/// assert!(source.next_packet().unwrap().is_some());
+/// ```
+pub struct UdpSource<S: RecvSocket = UdpSocket> {
+    socket: S,
+    buf: [u8; MAX_PACKET_SIZE],
+    packet_size: usize,
+    paused: bool,
+    discontinuity: bool,
+    drop_stats: DropStats,
+    source_filter: Option<Ipv4Addr>,
 }
 
 impl UdpSource {
@@ -18,6 +104,11 @@ impl UdpSource {
         Self::new_custom(DEFAULT_ADDR, Some(Duration::from_secs(1)))
     }
 
+    /// Create builder for configuration of all socket options at once
+    pub fn builder() -> UdpSourceBuilder {
+        UdpSourceBuilder::default()
+    }
+
     /// Listen for inbound UDP packets on specified address
     pub fn new_custom<A>(addr: A, timeout: Option<Duration>)
         -> io::Result<Self>
@@ -28,36 +119,434 @@ impl UdpSource {
         Ok(Self::new_custom_socket(socket))
     }
 
+    /// Listen for inbound UDP packets on specified address using socket
+    /// receive buffer of `recv_buffer_size` bytes, see
+    /// `set_recv_buffer_size`
+    pub fn new_custom_with_buffer<A>(
+        addr: A, timeout: Option<Duration>, recv_buffer_size: usize,
+    ) -> io::Result<Self>
+        where A: ToSocketAddrs
+    {
+        let mut source = Self::new_custom(addr, timeout)?;
+        source.set_recv_buffer_size(recv_buffer_size)?;
+        Ok(source)
+    }
+
+    /// Set size of the socket receive buffer (`SO_RCVBUF`) in bytes
+    ///
+    /// Packets which arrive while the buffer is full are silently dropped by
+    /// OS, so default buffer size can be insufficient at full data rate of
+    /// the sensor on a busy host. Recommended value for full-rate capture is
+    /// 4 MiB. Note that OS can adjust the requested value, e.g. Linux doubles
+    /// it and limits it by `net.core.rmem_max`.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::time::Duration;
+    /// use velodyne::packet::UdpSource;
+    ///
+    /// let timeout = Some(Duration::from_millis(10));
+    /// let mut source = UdpSource::new_custom("127.0.0.1:0", timeout)?;
+    /// source.set_recv_buffer_size(64*1024)?;
+    /// assert!(source.get_recv_buffer_size()? >= 64*1024);
+    /// # Ok(()) }
+    /// ```
+    pub fn set_recv_buffer_size(&mut self, bytes: usize) -> io::Result<()> {
+        SockRef::from(&self.socket).set_recv_buffer_size(bytes)
+    }
+
+    /// Get size of the socket receive buffer in bytes as reported by OS
+    pub fn get_recv_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&self.socket).recv_buffer_size()
+    }
+
+    /// Get local address to which socket is bound
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+impl<S: RecvSocket> UdpSource<S> {
     /// Listen for inbound UDP packets on initialized socket
-    pub fn new_custom_socket(socket: UdpSocket) -> Self {
-        Self { socket: socket, buf: [0u8; PACKET_SIZE] }
+    ///
+    /// Socket must be in the blocking mode, optionally with read timeout.
+    /// To discard pending packets `resume` and `next_packet` of a paused
+    /// source temporarily switch it to the non-blocking mode and always
+    /// switch it back to the blocking one.
+    pub fn new_custom_socket(socket: S) -> Self {
+        Self {
+            socket, buf: [0u8; MAX_PACKET_SIZE], packet_size: PACKET_SIZE,
+            paused: false, discontinuity: false,
+            drop_stats: Default::default(), source_filter: None,
+        }
+    }
+
+    /// Pause packet acquisition without closing the socket
+    ///
+    /// While source is paused `next_packet` discards all received packets and
+    /// immediately returns `Ok(None)` in the same way as on timeout, so
+    /// paused source does not interrupt `MultiSource` or `PointSource`. Use
+    /// `is_paused` to distinguish pause from timeout.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::net::UdpSocket;
+    /// use std::time::Duration;
+    /// use velodyne::packet::{PacketSource, UdpSource};
+    ///
+    /// let socket = UdpSocket::bind("127.0.0.1:0")?;
+    /// socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    /// let addr = socket.local_addr()?;
+    /// let mut source = UdpSource::new_custom_socket(socket);
+    /// let sender = UdpSocket::bind("127.0.0.1:0")?;
+    ///
+    /// source.pause();
+    /// assert!(source.is_paused());
+    /// sender.send_to(&[1; 1206], addr)?;
+    /// std::thread::sleep(Duration::from_millis(50));
+    /// assert!(source.next_packet()?.is_none());
+    ///
+    /// // packets received during the pause are discarded
+    /// sender.send_to(&[2; 1206], addr)?;
+    /// std::thread::sleep(Duration::from_millis(50));
+    /// source.resume()?;
+    /// assert!(!source.is_paused());
+    /// assert!(source.take_discontinuity());
+    /// sender.send_to(&[3; 1206], addr)?;
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// assert_eq!(packet[0], 3);
+    /// # Ok(()) }
+    /// ```
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resume packet acquisition, packets received while source was paused
+    /// are discarded.
+    pub fn resume(&mut self) -> io::Result<()> {
+        self.drain()?;
+        self.paused = false;
+        self.discontinuity = true;
+        Ok(())
+    }
+
+    /// Returns `true` if source is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Accept only packets sent from the `allowed` address
+    ///
+    /// Packets from other senders are silently skipped. By default packets
+    /// from all senders are accepted.
+    ///
+    /// # Example
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::io;
+    /// use std::net::{Ipv4Addr, SocketAddr};
+    /// use velodyne::packet::{PacketSource, RecvSocket, UdpSource};
+    ///
+    /// /// Emits queued packets from the given hosts, filled with the last
+    /// /// byte of the sender address
+    /// struct Hosts(RefCell<Vec<u8>>);
+    ///
+    /// impl RecvSocket for Hosts {
+    ///     fn recv_from(&self, buf: &mut [u8])
+    ///         -> io::Result<(usize, SocketAddr)>
+    ///     {
+    ///         let mut hosts = self.0.borrow_mut();
+    ///         if hosts.is_empty() {
+    ///             return Err(io::ErrorKind::WouldBlock.into());
+    ///         }
+    ///         let host = hosts.remove(0);
+    ///         buf[..1206].fill(host);
+    ///         Ok((1206, ([10, 0, 0, host], 2368).into()))
+    ///     }
+    ///
+    ///     fn set_nonblocking(&self, _: bool) -> io::Result<()> { Ok(()) }
+    /// }
+    ///
+    /// let hosts = || Hosts(RefCell::new(vec![1, 2, 2, 1, 2]));
+    /// let mut source = UdpSource::new_custom_socket(hosts());
+    /// source.set_source_filter(Ipv4Addr::new(10, 0, 0, 2));
+    /// let mut received = vec![];
+    /// while let Some((addr, packet)) = source.next_packet().unwrap() {
+    ///     assert_eq!(addr.ip().octets()[3], packet[0]);
+    ///     received.push(packet[0]);
+    /// }
+    /// assert_eq!(received, [2, 2, 2]);
+    /// assert_eq!(source.drop_stats().wrong_source, 2);
+    ///
+    /// // all packets are accepted without filter
+    /// let mut source = UdpSource::new_custom_socket(hosts());
+    /// source.clear_source_filter();
+    /// let mut n = 0;
+    /// while source.next_packet().unwrap().is_some() { n += 1; }
+    /// assert_eq!(n, 5);
+    /// ```
+    pub fn set_source_filter(&mut self, allowed: Ipv4Addr) {
+        self.source_filter = Some(allowed);
+    }
+
+    /// Accept packets from all senders
+    pub fn clear_source_filter(&mut self) {
+        self.source_filter = None;
+    }
+
+    /// Set expected size of received datagrams in bytes, by default it's
+    /// equal to 1206
+    ///
+    /// Datagrams of other sizes are rejected by `next_packet`. Some firmware
+    /// versions append a footer to the packet or do not send the status bytes,
+    /// so `size` can be in the range from 1204 to 2048 bytes. Only the first
+    /// 1206 bytes of larger datagrams are used, missing status bytes are
+    /// reported as zeros.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::net::UdpSocket;
+    /// use std::time::Duration;
+    /// use velodyne::packet::{PacketSource, UdpSource};
+    ///
+    /// let socket = UdpSocket::bind("127.0.0.1:0")?;
+    /// socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+    /// let addr = socket.local_addr()?;
+    /// let mut source = UdpSource::new_custom_socket(socket);
+    /// source.set_packet_size(1248)?;
+    /// assert_eq!(source.get_packet_size(), 1248);
+    /// assert!(source.set_packet_size(1000).is_err());
+    ///
+    /// let mut data = [0u8; 1248];
+    /// for (i, b) in data.iter_mut().enumerate() { *b = i as u8; }
+    /// let sender = UdpSocket::bind("127.0.0.1:0")?;
+    /// sender.send_to(&data, addr)?;
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// assert_eq!(&packet[..], &data[..1206]);
+    ///
+    /// // standard packet size does not match the configured one
+    /// sender.send_to(&data[..1206], addr)?;
+    /// assert!(source.next_packet().is_err());
+    /// assert_eq!(source.drop_stats().size_mismatch, 1);
+    ///
+    /// // status bytes are stripped
+    /// source.set_packet_size(1204)?;
+    /// sender.send_to(&data[..1204], addr)?;
+    /// let (_, packet) = source.next_packet()?.unwrap();
+    /// assert_eq!(&packet[..1204], &data[..1204]);
+    /// assert_eq!(&packet[1204..], &[0, 0]);
+    /// # Ok(()) }
+    /// ```
+    pub fn set_packet_size(&mut self, size: usize) -> io::Result<()> {
+        if !(MIN_PACKET_SIZE..=MAX_PACKET_SIZE).contains(&size) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "packet size must be in the range from 1204 to 2048 bytes"));
+        }
+        self.packet_size = size;
+        Ok(())
+    }
+
+    /// Get expected size of received datagrams in bytes
+    pub fn get_packet_size(&self) -> usize {
+        self.packet_size
+    }
+
+    /// Get counters of packets dropped since source creation
+    pub fn drop_stats(&self) -> DropStats {
+        self.drop_stats
+    }
+
+    /// Get reference to the used socket
+    pub fn get_socket(&self) -> &S {
+        &self.socket
+    }
+
+    /// Discard all packets pending in the socket buffer, the socket is
+    /// switched back to the blocking mode afterwards
+    fn drain(&mut self) -> io::Result<()> {
+        self.socket.set_nonblocking(true)?;
+        let res = loop {
+            match self.socket.recv_from(&mut self.buf) {
+                Ok(_) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    break Ok(())
+                },
+                Err(e) => break Err(e),
+            }
+        };
+        self.socket.set_nonblocking(false)?;
+        res
+    }
+}
+
+/// Builder of `UdpSource` with the given socket options
+///
+/// By default source listens on port 2368 of all interfaces with 1 second
+/// timeout, same as `UdpSource::new`.
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use std::net::{SocketAddr, UdpSocket};
+/// use std::time::{Duration, Instant};
+/// use velodyne::packet::{PacketSource, UdpSource};
+///
+/// let mut source = UdpSource::builder()
+///     .with_port(0)
+///     .with_timeout(Some(Duration::from_millis(50)))
+///     .with_recv_buffer(64*1024)
+///     .with_source_filter([127, 0, 0, 1].into())
+///     .with_packet_size(1248)
+///     .build()?;
+/// assert!(source.get_recv_buffer_size()? >= 64*1024);
+/// assert_eq!(source.get_packet_size(), 1248);
+///
+/// // ephemeral port was assigned
+/// let addr = source.local_addr()?;
+/// assert_ne!(addr.port(), 0);
+/// let sender = UdpSocket::bind("127.0.0.1:0")?;
+/// sender.send_to(&[1; 1248], ("127.0.0.1", addr.port()))?;
+/// let (from, packet) = source.next_packet()?.unwrap();
+/// assert_eq!(SocketAddr::V4(from), sender.local_addr()?);
+/// assert_eq!(packet[0], 1);
+///
+/// let t = Instant::now();
+/// assert!(source.next_packet()?.is_none());
+/// assert!(t.elapsed() < Duration::from_millis(500));
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct UdpSourceBuilder {
+    port: u16,
+    timeout: Option<Duration>,
+    recv_buffer: Option<usize>,
+    multicast: Option<(Ipv4Addr, Ipv4Addr)>,
+    source_filter: Option<Ipv4Addr>,
+    packet_size: usize,
+}
+
+impl Default for UdpSourceBuilder {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT, timeout: Some(Duration::from_secs(1)),
+            recv_buffer: None, multicast: None, source_filter: None,
+            packet_size: PACKET_SIZE,
+        }
+    }
+}
+
+impl UdpSourceBuilder {
+    /// Listen on the given `port`, zero selects an ephemeral port
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Set socket read timeout, `None` blocks indefinitely
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set size of the socket receive buffer, see
+    /// `UdpSource::set_recv_buffer_size`
+    pub fn with_recv_buffer(mut self, bytes: usize) -> Self {
+        self.recv_buffer = Some(bytes);
+        self
+    }
+
+    /// Join multicast `group` on the interface with `interface` address
+    /// (`0.0.0.0` lets OS choose the interface)
+    pub fn with_multicast(mut self, group: Ipv4Addr, interface: Ipv4Addr)
+        -> Self
+    {
+        self.multicast = Some((group, interface));
+        self
+    }
+
+    /// Accept only packets sent from the `allowed` address, see
+    /// `UdpSource::set_source_filter`
+    pub fn with_source_filter(mut self, allowed: Ipv4Addr) -> Self {
+        self.source_filter = Some(allowed);
+        self
+    }
+
+    /// Set expected size of received datagrams, see
+    /// `UdpSource::set_packet_size`
+    pub fn with_packet_size(mut self, size: usize) -> Self {
+        self.packet_size = size;
+        self
+    }
+
+    /// Bind socket and create source with the configured options
+    pub fn build(self) -> io::Result<UdpSource> {
+        let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, self.port);
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(self.timeout)?;
+        if let Some((group, interface)) = self.multicast {
+            socket.join_multicast_v4(&group, &interface)?;
+        }
+        let mut source = UdpSource::new_custom_socket(socket);
+        if let Some(bytes) = self.recv_buffer {
+            source.set_recv_buffer_size(bytes)?;
+        }
+        if let Some(allowed) = self.source_filter {
+            source.set_source_filter(allowed);
+        }
+        source.set_packet_size(self.packet_size)?;
+        Ok(source)
     }
 }
 
-impl PacketSource for UdpSource {
+impl<S: RecvSocket> PacketSource for UdpSource<S> {
     fn next_packet(&mut self)
         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
     {
-        let socket = &self.socket;
-        let buf = &mut self.buf;
-        match socket.recv_from(buf) {
-            Ok((n, addr)) => if n != PACKET_SIZE {
-                    Err(io::Error::new(io::ErrorKind::InvalidData,
-                        "Packet is smaller than 1206 bytes"))
-                } else {
-                    match addr {
-                        SocketAddr::V4(addr) => Ok(Some((addr, &*buf))),
-                        SocketAddr::V6(_) => panic!("IPv6 is not supported"),
-                    }
-
+        if self.paused {
+            self.drain()?;
+            return Ok(None);
+        }
+        let (n, addr) = loop {
+            match self.socket.recv_from(&mut self.buf) {
+                Ok((n, SocketAddr::V4(addr))) => {
+                    let allowed = self.source_filter
+                        .map_or(true, |ip| ip == *addr.ip());
+                    if allowed { break (n, addr); }
+                    self.drop_stats.wrong_source += 1;
+                },
+                Ok((_, SocketAddr::V6(_))) => panic!("IPv6 is not supported"),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    return Ok(None)
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(None)
                 },
-            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                Ok(None)
-            },
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                Ok(None)
-            },
-            Err(e) => Err(e),
+                Err(ref e) if is_transient(e) => {
+                    warn!("transient socket error, retrying: {}", e);
+                },
+                Err(e) => return Err(e),
+            }
+        };
+        if n != self.packet_size {
+            self.drop_stats.size_mismatch += 1;
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "Packet size is not equal to the expected one"));
         }
+        if n < PACKET_SIZE { self.buf[n..PACKET_SIZE].fill(0); }
+        let packet = <&RawPacket>::try_from(&self.buf[..PACKET_SIZE])
+            .expect("slice has PACKET_SIZE length");
+        Ok(Some((addr, packet)))
+    }
+
+    fn take_discontinuity(&mut self) -> bool {
+        std::mem::replace(&mut self.discontinuity, false)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        // `Ok(None)` is returned only on timeout
+        false
     }
 }
@@ -0,0 +1,151 @@
+//! Additional point types which can be used as conversion targets
+use crate::FullPoint;
+#[cfg(feature = "std")]
+use crate::{Convertor, ConversionError};
+#[cfg(feature = "std")]
+use crate::packet::{RawPacket, PacketMeta};
+
+/// Point packed into 4 floats: `[x, y, z, intensity]`
+///
+/// Intensity is stored as float without normalization, i.e. in the `[0, 255]`
+/// range. The struct is `#[repr(transparent)]`, so a slice of points has the
+/// same memory layout as a tightly packed `f32` buffer with stride of 16 bytes
+/// (4 floats) per point, which can be directly uploaded to GPU.
+#[repr(transparent)]
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct PackedXYZI(pub [f32; 4]);
+
+impl PackedXYZI {
+    /// View slice of points as a flat `f32` buffer of length `4*points.len()`
+    pub fn as_flat(points: &[PackedXYZI]) -> &[f32] {
+        // `PackedXYZI` is a transparent wrapper around `[f32; 4]`
+        unsafe {
+            core::slice::from_raw_parts(
+                points.as_ptr() as *const f32, 4*points.len())
+        }
+    }
+}
+
+impl From<FullPoint> for PackedXYZI {
+    fn from(p: FullPoint) -> Self {
+        let [x, y, z] = p.xyz;
+        PackedXYZI([x, y, z, p.intensity as f32])
+    }
+}
+
+/// `FullPoint` with double precision coordinates
+///
+/// Conversion from `FullPoint` only upcasts coordinates, use
+/// `Hdl32Convertor::convert_f64` to compute coordinates without intermediate
+/// single precision rounding.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct FullPointF64 {
+    /// XYZ coordinates of the point
+    pub xyz: [f64; 3],
+    /// Laser number which has measured the point
+    pub laser_id: u8,
+    /// Intensity value
+    pub intensity: u8,
+    /// Intensity value with extended range, see `FullPoint::intensity16`
+    pub intensity16: u16,
+    /// Point measurment timestamp in microseconds from the top of the hour
+    pub timestamp: u32,
+    /// Return number of the point, see `FullPoint::return_number`
+    pub return_number: u8,
+    /// Number of distinct returns measured by the laser in the same firing
+    pub number_of_returns: u8,
+    /// Sensor azimuth at which the point was measured in hundredths of degree
+    pub azimuth: u16,
+    /// ID of the sensor which has measured the point
+    pub sensor_id: u8,
+}
+
+impl From<FullPoint> for FullPointF64 {
+    fn from(p: FullPoint) -> Self {
+        let [x, y, z] = p.xyz;
+        FullPointF64 {
+            xyz: [x as f64, y as f64, z as f64],
+            laser_id: p.laser_id,
+            intensity: p.intensity,
+            intensity16: p.intensity16,
+            timestamp: p.timestamp,
+            return_number: p.return_number,
+            number_of_returns: p.number_of_returns,
+            azimuth: p.azimuth,
+            sensor_id: p.sensor_id,
+        }
+    }
+}
+
+/// Point with intensity normalized to the `[0, 1]` range
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::{TurnIterator, FullPoint, FloatPoint};
+/// use velodyne::packet::PcapSource;
+///
+/// let source = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let mut turns: TurnIterator<_, _, _, FloatPoint> =
+///     TurnIterator::hdl32_init(source);
+/// let (_, points) = turns.next().unwrap()?;
+/// assert!(!points.is_empty());
+/// assert!(points.iter().all(|p| 0. <= p.intensity && p.intensity <= 1.));
+///
+/// let p = |intensity| FloatPoint::from(FullPoint {
+///     intensity, ..Default::default()
+/// });
+/// assert_eq!(p(255).intensity, 1.);
+/// assert_eq!(p(0).intensity, 0.);
+/// # Ok(()) }
+/// ```
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct FloatPoint {
+    /// Point coordinates in meters
+    pub xyz: [f32; 3],
+    /// Normalized intensity
+    pub intensity: f32,
+}
+
+impl From<FullPoint> for FloatPoint {
+    fn from(p: FullPoint) -> Self {
+        FloatPoint { xyz: p.xyz, intensity: p.intensity as f32/255. }
+    }
+}
+
+/// Convert packet and append 4 floats (see `PackedXYZI`) for each resulting
+/// point to `out`
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::{convert_packed, Convertor, FullPoint, PackedXYZI};
+/// use velodyne::hdl32::Hdl32Convertor;
+/// use velodyne::packet::{PacketSource, PcapSource};
+///
+/// let mut source = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let (_, packet) = source.next_packet()?.unwrap();
+/// let convertor = Hdl32Convertor::default();
+/// let mut points: Vec<FullPoint> = vec![];
+/// convertor.convert(packet, |p| points.push(p)).unwrap();
+///
+/// let mut buf = vec![];
+/// convert_packed(&convertor, packet, &mut buf).unwrap();
+/// assert_eq!(buf.len(), 4*points.len());
+/// for (p, v) in points.iter().zip(buf.chunks_exact(4)) {
+///     assert_eq!(&v[..3], &p.xyz[..]);
+///     assert_eq!(v[3], p.intensity as f32);
+/// }
+///
+/// // the same layout is produced by a slice of `PackedXYZI`
+/// let mut packed: Vec<PackedXYZI> = vec![];
+/// convertor.convert(packet, |p| packed.push(p)).unwrap();
+/// assert_eq!(PackedXYZI::as_flat(&packed), &buf[..]);
+/// # Ok(()) }
+/// ```
+#[cfg(feature = "std")]
+pub fn convert_packed<C: Convertor>(
+    convertor: &C, packet: &RawPacket, out: &mut Vec<f32>,
+) -> Result<PacketMeta, ConversionError> {
+    convertor.convert(packet, |p: PackedXYZI| out.extend_from_slice(&p.0))
+}
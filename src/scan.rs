@@ -0,0 +1,76 @@
+//! Reduction of a single laser ring to a 2D laser scan
+use std::f32::consts::PI;
+
+use crate::FullPoint;
+
+/// Planar laser scan following ROS `sensor_msgs/LaserScan` conventions
+///
+/// Angles are measured in radians counterclockwise from the X axis, range
+/// of the `i`-th bin corresponds to the angle `angle_min + i*angle_increment`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LaserScan {
+    /// Start angle of the scan
+    pub angle_min: f32,
+    /// End angle of the scan
+    pub angle_max: f32,
+    /// Angular distance between bins
+    pub angle_increment: f32,
+    /// Ranges in meters, bins without returns are set to infinity
+    pub ranges: Vec<f32>,
+}
+
+impl LaserScan {
+    /// Build scan from returns of the laser `laser_id` binned by azimuth into
+    /// `bins` bins covering the full turn
+    ///
+    /// Ranges are measured in the XY plane, i.e. for lasers with non-zero
+    /// elevation ring is projected onto the horizontal plane. If several
+    /// returns fall into the same bin, the nearest one is used. Points with
+    /// NaN coordinates are ignored.
+    ///
+    /// # Panics
+    /// If `bins` is equal to zero.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::FullPoint;
+    /// use velodyne::scan::LaserScan;
+    ///
+    /// let point = |x, y, laser_id| FullPoint {
+    ///     xyz: [x, y, 0.5], laser_id, ..Default::default()
+    /// };
+    /// let turn = [
+    ///     point(2., 0., 3),
+    ///     point(0., 1., 3),
+    ///     point(0., 0.5, 3),
+    ///     point(5., 5., 7),
+    /// ];
+    /// let scan = LaserScan::from_ring(&turn, 3, 360);
+    /// assert_eq!(scan.ranges.len(), 360);
+    /// assert_eq!(scan.ranges[180], 2.);
+    /// // nearest of two returns is used
+    /// assert_eq!(scan.ranges[270], 0.5);
+    /// assert_eq!(scan.ranges.iter().filter(|r| r.is_finite()).count(), 2);
+    /// ```
+    pub fn from_ring(points: &[FullPoint], laser_id: u8, bins: usize) -> Self {
+        assert!(bins > 0, "number of bins must be positive");
+        let angle_increment = 2.*PI/bins as f32;
+        let angle_min = -PI;
+        let angle_max = angle_min + angle_increment*(bins - 1) as f32;
+        let mut ranges = vec![f32::INFINITY; bins];
+
+        let ring = points.iter()
+            .filter(|p| p.laser_id == laser_id)
+            .filter(|p| !p.xyz.iter().any(|v| v.is_nan()));
+        for p in ring {
+            let [x, y, _] = p.xyz;
+            let angle = y.atan2(x);
+            let bin = ((angle - angle_min)/angle_increment).round() as usize;
+            // angle of `PI` wraps to the first bin
+            let r = &mut ranges[bin % bins];
+            *r = r.min(x.hypot(y));
+        }
+
+        Self { angle_min, angle_max, angle_increment, ranges }
+    }
+}
@@ -0,0 +1,1724 @@
+//! Point sources and status tracking built on top of packet sources
+use std::io;
+use std::cmp::max;
+use std::marker::PhantomData;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::Path;
+use log::warn;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    hdl32, hdl64, vlp16, model, Azimuth, Convertor, FullPoint, TimedPoint,
+    TimeBase, ReturnType,
+};
+use crate::packet::{PacketSource, StatusBytes, PacketMeta, RawPacket, BLOCKS};
+use crate::packet::dual_return_partners;
+
+/// Trait for tracking sensor status
+///
+/// In case if sensor does not emits meaningful status data or you don't need
+/// status information, use `DummyStatusListener`.
+pub trait StatusListener: Sized {
+    /// Type of the status information
+    type Status: Clone;
+
+    /// Initialize listener from packet source
+    fn init<T: PacketSource>(packet_source: &mut T) -> io::Result<Self>;
+
+    /// Feed status from the parsed packet.
+    ///
+    /// This method will update internall state of the listener, updating sensor
+    /// status (which is accessible through `get_status` method) when possible
+    fn feed(&mut self, status: StatusBytes);
+
+    /// Feed status from the parsed packet in the same way as `feed` and
+    /// return `true` if sensor status was updated by this call.
+    ///
+    /// Used by `PointSource` to call status change callbacks. Default
+    /// implementation calls `feed` and always returns `false`.
+    fn feed_updated(&mut self, status: StatusBytes) -> bool {
+        self.feed(status);
+        false
+    }
+
+    /// Get current status state
+    fn get_status(&self) -> &Self::Status;
+
+    /// Get field of view configured in the sensor as `(start, end)` azimuths
+    /// (inclusive), `None` if sensor covers the full turn or if the field of
+    /// view is unknown.
+    fn fov(&self) -> Option<(Azimuth, Azimuth)> { None }
+
+    /// Get return mode configured in the sensor, `None` if it is unknown
+    fn return_type(&self) -> Option<ReturnType> { None }
+
+    /// Discard partially accumulated status data after discontinuity of the
+    /// packet stream (see `PacketSource::take_discontinuity`), current status
+    /// is kept. Default implementation does nothing.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::sync::{Arc, Mutex};
+    /// use velodyne::{PointSource, FullPoint};
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// // the file contains 16592 packets and is replayed in a loop
+    /// let source = PcapSource::new("data/hdl64.pcap", false, true)?;
+    /// let mut point_source = PointSource::hdl64_init(source)?;
+    /// let statuses = Arc::new(Mutex::new(vec![]));
+    /// let s = statuses.clone();
+    /// point_source.on_status_change(move |st| s.lock().unwrap().push(*st));
+    /// for _ in 0..2*16592 + 1000 {
+    ///     point_source.process_points(|_: FullPoint| {})?;
+    /// }
+    /// // status cycles after the loop are received in the same way as before
+    /// let statuses = statuses.lock().unwrap();
+    /// assert_eq!(statuses.len(), 6);
+    /// for (a, b) in statuses[..3].iter().zip(statuses[3..].iter()) {
+    ///     assert_eq!((a.dt, a.rpm), (b.dt, b.rpm));
+    /// }
+    /// # Ok(()) }
+    /// ```
+    fn reset(&mut self) {}
+
+    /// Returns `true` if sensor reconfiguration (e.g. change of return mode
+    /// or rotation speed) was detected in the fed status since the previous
+    /// call. Default implementation never detects it.
+    fn take_reconfiguration(&mut self) -> bool { false }
+}
+
+/// Dummy status listener which does nothing
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DummyStatusListener;
+
+impl StatusListener for DummyStatusListener {
+    type Status = ();
+
+    fn init<T: PacketSource>(_source: &mut T) -> io::Result<Self> {
+        Ok(DummyStatusListener)
+    }
+
+    fn feed(&mut self, _status: StatusBytes) { }
+    fn get_status(&self) -> &Self::Status { &() }
+}
+
+
+/// Handling of packets which can not be converted by `PointSource`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ConversionErrorPolicy {
+    /// Return conversion error as `io::Error` with `InvalidData` kind
+    /// (default)
+    #[default]
+    Fail,
+    /// Log warning and skip packet
+    SkipAndWarn,
+    /// Silently skip packet
+    Skip,
+}
+
+/// Configuration of `PointSource`, see the corresponding `PointSource`
+/// setters for description of the options
+///
+/// # Example
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use velodyne::{PointSource, PointSourceConfig, ConversionErrorPolicy};
+/// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+/// use velodyne::packet::PcapSource;
+///
+/// let config = PointSourceConfig::default()
+///     .with_sensor_id(3)
+///     .with_conversion_error_policy(ConversionErrorPolicy::Skip);
+/// # #[cfg(feature = "serde")]
+/// # let config = {
+/// #     let json = serde_json::to_string(&config).unwrap();
+/// #     let decoded: PointSourceConfig = serde_json::from_str(&json).unwrap();
+/// #     assert_eq!(decoded, config);
+/// #     decoded
+/// # };
+///
+/// let source = PcapSource::new("data/hdl32.pcap", false, false)?;
+/// let point_source: PointSource<_, _, StatusListener> =
+///     PointSource::new_with_config(source, Hdl32Convertor::default(), &config)?;
+/// assert_eq!(point_source.get_config(), config);
+/// assert_eq!(point_source.get_sensor_id(), 3);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PointSourceConfig {
+    /// See `PointSource::set_fov_filter`
+    pub fov_filter: bool,
+    /// See `PointSource::set_sensor_id`
+    pub sensor_id: u8,
+    /// See `PointSource::set_on_conversion_error`
+    pub conversion_error_policy: ConversionErrorPolicy,
+    /// See `PointSource::set_duplicate_detection`
+    pub duplicate_detection: bool,
+}
+
+impl PointSourceConfig {
+    /// Set whether points outside of the sensor field of view are dropped
+    pub fn with_fov_filter(mut self, enabled: bool) -> Self {
+        self.fov_filter = enabled;
+        self
+    }
+
+    /// Set ID written into the produced points
+    pub fn with_sensor_id(mut self, sensor_id: u8) -> Self {
+        self.sensor_id = sensor_id;
+        self
+    }
+
+    /// Set handling of packets which can not be converted
+    pub fn with_conversion_error_policy(
+        mut self, policy: ConversionErrorPolicy,
+    ) -> Self {
+        self.conversion_error_policy = policy;
+        self
+    }
+
+    /// Set whether repeated packets are counted
+    pub fn with_duplicate_detection(mut self, enabled: bool) -> Self {
+        self.duplicate_detection = enabled;
+        self
+    }
+}
+
+/// Point tagged with the return (echo) of the firing which has produced it,
+/// see `PointSource::process_echoes`
+#[derive(Copy, Clone, Debug)]
+pub struct Echo {
+    /// Converted point
+    pub point: FullPoint,
+    /// Number of the echo in the firing, equal to `point.return_number`
+    pub echo_number: u8,
+    /// Type of the echo, `ReturnType::Both` means that the echo is both the
+    /// strongest and the last return of the firing
+    pub echo_type: ReturnType,
+}
+
+/// Callback which is called on sensor status updates
+type StatusCallback<S> = Box<dyn FnMut(&S) + Send>;
+
+/// This struct listens to the `packet_source` and converts packets data to
+/// points
+pub struct PointSource<T, C, S>
+    where T: PacketSource, C: Convertor, S: StatusListener
+{
+    packet_source: T,
+    status_lst: S,
+    convertor: C,
+    on_status_change: Option<StatusCallback<S::Status>>,
+    on_reconfiguration: Option<StatusCallback<S::Status>>,
+    // set on sensor reconfiguration, cleared by `TurnIterator`
+    reconfigured: bool,
+    fov_filter: bool,
+    sensor_id: u8,
+    // sensor IDs assigned to the source IP addresses
+    addr_sensor_ids: Vec<(Ipv4Addr, u8)>,
+    conv_err_policy: ConversionErrorPolicy,
+    detect_duplicates: bool,
+    // copy of the previous packet used for duplicate detection
+    prev_packet: Option<Box<RawPacket>>,
+    duplicates: u64,
+}
+
+impl<T, C, S> PointSource<T, C, S>
+    where T: PacketSource, C: Convertor, S: StatusListener
+{
+    fn from_parts(packet_source: T, status_lst: S, convertor: C) -> Self {
+        Self {
+            packet_source, status_lst, convertor, on_status_change: None,
+            on_reconfiguration: None, reconfigured: false,
+            fov_filter: false, sensor_id: 0, addr_sensor_ids: vec![],
+            conv_err_policy: Default::default(), detect_duplicates: false,
+            prev_packet: None, duplicates: 0,
+        }
+    }
+
+    /// Create new `PointSource`
+    pub fn new(mut packet_source: T, convertor: C) -> io::Result<Self> {
+        let status_lst = S::init(&mut packet_source)?;
+        Ok(Self::from_parts(packet_source, status_lst, convertor))
+    }
+
+    /// Create new `PointSource` configured using `config`
+    pub fn new_with_config(
+        packet_source: T, convertor: C, config: &PointSourceConfig,
+    ) -> io::Result<Self> {
+        let mut point_source = Self::new(packet_source, convertor)?;
+        point_source.set_config(config);
+        Ok(point_source)
+    }
+
+    /// Apply all options stored in `config`
+    pub fn set_config(&mut self, config: &PointSourceConfig) {
+        self.fov_filter = config.fov_filter;
+        self.sensor_id = config.sensor_id;
+        self.conv_err_policy = config.conversion_error_policy;
+        self.set_duplicate_detection(config.duplicate_detection);
+    }
+
+    /// Get current configuration
+    pub fn get_config(&self) -> PointSourceConfig {
+        PointSourceConfig {
+            fov_filter: self.fov_filter,
+            sensor_id: self.sensor_id,
+            conversion_error_policy: self.conv_err_policy,
+            duplicate_detection: self.detect_duplicates,
+        }
+    }
+
+    /// Get current sensor status
+    pub fn get_status(&self) -> &S::Status {
+        self.status_lst.get_status()
+    }
+
+    /// Get reference to the used convertor
+    pub fn get_convertor(&self) -> &C {
+        &self.convertor
+    }
+
+    /// Register callback which will be called each time status listener
+    /// updates sensor status (e.g. after each full HDL-64 status cycle).
+    ///
+    /// Replaces previously registered callback.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use std::sync::{Arc, Mutex};
+    /// use velodyne::{PointSource, FullPoint, ReturnType};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits packets with the given return mode bytes
+    /// struct Source { packet: RawPacket, modes: Vec<u8>, n: usize }
+    ///
+    /// impl PacketSource for Source {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         let mode = match self.modes.get(self.n) {
+    ///             Some(&mode) => mode,
+    ///             None => return Ok(None),
+    ///         };
+    ///         for block in self.packet[..1200].chunks_exact_mut(100) {
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///         }
+    ///         self.packet[1204..].copy_from_slice(&[mode, 0x21]);
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// // repeated status bytes do not change the status, unknown return
+    /// // mode byte is ignored
+    /// let modes = vec![0x37, 0x37, 0x37, 0x39, 0x39, 0x00, 0x39, 0x37, 0x37];
+    /// let source = Source { packet: [0; 1206], modes, n: 0 };
+    /// let mut point_source: PointSource<_, _, StatusListener> =
+    ///     PointSource::new(source, Hdl32Convertor::default()).unwrap();
+    /// let calls = Arc::new(Mutex::new(vec![]));
+    /// let c = calls.clone();
+    /// point_source.on_status_change(move |st| {
+    ///     c.lock().unwrap().push(st.unwrap().return_mode);
+    /// });
+    /// let mut noop = |_: FullPoint| {};
+    /// while point_source.process_points(&mut noop).unwrap().is_some() {}
+    /// let calls = calls.lock().unwrap();
+    /// assert_eq!(*calls, [
+    ///     ReturnType::Strongest, ReturnType::Both, ReturnType::Strongest,
+    /// ]);
+    /// ```
+    pub fn on_status_change<F>(&mut self, f: F)
+        where F: FnMut(&S::Status) + Send + 'static
+    {
+        self.on_status_change = Some(Box::new(f));
+    }
+
+    /// Register callback which will be called with the new status each time
+    /// status listener detects sensor reconfiguration (see
+    /// `StatusListener::take_reconfiguration`), e.g. change of the return
+    /// mode at runtime.
+    ///
+    /// On reconfiguration the cache used for duplicate packet detection is
+    /// cleared and `TurnIterator` discards points of the current turn. The
+    /// callback can be used to reset other state which depends on sensor
+    /// settings. Replaces previously registered callback.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use std::sync::{Arc, Mutex};
+    /// use velodyne::{PointSource, FullPoint, ReturnType};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits 100 packets, sensor works in the dual return mode between
+    /// /// packets 50 and 80
+    /// struct Source { packet: RawPacket, n: u8 }
+    ///
+    /// impl PacketSource for Source {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 100 { return Ok(None); }
+    ///         for block in self.packet[..1200].chunks_exact_mut(100) {
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///         }
+    ///         let mode = if (50..80).contains(&self.n) { 0x39 } else { 0x37 };
+    ///         self.packet[1204..].copy_from_slice(&[mode, 0x21]);
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Source { packet: [0; 1206], n: 0 };
+    /// let mut point_source: PointSource<_, _, StatusListener> =
+    ///     PointSource::new(source, Hdl32Convertor::default()).unwrap();
+    /// let modes = Arc::new(Mutex::new(vec![]));
+    /// let m = modes.clone();
+    /// point_source.on_reconfiguration(move |st| {
+    ///     m.lock().unwrap().push(st.unwrap().return_mode);
+    /// });
+    /// let mut noop = |_: FullPoint| {};
+    /// while point_source.process_points(&mut noop).unwrap().is_some() {}
+    /// let modes = modes.lock().unwrap();
+    /// assert_eq!(*modes, [ReturnType::Both, ReturnType::Strongest]);
+    /// ```
+    pub fn on_reconfiguration<F>(&mut self, f: F)
+        where F: FnMut(&S::Status) + Send + 'static
+    {
+        self.on_reconfiguration = Some(Box::new(f));
+    }
+
+    /// Set whether points outside of the field of view reported by the
+    /// sensor status should be dropped, disabled by default.
+    ///
+    /// Field of view is taken from the current status for each packet, so
+    /// filtering starts working only after sensor reports it (see
+    /// `StatusListener::fov`).
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{PointSource, StatusListener, FullPoint, Azimuth};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, RawPacket, StatusBytes};
+    ///
+    /// /// Listener which reports 90°–180° field of view
+    /// struct Fov;
+    ///
+    /// impl StatusListener for Fov {
+    ///     type Status = ();
+    ///     fn init<T: PacketSource>(_: &mut T) -> io::Result<Self> { Ok(Fov) }
+    ///     fn feed(&mut self, _: StatusBytes) {}
+    ///     fn get_status(&self) -> &() { &() }
+    ///     fn fov(&self) -> Option<(Azimuth, Azimuth)> {
+    ///         Some((Azimuth::new(9000), Azimuth::new(18000)))
+    ///     }
+    /// }
+    ///
+    /// /// Emits full turn of packets with one point in each block
+    /// struct Turn { packet: RawPacket, n: u16 }
+    ///
+    /// impl PacketSource for Turn {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 30 { return Ok(None); }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = 100*(12*self.n + i as u16);
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///             block[4..7].copy_from_slice(&[0xE8, 0x03, 0]);
+    ///         }
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Turn { packet: [0; 1206], n: 0 };
+    /// let convertor = Hdl32Convertor::default();
+    /// let mut point_source: PointSource<_, _, Fov> =
+    ///     PointSource::new(source, convertor).unwrap();
+    /// point_source.set_fov_filter(true);
+    /// let mut points = vec![];
+    /// while point_source.process_points(|p: FullPoint| points.push(p))
+    ///     .unwrap().is_some() {}
+    /// // one point per 1° in the inclusive window
+    /// assert_eq!(points.len(), 91);
+    /// assert!(points.iter().all(|p| 9000 <= p.azimuth && p.azimuth <= 18000));
+    /// ```
+    pub fn set_fov_filter(&mut self, enabled: bool) {
+        self.fov_filter = enabled;
+    }
+
+    /// Set ID written into `FullPoint::sensor_id` of the produced points, 0 by
+    /// default
+    ///
+    /// Allows to distinguish points of different sensors after merging their
+    /// clouds. IDs assigned to source addresses using `set_sensor_id_for`
+    /// take precedence over this value.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{PointSource, FullPoint};
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let mut points = vec![];
+    /// for id in 1..3 {
+    ///     let source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    ///     let mut point_source = PointSource::hdl32_init(source);
+    ///     point_source.set_sensor_id(id);
+    ///     point_source.process_points(|p: FullPoint| points.push(p))?;
+    /// }
+    /// let n = points.len()/2;
+    /// assert!(n > 0);
+    /// assert!(points[..n].iter().all(|p| p.sensor_id == 1));
+    /// assert!(points[n..].iter().all(|p| p.sensor_id == 2));
+    /// # Ok(()) }
+    /// ```
+    pub fn set_sensor_id(&mut self, sensor_id: u8) {
+        self.sensor_id = sensor_id;
+    }
+
+    /// Get ID written into the produced points
+    pub fn get_sensor_id(&self) -> u8 {
+        self.sensor_id
+    }
+
+    /// Set ID written into `FullPoint::sensor_id` of points produced from
+    /// packets sent from the IP address `ip`
+    ///
+    /// Allows to distinguish points of several sensors read through a single
+    /// packet source, e.g. `MultiSource`. Points from addresses without
+    /// assigned ID get the ID set by `set_sensor_id`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::{Ipv4Addr, SocketAddrV4};
+    /// use velodyne::{PointSource, FullPoint};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{MultiSource, PacketSource, RawPacket};
+    ///
+    /// /// Emits 3 packets with one point in each block sent from `ip`
+    /// struct Sensor { packet: RawPacket, ip: Ipv4Addr, n: u8 }
+    ///
+    /// impl PacketSource for Sensor {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 3 { return Ok(None); }
+    ///         self.n += 1;
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&(20*i as u16).to_le_bytes());
+    ///             block[4..7].copy_from_slice(&[0xE8, 0x03, 0]);
+    ///         }
+    ///         Ok(Some((SocketAddrV4::new(self.ip, 2368), &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let sensor = |ip| Sensor { packet: [0; 1206], ip, n: 0 };
+    /// let ip1 = Ipv4Addr::new(10, 0, 0, 1);
+    /// let ip2 = Ipv4Addr::new(10, 0, 0, 2);
+    /// let source = MultiSource::new(vec![sensor(ip1), sensor(ip2)]);
+    /// let mut point_source: PointSource<_, _, StatusListener> =
+    ///     PointSource::new(source, Hdl32Convertor::default()).unwrap();
+    /// point_source.set_sensor_id_for(ip1, 1);
+    /// point_source.set_sensor_id_for(ip2, 2);
+    ///
+    /// let mut packets = 0;
+    /// loop {
+    ///     let mut ids = vec![];
+    ///     let res = point_source.process_points(|p: FullPoint| {
+    ///         ids.push(p.sensor_id)
+    ///     }).unwrap();
+    ///     let (addr, _) = match res { Some(v) => v, None => break };
+    ///     let id = if *addr.ip() == ip1 { 1 } else { 2 };
+    ///     assert_eq!(ids.len(), 12);
+    ///     assert!(ids.iter().all(|&i| i == id));
+    ///     packets += 1;
+    /// }
+    /// assert_eq!(packets, 6);
+    /// ```
+    pub fn set_sensor_id_for(&mut self, ip: Ipv4Addr, sensor_id: u8) {
+        let ids = &mut self.addr_sensor_ids;
+        match ids.iter_mut().find(|(addr, _)| *addr == ip) {
+            Some(entry) => entry.1 = sensor_id,
+            None => ids.push((ip, sensor_id)),
+        }
+    }
+
+    /// Set handling of packets which can not be converted, by default
+    /// conversion errors are returned by `process_points`
+    ///
+    /// If packet is skipped, `process_points` reads the next one. Note that
+    /// points converted before error was detected are still processed.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{
+    ///     PointSource, FullPoint, DummyStatusListener, ConversionErrorPolicy,
+    /// };
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits valid, garbage and valid packets
+    /// struct Source { packet: RawPacket, n: u8 }
+    ///
+    /// impl PacketSource for Source {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 3 { return Ok(None); }
+    ///         let header = if self.n == 1 { [0, 0] } else { [0xFF, 0xEE] };
+    ///         for block in self.packet[..1200].chunks_exact_mut(100) {
+    ///             block[..2].copy_from_slice(&header);
+    ///         }
+    ///         self.packet[1200] = self.n;
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let policies = [
+    ///     ConversionErrorPolicy::Fail,
+    ///     ConversionErrorPolicy::SkipAndWarn,
+    ///     ConversionErrorPolicy::Skip,
+    /// ];
+    /// for &policy in policies.iter() {
+    ///     let source = Source { packet: [0; 1206], n: 0 };
+    ///     let mut point_source: PointSource<_, _, DummyStatusListener> =
+    ///         PointSource::new(source, Hdl32Convertor::default()).unwrap();
+    ///     point_source.set_on_conversion_error(policy);
+    ///     let mut timestamps = vec![];
+    ///     loop {
+    ///         match point_source.process_points(|_: FullPoint| {}) {
+    ///             Ok(Some((_, meta))) => timestamps.push(meta.timestamp),
+    ///             Ok(None) => break,
+    ///             Err(err) => {
+    ///                 assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    ///                 timestamps.push(u32::MAX);
+    ///             },
+    ///         }
+    ///     }
+    ///     if policy == ConversionErrorPolicy::Fail {
+    ///         assert_eq!(timestamps, [0, u32::MAX, 2]);
+    ///     } else {
+    ///         assert_eq!(timestamps, [0, 2]);
+    ///     }
+    /// }
+    /// ```
+    pub fn set_on_conversion_error(&mut self, policy: ConversionErrorPolicy) {
+        self.conv_err_policy = policy;
+    }
+
+    /// Get handling of packets which can not be converted
+    pub fn get_on_conversion_error(&self) -> ConversionErrorPolicy {
+        self.conv_err_policy
+    }
+
+    /// Set whether packets identical to the previous packet are counted,
+    /// disabled by default
+    ///
+    /// Repeated packets usually indicate a stuck feed or duplicates in
+    /// merged captures, while packets of a static scene still differ in
+    /// timestamps. Duplicates are processed as usual, their number is
+    /// reported by `duplicate_packets`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{PointSource, FullPoint, DummyStatusListener};
+    /// use velodyne::hdl32::Hdl32Convertor;
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits packets with timestamps 0, 0, 1
+    /// struct Source { packet: RawPacket, n: u32 }
+    ///
+    /// impl PacketSource for Source {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 3 { return Ok(None); }
+    ///         for block in self.packet[..1200].chunks_exact_mut(100) {
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///         }
+    ///         let t = self.n.saturating_sub(1);
+    ///         self.packet[1200..1204].copy_from_slice(&t.to_le_bytes());
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Source { packet: [0; 1206], n: 0 };
+    /// let mut point_source: PointSource<_, _, DummyStatusListener> =
+    ///     PointSource::new(source, Hdl32Convertor::default()).unwrap();
+    /// point_source.set_duplicate_detection(true);
+    /// let mut n = 0;
+    /// while point_source.process_points(|_: FullPoint| {}).unwrap().is_some() {
+    ///     n += 1;
+    /// }
+    /// assert_eq!(n, 3);
+    /// assert_eq!(point_source.duplicate_packets(), 1);
+    /// ```
+    pub fn set_duplicate_detection(&mut self, enabled: bool) {
+        self.detect_duplicates = enabled;
+        if !enabled { self.prev_packet = None; }
+    }
+
+    /// Get whether repeated packets are counted
+    pub fn get_duplicate_detection(&self) -> bool {
+        self.detect_duplicates
+    }
+
+    /// Number of packets identical to the previous packet detected since
+    /// source creation, see `set_duplicate_detection`
+    pub fn duplicate_packets(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// Process points in the next recieved packet converted to `TimedPoint`s
+    /// using `time_base`
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use chrono::{Duration, TimeZone, Utc};
+    /// use velodyne::{PointSource, TimeBase};
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let mut point_source = PointSource::hdl32_init(source);
+    /// let hour = Utc.with_ymd_and_hms(2018, 11, 14, 9, 0, 0).unwrap();
+    /// let mut time_base = TimeBase::new(hour);
+    /// let mut stamps = vec![];
+    /// let (_, meta) = point_source.process_timed_points(
+    ///     &mut time_base, |p| stamps.push(p.stamp))?.unwrap();
+    /// assert!(!stamps.is_empty());
+    /// let t = hour + Duration::microseconds(meta.timestamp as i64);
+    /// assert!(stamps.iter().all(|&s| s == t));
+    /// # Ok(()) }
+    /// ```
+    pub fn process_timed_points<F>(
+        &mut self, time_base: &mut TimeBase, mut f: F,
+    ) -> io::Result<Option<(SocketAddrV4, PacketMeta)>>
+        where F: FnMut(TimedPoint)
+    {
+        self.process_points(|p: FullPoint| f(time_base.timed_point(&p)))
+    }
+
+    /// Process points in the next recieved packet
+    pub fn process_points<F, P>(&mut self, mut process_point: F)
+        -> io::Result<Option<(SocketAddrV4, PacketMeta)>>
+        where P: From<FullPoint>, F: FnMut(P)
+    {
+        self.process_packet(false, |p, _| process_point(p.into()))
+    }
+
+    /// Process all echoes in the next recieved packet
+    ///
+    /// Dual return packets are detected using paired blocks. In such packets
+    /// a firing with distinct returns produces the last return, which is
+    /// the farther one and gets echo number 2, and the strongest return (or
+    /// the next strongest return if the strongest one is equal to the last),
+    /// which gets echo number 1. A firing with a single return produces one
+    /// echo of `ReturnType::Both` type with echo number 1.
+    /// Echoes in single return packets get the return type reported by the
+    /// sensor status (`ReturnType::Strongest` if it is unknown).
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{PointSource, Echo, ReturnType};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits a single dual return packet, laser 0 has the strongest return
+    /// /// at 2 meters and the last return at 4 meters, laser 1 has a single
+    /// /// return at 3 meters
+    /// struct Single { packet: RawPacket, done: bool }
+    ///
+    /// impl PacketSource for Single {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if std::mem::replace(&mut self.done, true) { return Ok(None); }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = 20*(i as u16/2);
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///             let d0: u16 = if i % 2 == 0 { 2000 } else { 1000 };
+    ///             block[4..6].copy_from_slice(&d0.to_le_bytes());
+    ///             block[6] = 20 + i as u8 % 2;
+    ///             block[7..9].copy_from_slice(&1500u16.to_le_bytes());
+    ///             block[9] = 10;
+    ///         }
+    ///         self.packet[1204..].copy_from_slice(&[0x39, 0x21]);
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Single { packet: [0; 1206], done: false };
+    /// let mut point_source: PointSource<_, _, StatusListener> =
+    ///     PointSource::new(source, Hdl32Convertor::default()).unwrap();
+    /// let mut echoes: Vec<Echo> = vec![];
+    /// point_source.process_echoes(|e| echoes.push(e)).unwrap().unwrap();
+    /// // duplicated return of laser 1 is removed by the convertor
+    /// assert_eq!(echoes.len(), 6*3);
+    /// for pair in echoes.chunks_exact(3) {
+    ///     let tags: Vec<_> = pair.iter()
+    ///         .map(|e| (e.point.laser_id, e.echo_number, e.echo_type))
+    ///         .collect();
+    ///     assert_eq!(tags, [
+    ///         (0, 2, ReturnType::Last),
+    ///         (1, 1, ReturnType::Both),
+    ///         (0, 1, ReturnType::Strongest),
+    ///     ]);
+    ///     for (e, d) in pair.iter().zip([4., 3., 2.]) {
+    ///         assert!((e.point.range() - d).abs() < 1e-5);
+    ///     }
+    /// }
+    /// ```
+    pub fn process_echoes<F>(&mut self, mut f: F)
+        -> io::Result<Option<(SocketAddrV4, PacketMeta)>>
+        where F: FnMut(Echo)
+    {
+        let single = match self.status_lst.return_type() {
+            Some(ReturnType::Last) => ReturnType::Last,
+            _ => ReturnType::Strongest,
+        };
+        self.process_packet(true, |point, dual| {
+            let echo_type = if !dual {
+                single
+            } else if point.number_of_returns < 2 {
+                ReturnType::Both
+            } else if point.return_number == 1 {
+                ReturnType::Strongest
+            } else {
+                ReturnType::Last
+            };
+            f(Echo { point, echo_number: point.return_number, echo_type });
+        })
+    }
+
+    /// Process points in the next recieved packet together with the flag
+    /// indicating that packet is in the dual return mode, the flag is always
+    /// `false` if `detect_dual` is `false`
+    fn process_packet<F>(&mut self, detect_dual: bool, mut process_point: F)
+        -> io::Result<Option<(SocketAddrV4, PacketMeta)>>
+        where F: FnMut(FullPoint, bool)
+    {
+        let packets = &mut self.packet_source;
+        let convertor = &self.convertor;
+        let fov = if self.fov_filter { self.status_lst.fov() } else { None };
+        let addr_sensor_ids = &self.addr_sensor_ids;
+
+        let (addr, meta) = loop {
+            let (addr, packet) = match packets.next_packet()? {
+                Some(val) => val,
+                None => return Ok(None),
+            };
+            let sensor_id = addr_sensor_ids.iter()
+                .find(|(ip, _)| ip == addr.ip())
+                .map_or(self.sensor_id, |&(_, id)| id);
+            if self.detect_duplicates {
+                match &mut self.prev_packet {
+                    Some(prev) if **prev == *packet => self.duplicates += 1,
+                    Some(prev) => **prev = *packet,
+                    None => self.prev_packet = Some(Box::new(*packet)),
+                }
+            }
+
+            let dual = detect_dual && dual_return_partners(packet).iter()
+                .any(|p| p.is_some());
+            let res = convertor.convert(packet, |mut p: FullPoint| {
+                if let Some((start, end)) = fov {
+                    if !Azimuth::new(p.azimuth).is_within(start, end) {
+                        return;
+                    }
+                }
+                p.sensor_id = sensor_id;
+                process_point(p, dual);
+            });
+            match res {
+                Ok(meta) => break (addr, meta),
+                Err(err) => match self.conv_err_policy {
+                    ConversionErrorPolicy::Fail => return Err(
+                        io::Error::new(io::ErrorKind::InvalidData, err)),
+                    ConversionErrorPolicy::SkipAndWarn => {
+                        warn!("skipping packet from {}: {}", addr, err);
+                    },
+                    ConversionErrorPolicy::Skip => (),
+                },
+            }
+            // status bytes of the skipped packet are lost
+            self.status_lst.reset();
+        };
+        if packets.take_discontinuity() {
+            self.status_lst.reset();
+        }
+        if self.status_lst.feed_updated(meta.status) {
+            if let Some(f) = self.on_status_change.as_mut() {
+                f(self.status_lst.get_status());
+            }
+            if self.status_lst.take_reconfiguration() {
+                // packet cached for duplicate detection was produced with
+                // the old settings
+                self.prev_packet = None;
+                self.reconfigured = true;
+                if let Some(f) = self.on_reconfiguration.as_mut() {
+                    f(self.status_lst.get_status());
+                }
+            }
+        }
+
+        Ok(Some((addr, meta)))
+    }
+
+    /// Convert the next recieved packet and return its points
+    ///
+    /// Returns `None` if packet source did not return a packet. See also
+    /// `next_points_into` which allows to reuse allocated buffer.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{PointSource, FullPoint};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits a single packet
+    /// struct Single { packet: RawPacket, done: bool }
+    ///
+    /// impl PacketSource for Single {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if std::mem::replace(&mut self.done, true) { return Ok(None); }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = 20*i as u16;
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///         }
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Single { packet: [0; 1206], done: false };
+    /// let mut convertor = Hdl32Convertor::default();
+    /// convertor.set_keep_no_return(true);
+    /// let mut point_source: PointSource<_, _, StatusListener> =
+    ///     PointSource::new(source, convertor).unwrap();
+    /// let (meta, points) = point_source.next_points::<FullPoint>()
+    ///     .unwrap().unwrap();
+    /// assert_eq!(meta.azimuth, 0);
+    /// assert_eq!(points.len(), 12*32);
+    /// assert!(point_source.next_points::<FullPoint>().unwrap().is_none());
+    /// ```
+    pub fn next_points<P: From<FullPoint>>(&mut self)
+        -> io::Result<Option<(PacketMeta, Vec<P>)>>
+    {
+        let mut buf = Vec::with_capacity(BLOCKS*32);
+        let meta = self.next_points_into(&mut buf)?;
+        Ok(meta.map(|meta| (meta, buf)))
+    }
+
+    /// Clear `buf` and fill it with points of the next recieved packet
+    ///
+    /// Returns `None` if packet source did not return a packet.
+    pub fn next_points_into<P: From<FullPoint>>(&mut self, buf: &mut Vec<P>)
+        -> io::Result<Option<PacketMeta>>
+    {
+        buf.clear();
+        let res = self.process_points(|p| buf.push(p))?;
+        Ok(res.map(|(_, meta)| meta))
+    }
+
+    /// Process all packets streaming points into `on_point` and calling
+    /// `on_turn_end` with the current status each time packet azimuth
+    /// crosses `split_azimuth`.
+    ///
+    /// Turns are split in the same way as in `TurnIterator`, i.e. points of
+    /// the packet which crosses split azimuth belong to the ending turn. Points
+    /// of the incomplete last turn are passed to `on_point`, but `on_turn_end`
+    /// is not called for it. Returns after packet source is exhausted.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::cell::Cell;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{PointSource, Azimuth};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits 3 turns of packets with one point and 30° step
+    /// struct Turns { packet: RawPacket, n: u16 }
+    ///
+    /// impl PacketSource for Turns {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 36 { return Ok(None); }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = (3000*self.n as u32 + 100*i as u32) % 36000;
+    ///             let azimuth = azimuth as u16;
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///         }
+    ///         self.packet[4..7].copy_from_slice(&[0xE8, 0x03, 0]);
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Turns { packet: [0; 1206], n: 0 };
+    /// let convertor = Hdl32Convertor::default();
+    /// let mut point_source: PointSource<_, _, StatusListener> =
+    ///     PointSource::new(source, convertor).unwrap();
+    /// let n = Cell::new(0);
+    /// let mut turns = vec![];
+    /// point_source.for_each_turn(
+    ///     Azimuth::from_degrees(180.),
+    ///     |_| n.set(n.get() + 1),
+    ///     |_| turns.push(n.get()),
+    /// ).unwrap();
+    /// assert_eq!(n.get(), 36);
+    /// // the first turn is partial, the last 5 points do not end a turn
+    /// assert_eq!(turns, [7, 19, 31]);
+    /// ```
+    pub fn for_each_turn<F, G>(
+        &mut self, split_azimuth: Azimuth, mut on_point: F, mut on_turn_end: G,
+    ) -> io::Result<()>
+        where F: FnMut(&FullPoint), G: FnMut(&S::Status)
+    {
+        let mut prev = Azimuth::default();
+        while let Some((_, meta)) =
+            self.process_points(|p: FullPoint| on_point(&p))?
+        {
+            let azimuth = Azimuth::new(meta.azimuth);
+            if split_azimuth.is_crossed(prev, azimuth) {
+                on_turn_end(self.status_lst.get_status());
+            }
+            prev = azimuth;
+        }
+        Ok(())
+    }
+
+    /// Process all packets streaming points into `f` as `Some(point)` and
+    /// marking turn boundaries with `None`.
+    ///
+    /// Turns are split in the same way as in `for_each_turn`, i.e. `None` is
+    /// passed after points of the packet which crosses `split_azimuth`.
+    /// Returns after packet source is exhausted.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{PointSource, Azimuth};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits 3 turns of packets with one point and 30° step
+    /// struct Turns { packet: RawPacket, n: u16 }
+    ///
+    /// impl PacketSource for Turns {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 36 { return Ok(None); }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = (3000*self.n as u32 + 100*i as u32) % 36000;
+    ///             let azimuth = azimuth as u16;
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///         }
+    ///         self.packet[4..7].copy_from_slice(&[0xE8, 0x03, 0]);
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Turns { packet: [0; 1206], n: 0 };
+    /// let convertor = Hdl32Convertor::default();
+    /// let mut point_source: PointSource<_, _, StatusListener> =
+    ///     PointSource::new(source, convertor).unwrap();
+    /// let mut stream = vec![];
+    /// point_source.for_each_point_delimited(
+    ///     Azimuth::from_degrees(180.),
+    ///     |p| stream.push(p.is_some()),
+    /// ).unwrap();
+    /// assert_eq!(stream.iter().filter(|&&p| p).count(), 36);
+    /// // 3 turns were crossed
+    /// let boundaries: Vec<usize> = stream.iter().enumerate()
+    ///     .filter(|(_, &p)| !p)
+    ///     .map(|(i, _)| i)
+    ///     .collect();
+    /// assert_eq!(boundaries, [7, 20, 33]);
+    /// ```
+    pub fn for_each_point_delimited<F>(
+        &mut self, split_azimuth: Azimuth, mut f: F,
+    ) -> io::Result<()>
+        where F: FnMut(Option<FullPoint>)
+    {
+        let mut prev = Azimuth::default();
+        while let Some((_, meta)) =
+            self.process_points(|p: FullPoint| f(Some(p)))?
+        {
+            let azimuth = Azimuth::new(meta.azimuth);
+            if split_azimuth.is_crossed(prev, azimuth) {
+                f(None);
+            }
+            prev = azimuth;
+        }
+        Ok(())
+    }
+}
+
+impl<T: PacketSource> PointSource<T, hdl64::Hdl64Convertor, hdl64::StatusListener> {
+    /// Initialize HDL-64 packet source
+    pub fn hdl64_init(mut packet_source: T) -> io::Result<Self> {
+        let status_lst = hdl64::StatusListener::init(&mut packet_source)?;
+        let db = status_lst.get_calib_db(0.2);
+        let convertor = hdl64::Hdl64Convertor::new(db);
+        Ok(Self::from_parts(packet_source, status_lst, convertor))
+    }
+
+    /// Update HDL-64 calibration table
+    pub fn hdl64_set_calib_db(&mut self, calib_db: hdl64::CalibDb) {
+        self.convertor = hdl64::Hdl64Convertor::new(calib_db);
+    }
+
+    pub fn get_calib_db(&self) -> hdl64::CalibDb {
+        self.convertor.db.clone()
+    }
+
+    /// Save currently used calibration table into file at `path`
+    ///
+    /// Saved table can be loaded with `CalibDb::load` and used for offline
+    /// processing of recordings made with the same sensor, e.g. ones which
+    /// do not contain a full status cycle.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::PointSource;
+    /// use velodyne::hdl64::CalibDb;
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let source = PcapSource::new("data/hdl64.pcap", false, false)?;
+    /// let point_source = PointSource::hdl64_init(source)?;
+    /// let path = std::env::temp_dir().join("velodyne_calib_snapshot.bin");
+    /// point_source.dump_calib_db(&path)?;
+    ///
+    /// let db = point_source.get_calib_db();
+    /// let loaded = CalibDb::load(&path)?;
+    /// assert_eq!(loaded.dist_lsb, db.dist_lsb);
+    /// for (a, b) in loaded.lasers.iter().zip(db.lasers.iter()) {
+    ///     assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    /// }
+    /// assert!(loaded.diff(&db, 0.).is_empty());
+    ///
+    /// let source = PcapSource::new("data/hdl64.pcap", false, false)?;
+    /// let mut point_source = PointSource::hdl64_init(source)?;
+    /// point_source.hdl64_set_calib_db(loaded);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn dump_calib_db<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.convertor.db.save(path)
+    }
+}
+
+impl<T: PacketSource> PointSource<T, hdl32::Hdl32Convertor, hdl32::StatusListener> {
+    /// Initialize HDL-32E point source
+    pub fn hdl32_init(packet_source: T) -> Self {
+        Self::from_parts(packet_source, Default::default(), Default::default())
+    }
+}
+
+impl<T: PacketSource> PointSource<T, model::AnyConvertor, model::AnyStatusListener> {
+    /// Initialize point source for the given sensor `model`
+    pub fn for_model(
+        mut packet_source: T, model: model::SensorModel,
+        config: model::SensorConfig,
+    ) -> io::Result<Self> {
+        let status_lst = model::AnyStatusListener::for_model(
+            &mut packet_source, model)?;
+        let convertor = model::convertor_for_model(config, &status_lst);
+        Ok(Self::from_parts(packet_source, status_lst, convertor))
+    }
+}
+
+impl<T: PacketSource> PointSource<T, vlp16::Vlp16Convertor, vlp16::StatusListener> {
+    /// Initialize VLP-16 point source
+    pub fn vlp16_init(packet_source: T) -> Self {
+        Self::from_parts(packet_source, Default::default(), Default::default())
+    }
+}
+
+
+/// Azimuth coverage of a turn computed from azimuths of its packets
+///
+/// All values are measured in hundredths of degree.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TurnCoverage {
+    /// Expected azimuth step between packets estimated as median step
+    pub step: u16,
+    /// Largest azimuth step between consecutive packets
+    pub largest_gap: u16,
+    /// Total azimuth span not covered by packets, i.e. sum of excesses over
+    /// `step` for steps larger than `1.5*step`
+    pub missing: u32,
+}
+
+impl TurnCoverage {
+    /// Compute coverage from azimuth steps between consecutive packets,
+    /// order of `gaps` is not preserved
+    fn from_gaps(gaps: &mut [u16]) -> Self {
+        if gaps.is_empty() { return Self::default(); }
+        let largest_gap = gaps.iter().cloned().max().unwrap_or(0);
+        let mid = gaps.len()/2;
+        let step = *gaps.select_nth_unstable(mid).1;
+        let missing = gaps.iter()
+            .filter(|&&g| 2*g as u32 > 3*step as u32)
+            .map(|&g| (g - step) as u32)
+            .sum();
+        Self { step, largest_gap, missing }
+    }
+}
+
+/// Iterator which returns points for each sensor rotation
+///
+/// If sensor reconfiguration is detected (see
+/// `PointSource::on_reconfiguration`), points of the current turn are
+/// discarded and the next returned turn starts at the following crossing of
+/// the split azimuth, so turns never mix points measured with different
+/// settings.
+///
+/// # Example
+/// ```
+/// use std::io;
+/// use std::net::SocketAddrV4;
+/// use velodyne::{TurnIterator, FullPoint, ReturnType};
+/// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+/// use velodyne::packet::{PacketSource, RawPacket};
+///
+/// /// Emits 150 packets with 10° step, sensor switches to the dual return
+/// /// mode in the middle of the second turn
+/// struct Turns { packet: RawPacket, n: u32 }
+///
+/// impl PacketSource for Turns {
+///     fn next_packet(&mut self)
+///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+///     {
+///         if self.n == 150 { return Ok(None); }
+///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+///         for (i, block) in blocks.enumerate() {
+///             let azimuth = ((1000*self.n + 50*i as u32) % 36000) as u16;
+///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+///         }
+///         let mode = if self.n < 50 { 0x37 } else { 0x39 };
+///         self.packet[1204..].copy_from_slice(&[mode, 0x21]);
+///         self.n += 1;
+///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+///         Ok(Some((addr, &self.packet)))
+///     }
+/// }
+///
+/// let source = Turns { packet: [0; 1206], n: 0 };
+/// let mut convertor = Hdl32Convertor::default();
+/// convertor.set_keep_no_return(true);
+/// let turns: TurnIterator<_, _, StatusListener, FullPoint> =
+///     TurnIterator::new(source, convertor).unwrap();
+/// let turns: Vec<_> = turns.map(|turn| turn.unwrap()).collect();
+/// // the second turn is discarded
+/// assert_eq!(turns.len(), 3);
+/// assert_eq!(turns[0].0.unwrap().return_mode, ReturnType::Strongest);
+/// for (status, points) in &turns[1..] {
+///     assert_eq!(status.unwrap().return_mode, ReturnType::Both);
+///     assert_eq!(points.len(), 36*12*32);
+/// }
+/// // the turn starts after the split azimuth crossing at packet 72
+/// assert_eq!(turns[1].1[0].azimuth, 1000);
+/// ```
+pub struct TurnIterator<T, C, S, P>
+    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    point_source: PointSource<T, C, S>,
+    cap: usize,
+    // azimuth steps between packets of the current turn
+    gaps: Vec<u16>,
+    coverage: TurnCoverage,
+    prev_azimuth: Azimuth,
+    split_azimuth: Azimuth,
+    skip_partial: bool,
+    // points of the turn interrupted by a transient `Ok(None)` from source
+    partial: Vec<P>,
+    resume: bool,
+    _p: PhantomData<P>,
+}
+
+impl<T, C, S, P> TurnIterator<T, C, S, P>
+    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    fn from_point_source(point_source: PointSource<T, C, S>) -> Self {
+        Self {
+            point_source, cap: 0, gaps: vec![],
+            coverage: Default::default(), prev_azimuth: Azimuth::default(),
+            split_azimuth: Azimuth::default(),
+            skip_partial: false, partial: vec![], resume: false,
+            _p: Default::default(),
+        }
+    }
+
+    /// Create new `TurnIterator`
+    pub fn new(packet_source: T, convertor: C) -> io::Result<Self> {
+        let point_source = PointSource::new(packet_source, convertor)?;
+        Ok(Self::from_point_source(point_source))
+    }
+
+    /// Set azimuth at which next turn will begin in `degrees*100`,
+    pub fn set_split_azimuth(&mut self, val: u16) {
+        self.split_azimuth = Azimuth::new(val);
+    }
+
+    /// Reserve space for at least `points_per_turn` points in each turn
+    /// returned by `Iterator::next`.
+    ///
+    /// By default capacity of the turn buffers is adjusted using sizes of the
+    /// previous turns, so the first turns can cause several reallocations.
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{TurnIterator, FullPoint};
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let mut turns: TurnIterator<_, _, _, FullPoint> =
+    ///     TurnIterator::hdl32_init(source);
+    /// turns.reserve(100_000);
+    /// let (_, points) = turns.next().unwrap()?;
+    /// assert!(points.capacity() >= 100_000);
+    /// # Ok(()) }
+    /// ```
+    pub fn reserve(&mut self, points_per_turn: usize) {
+        self.cap = max(self.cap, points_per_turn);
+    }
+
+    /// Get azimuth coverage of the last returned turn
+    ///
+    /// Gaps larger than the expected step between packets indicate dropped
+    /// packets. Step between the last packet of the previous turn and the
+    /// first packet of the returned turn is taken into account as well.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{TurnIterator, FullPoint};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits two turns of packets with 1° step, 20 packets of the second
+    /// /// turn are dropped
+    /// struct Turns { packet: RawPacket, n: u32 }
+    ///
+    /// impl PacketSource for Turns {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 400 { self.n += 20; }
+    ///         if self.n == 800 { return Ok(None); }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = ((100*self.n + 8*i as u32) % 36000) as u16;
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///         }
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let source = Turns { packet: [0; 1206], n: 0 };
+    /// let mut turns: TurnIterator<_, _, StatusListener, FullPoint> =
+    ///     TurnIterator::new(source, Hdl32Convertor::default()).unwrap();
+    /// turns.next().unwrap().unwrap();
+    /// assert_eq!(turns.coverage().missing, 0);
+    /// turns.next().unwrap().unwrap();
+    /// let coverage = turns.coverage();
+    /// assert_eq!(coverage.step, 100);
+    /// assert_eq!(coverage.largest_gap, 2100);
+    /// assert_eq!(coverage.missing, 2000);
+    /// ```
+    pub fn coverage(&self) -> TurnCoverage {
+        self.coverage
+    }
+
+    /// Discard points until the first crossing of the split azimuth.
+    ///
+    /// Useful if packet source starts in the middle of rotation, in this case
+    /// the next yielded turn will be guaranteed to be complete.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{TurnIterator, FullPoint};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits two turns of packets with 1° step starting at 90°
+    /// struct Turns { packet: RawPacket, n: u32 }
+    ///
+    /// impl PacketSource for Turns {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 90 + 720 { return Ok(None); }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = ((100*self.n + 8*i as u32) % 36000) as u16;
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///         }
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    /// }
+    ///
+    /// let turns = |skip| {
+    ///     let source = Turns { packet: [0; 1206], n: 90 };
+    ///     let mut convertor = Hdl32Convertor::default();
+    ///     convertor.set_keep_no_return(true);
+    ///     let mut turns: TurnIterator<_, _, StatusListener, FullPoint> =
+    ///         TurnIterator::new(source, convertor).unwrap();
+    ///     if skip { turns.skip_partial_first_turn(); }
+    ///     turns.next().unwrap().unwrap().1
+    /// };
+    ///
+    /// // by default the first turn starts at the first packet
+    /// let partial = turns(false);
+    /// assert_eq!(partial[0].azimuth, 9000);
+    /// // the first yielded turn covers the full rotation, it ends with the
+    /// // packet which crosses the split azimuth
+    /// let full = turns(true);
+    /// assert_eq!(full.len(), 360*12*32);
+    /// assert_eq!(full[0].azimuth, 100);
+    /// assert_eq!(full.last().unwrap().azimuth, 88);
+    /// ```
+    pub fn skip_partial_first_turn(&mut self) {
+        self.skip_partial = true;
+    }
+
+    /// Process next packet and return `Ok(true)` if its azimuth crosses split
+    /// azimuth, `Ok(false)` if it does not and `Err(None)` if source is
+    /// exhausted.
+    fn process_packet<F>(&mut self, f: F, first: bool)
+        -> Result<bool, Option<io::Error>>
+        where F: FnMut(P)
+    {
+        let azimuth = match self.point_source.process_points(f) {
+            Ok(Some((_, meta))) => Azimuth::new(meta.azimuth),
+            Ok(None) => return Err(None),
+            Err(err) => return Err(Some(err)),
+        };
+        let prev = self.prev_azimuth;
+        self.prev_azimuth = azimuth;
+        if first { return Ok(false); }
+        self.gaps.push((azimuth - prev).raw());
+        Ok(self.split_azimuth.is_crossed(prev, azimuth))
+    }
+
+    /// Clear `buf` and fill it with points of the next turn.
+    ///
+    /// Unlike `Iterator::next` this method allows to reuse allocated buffer
+    /// across turns. Returns sensor status at the end of the turn or `None` if
+    /// packet source did not return a packet.
+    ///
+    /// If source is not exhausted (see `PacketSource::is_exhausted`), e.g.
+    /// `UdpSource` has timed out, points of the incomplete turn are retained
+    /// and the next call continues accumulation of the same turn.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io;
+    /// use std::net::SocketAddrV4;
+    /// use velodyne::{TurnIterator, FullPoint};
+    /// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+    /// use velodyne::packet::{PacketSource, RawPacket};
+    ///
+    /// /// Emits a turn and a half of packets with 1° step, optionally times
+    /// /// out once in the middle of the first turn
+    /// struct Turns { packet: RawPacket, n: u32, timeout: bool }
+    ///
+    /// impl PacketSource for Turns {
+    ///     fn next_packet(&mut self)
+    ///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+    ///     {
+    ///         if self.n == 540 { return Ok(None); }
+    ///         if self.n == 100 && self.timeout {
+    ///             self.timeout = false;
+    ///             return Ok(None);
+    ///         }
+    ///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+    ///         for (i, block) in blocks.enumerate() {
+    ///             let azimuth = ((100*self.n + 8*i as u32) % 36000) as u16;
+    ///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+    ///         }
+    ///         self.n += 1;
+    ///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+    ///         Ok(Some((addr, &self.packet)))
+    ///     }
+    ///
+    ///     fn is_exhausted(&self) -> bool {
+    ///         self.n == 540
+    ///     }
+    /// }
+    ///
+    /// let turns = |timeout| {
+    ///     let source = Turns { packet: [0; 1206], n: 0, timeout };
+    ///     let mut convertor = Hdl32Convertor::default();
+    ///     convertor.set_keep_no_return(true);
+    ///     let turns: TurnIterator<_, _, StatusListener, FullPoint> =
+    ///         TurnIterator::new(source, convertor).unwrap();
+    ///     turns
+    /// };
+    ///
+    /// let mut reference = turns(false);
+    /// let expected = reference.next().unwrap().unwrap().1;
+    /// // incomplete second turn is discarded at the end of the source
+    /// assert!(reference.next().is_none());
+    ///
+    /// let mut interrupted = turns(true);
+    /// // timeout does not discard points accumulated before it
+    /// assert!(interrupted.next().is_none());
+    /// let points = interrupted.next().unwrap().unwrap().1;
+    /// assert_eq!(points.len(), expected.len());
+    /// assert!(interrupted.next().is_none());
+    /// ```
+    ///
+    /// Allocation of the buffer is reused across turns:
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{TurnIterator, FullPoint};
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let source = PcapSource::new("data/hdl32.pcap", false, false)?;
+    /// let mut turns: TurnIterator<_, _, _, FullPoint> =
+    ///     TurnIterator::hdl32_init(source);
+    /// turns.skip_partial_first_turn();
+    /// let mut buf = vec![];
+    /// turns.fill_turn(&mut buf)?.unwrap();
+    /// let n = buf.len();
+    /// buf.reserve(n);
+    /// let (ptr, cap) = (buf.as_ptr(), buf.capacity());
+    /// for _ in 0..2 {
+    ///     turns.fill_turn(&mut buf)?.unwrap();
+    ///     assert!(buf.len() > n/2);
+    ///     assert_eq!((buf.as_ptr(), buf.capacity()), (ptr, cap));
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn fill_turn(&mut self, buf: &mut Vec<P>)
+        -> io::Result<Option<S::Status>>
+    {
+        buf.clear();
+        if self.skip_partial {
+            match self.skip_partial_turn() {
+                Ok(()) => self.skip_partial = false,
+                Err(None) if !self.point_source.packet_source.is_exhausted() => {
+                    return Ok(None);
+                },
+                Err(err) => {
+                    self.skip_partial = false;
+                    return err.map_or(Ok(None), Err);
+                },
+            }
+        }
+        if self.resume {
+            buf.append(&mut self.partial);
+        } else {
+            self.gaps.clear();
+            self.point_source.convertor.start_turn();
+        }
+        self.resume = false;
+        loop {
+            let crossed = match self.process_packet(|p| buf.push(p), false) {
+                Ok(crossed) => crossed,
+                Err(None) if !self.point_source.packet_source.is_exhausted() => {
+                    self.partial.append(buf);
+                    self.resume = true;
+                    return Ok(None);
+                },
+                Err(err) => return err.map_or(Ok(None), Err),
+            };
+            if std::mem::replace(&mut self.point_source.reconfigured, false) {
+                // the turn contains points measured with the old settings
+                buf.clear();
+                self.gaps.clear();
+                if !crossed {
+                    self.skip_partial = true;
+                    return self.fill_turn(buf);
+                }
+            } else if crossed {
+                break;
+            }
+        }
+        self.coverage = TurnCoverage::from_gaps(&mut self.gaps);
+        self.cap = max(self.cap, (11*buf.len())/10);
+        Ok(Some(self.point_source.get_status().clone()))
+    }
+
+    /// Skip points up to and including packet at which split azimuth is
+    /// crossed for the first time
+    fn skip_partial_turn(&mut self) -> Result<(), Option<io::Error>> {
+        let mut first = true;
+        while !self.process_packet(|_| {}, first)? {
+            first = false;
+        }
+        // skipped points do not need to be discarded again
+        self.point_source.reconfigured = false;
+        Ok(())
+    }
+}
+
+impl<T, P> TurnIterator<T, hdl64::Hdl64Convertor, hdl64::StatusListener, P>
+    where T: PacketSource, P: From<FullPoint>
+{
+    /// Initialize `TurnIterator` for HDL-64
+    pub fn hdl64_init(packet_source: T) -> io::Result<Self> {
+        let point_source = PointSource::hdl64_init(packet_source)?;
+        Ok(Self::from_point_source(point_source))
+    }
+
+    /// Update HDL-64 calibration table
+    pub fn hdl64_set_calib_db(&mut self, calib_db: hdl64::CalibDb) {
+        self.point_source.hdl64_set_calib_db(calib_db);
+    }
+
+    /// Get number of intensities clamped during conversion of the last
+    /// returned turn
+    ///
+    /// # Example
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use velodyne::{TurnIterator, FullPoint, StatusListener};
+    /// use velodyne::hdl64;
+    /// use velodyne::packet::PcapSource;
+    ///
+    /// let mut source = PcapSource::new("data/hdl64.pcap", false, false)?;
+    /// let listener = hdl64::StatusListener::init(&mut source)?;
+    /// let mut db = listener.get_calib_db(0.2);
+    /// for laser in db.lasers.iter_mut() {
+    ///     // huge focal slope saturates all intensities
+    ///     laser.focal_dist = 0.;
+    ///     laser.focal_slope = 1e6;
+    /// }
+    ///
+    /// let source = PcapSource::new("data/hdl64.pcap", false, false)?;
+    /// let mut turns: TurnIterator<_, _, _, FullPoint> =
+    ///     TurnIterator::hdl64_init(source)?;
+    /// turns.hdl64_set_calib_db(db);
+    /// turns.skip_partial_first_turn();
+    /// for _ in 0..2 {
+    ///     let (_, points) = turns.next().unwrap()?;
+    ///     assert!(points.iter().all(|p| p.intensity == 255));
+    ///     // counters are reset at the start of each turn
+    ///     let stats = turns.clamp_stats();
+    ///     assert_eq!((stats.low, stats.high), (0, points.len() as u64));
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn clamp_stats(&self) -> hdl64::ClampStats {
+        self.point_source.convertor.clamp_stats()
+    }
+}
+
+impl<T, P> TurnIterator<T, hdl32::Hdl32Convertor, hdl32::StatusListener, P>
+    where T: PacketSource, P: From<FullPoint>
+{
+    /// Initialize `TurnIterator` for HDL-32E
+    pub fn hdl32_init(packet_source: T) -> Self {
+        let point_source = PointSource::hdl32_init(packet_source);
+        Self::from_point_source(point_source)
+    }
+}
+
+impl<T, P> TurnIterator<T, model::AnyConvertor, model::AnyStatusListener, P>
+    where T: PacketSource, P: From<FullPoint>
+{
+    /// Initialize `TurnIterator` for the given sensor `model`
+    pub fn for_model(
+        packet_source: T, model: model::SensorModel,
+        config: model::SensorConfig,
+    ) -> io::Result<Self> {
+        let point_source = PointSource::for_model(packet_source, model, config)?;
+        Ok(Self::from_point_source(point_source))
+    }
+}
+
+impl<T, P> TurnIterator<T, vlp16::Vlp16Convertor, vlp16::StatusListener, P>
+    where T: PacketSource, P: From<FullPoint>
+{
+    /// Initialize `TurnIterator` for VLP-16
+    pub fn vlp16_init(packet_source: T) -> Self {
+        let point_source = PointSource::vlp16_init(packet_source);
+        Self::from_point_source(point_source)
+    }
+}
+
+impl<T, C, S, P> Iterator for TurnIterator<T, C, S, P>
+   where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    type Item = io::Result<(S::Status, Vec<P>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = Vec::with_capacity(self.cap);
+        match self.fill_turn(&mut buf) {
+            Ok(Some(status)) => Some(Ok((status, buf))),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
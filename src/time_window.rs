@@ -0,0 +1,147 @@
+use std::io;
+use std::cmp::max;
+use std::time::Duration;
+
+use crate::{PointSource, FullPoint, Convertor, StatusListener};
+use crate::packet::PacketSource;
+
+/// Number of microseconds in an hour, packet timestamps wrap at this value
+const HOUR_US: u64 = 3_600_000_000;
+
+/// Iterator which returns points in fixed time windows
+///
+/// Unlike `TurnIterator` which splits points at the given azimuth, this
+/// iterator uses packet timestamps. Window starts at the first packet and
+/// contains all packets with timestamps less than `window` from it, the
+/// packet which ends the window starts the next one. Incomplete last window
+/// is discarded.
+///
+/// # Example
+/// ```
+/// use std::io;
+/// use std::net::SocketAddrV4;
+/// use std::time::Duration;
+/// use velodyne::{TimeWindowIterator, FullPoint};
+/// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+/// use velodyne::packet::{PacketSource, RawPacket};
+///
+/// /// Emits packets with one point each 1 ms starting just before top of
+/// /// the hour
+/// struct Synthetic { packet: RawPacket, n: u32 }
+///
+/// impl PacketSource for Synthetic {
+///     fn next_packet(&mut self)
+///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+///     {
+///         if self.n == 20 { return Ok(None); }
+///         let t = (3_599_990_000 + 1000*self.n) % 3_600_000_000;
+///         self.n += 1;
+///         for block in self.packet[..1200].chunks_exact_mut(100) {
+///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///         }
+///         self.packet[4..7].copy_from_slice(&[0xE8, 0x03, 0]);
+///         self.packet[1200..1204].copy_from_slice(&t.to_le_bytes());
+///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+///         Ok(Some((addr, &self.packet)))
+///     }
+/// }
+///
+/// let source = Synthetic { packet: [0; 1206], n: 0 };
+/// let window = Duration::from_millis(5);
+/// let convertor = Hdl32Convertor::default();
+/// let iter: TimeWindowIterator<_, _, StatusListener, FullPoint> =
+///     TimeWindowIterator::new(source, convertor, window).unwrap();
+/// let batches: Vec<_> = iter.map(|r| r.unwrap().1).collect();
+/// // 20 packets result in 4 windows, but the last one is incomplete
+/// assert_eq!(batches.len(), 3);
+/// for batch in batches.iter() {
+///     assert_eq!(batch.len(), 5);
+/// }
+/// // second window crosses the top of the hour
+/// let t: Vec<u32> = batches[1].iter().map(|p| p.timestamp).collect();
+/// assert_eq!(t, [3_599_995_000, 3_599_996_000, 3_599_997_000,
+///     3_599_998_000, 3_599_999_000]);
+/// assert_eq!(batches[2][0].timestamp, 0);
+/// ```
+pub struct TimeWindowIterator<T, C, S, P>
+    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    point_source: PointSource<T, C, S>,
+    cap: usize,
+    // window duration in microseconds
+    window: u64,
+    // timestamp of the first packet in the current window
+    start: Option<u32>,
+    // points of the packet which has started the current window
+    carry: Vec<P>,
+}
+
+impl<T, C, S, P> TimeWindowIterator<T, C, S, P>
+    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    /// Create new `TimeWindowIterator` with the given `window` duration
+    pub fn new(packet_source: T, convertor: C, window: Duration)
+        -> io::Result<Self>
+    {
+        let point_source = PointSource::new(packet_source, convertor)?;
+        Ok(Self::with_point_source(point_source, window))
+    }
+
+    /// Create new `TimeWindowIterator` from initialized `PointSource`, e.g.
+    /// created by `PointSource::hdl64_init`
+    pub fn with_point_source(
+        point_source: PointSource<T, C, S>, window: Duration,
+    ) -> Self {
+        let window = window.as_micros().min(HOUR_US as u128) as u64;
+        Self { point_source, cap: 0, window, start: None, carry: vec![] }
+    }
+
+    /// Reserve space for at least `points_per_window` points in each window
+    /// returned by `Iterator::next`.
+    pub fn reserve(&mut self, points_per_window: usize) {
+        self.cap = max(self.cap, points_per_window);
+    }
+
+    /// Clear `buf` and fill it with points of the next time window.
+    ///
+    /// Returns sensor status at the end of the window or `None` if packet
+    /// source is exhausted.
+    pub fn fill_window(&mut self, buf: &mut Vec<P>)
+        -> io::Result<Option<S::Status>>
+    {
+        buf.clear();
+        buf.append(&mut self.carry);
+        loop {
+            let n = buf.len();
+            let t = match self.point_source.process_points(|p| buf.push(p))? {
+                Some((_, meta)) => meta.timestamp,
+                None => return Ok(None),
+            };
+            let start = *self.start.get_or_insert(t);
+            // handle wrap at the top of the hour
+            let dt = (t as u64 + HOUR_US - start as u64) % HOUR_US;
+            if dt >= self.window {
+                self.carry = buf.split_off(n);
+                self.start = Some(t);
+                break;
+            }
+        }
+        self.cap = max(self.cap, (11*buf.len())/10);
+        Ok(Some(self.point_source.get_status().clone()))
+    }
+}
+
+impl<T, C, S, P> Iterator for TimeWindowIterator<T, C, S, P>
+   where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    type Item = io::Result<(S::Status, Vec<P>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = Vec::with_capacity(self.cap);
+        match self.fill_window(&mut buf) {
+            Ok(Some(status)) => Some(Ok((status, buf))),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
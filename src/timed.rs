@@ -0,0 +1,96 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+
+use crate::FullPoint;
+
+/// Number of microseconds in an hour, packet timestamps wrap at this value
+const HOUR_US: u32 = 3_600_000_000;
+
+/// Point with absolute UTC timestamp
+///
+/// Can be produced by `PointSource::process_timed_points` using `TimeBase`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimedPoint {
+    /// Point coordinates in meters
+    pub xyz: [f32; 3],
+    /// Calibrated reflectivity from 0 to 255
+    pub intensity: u8,
+    /// Laser ID
+    pub laser_id: u8,
+    /// Point measurement time
+    pub stamp: DateTime<Utc>,
+}
+
+/// Time base used for conversion of point timestamps, which are measured in
+/// microseconds from the top of the hour, to absolute UTC time
+///
+/// Top of the hour is advanced automatically when point timestamps wrap.
+///
+/// # Example
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use velodyne::{FullPoint, TimeBase};
+///
+/// let hour = Utc.with_ymd_and_hms(2019, 3, 14, 10, 0, 0).unwrap();
+/// let mut time_base = TimeBase::new(hour);
+/// let p = FullPoint { timestamp: 500_000, ..Default::default() };
+/// let timed = time_base.timed_point(&p);
+/// let expected = Utc.with_ymd_and_hms(2019, 3, 14, 10, 0, 0).unwrap()
+///     + chrono::Duration::milliseconds(500);
+/// assert_eq!(timed.stamp, expected);
+///
+/// // timestamp has wrapped at the top of the next hour
+/// time_base.stamp(3_599_990_000);
+/// let stamp = time_base.stamp(1_000);
+/// let expected = Utc.with_ymd_and_hms(2019, 3, 14, 11, 0, 0).unwrap()
+///     + chrono::Duration::milliseconds(1);
+/// assert_eq!(stamp, expected);
+/// assert_eq!(time_base.get_top_of_hour().to_string(),
+///     "2019-03-14 11:00:00 UTC");
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct TimeBase {
+    top_of_hour: DateTime<Utc>,
+    last_timestamp: Option<u32>,
+}
+
+impl TimeBase {
+    /// Create new time base, `dt` is truncated to the top of the hour
+    ///
+    /// Current sensor time (e.g. `hdl64::Status::dt`) can be used as `dt`
+    /// as long as it belongs to the same hour as the following points.
+    pub fn new(dt: DateTime<Utc>) -> Self {
+        let top_of_hour = dt.duration_trunc(Duration::hours(1))
+            .expect("hour truncation can not overflow");
+        Self { top_of_hour, last_timestamp: None }
+    }
+
+    /// Get current top of the hour
+    pub fn get_top_of_hour(&self) -> DateTime<Utc> {
+        self.top_of_hour
+    }
+
+    /// Convert point timestamp in microseconds from the top of the hour to
+    /// UTC time
+    ///
+    /// If `timestamp` is less than the previous one by more than half an
+    /// hour, it's considered to belong to the next hour.
+    pub fn stamp(&mut self, timestamp: u32) -> DateTime<Utc> {
+        if let Some(last) = self.last_timestamp {
+            if last > timestamp && last - timestamp > HOUR_US/2 {
+                self.top_of_hour += Duration::hours(1);
+            }
+        }
+        self.last_timestamp = Some(timestamp);
+        self.top_of_hour + Duration::microseconds(timestamp as i64)
+    }
+
+    /// Convert `point` to `TimedPoint`
+    pub fn timed_point(&mut self, point: &FullPoint) -> TimedPoint {
+        TimedPoint {
+            xyz: point.xyz,
+            intensity: point.intensity,
+            laser_id: point.laser_id,
+            stamp: self.stamp(point.timestamp),
+        }
+    }
+}
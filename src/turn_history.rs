@@ -0,0 +1,146 @@
+use std::io;
+use std::mem;
+use std::collections::VecDeque;
+
+use crate::{TurnIterator, FullPoint, Convertor, StatusListener};
+use crate::packet::PacketSource;
+
+/// Wrapper around `TurnIterator` which keeps the last `capacity` turns
+///
+/// Each acquired turn is pushed to the back of the history and the oldest
+/// turn is evicted if history is full. Buffers of evicted turns are reused
+/// for the next turns. With zero capacity turns are acquired, but not kept.
+///
+/// # Example
+/// ```
+/// use std::io;
+/// use std::net::SocketAddrV4;
+/// use velodyne::{TurnIterator, TurnHistory, FullPoint};
+/// use velodyne::hdl32::{Hdl32Convertor, StatusListener};
+/// use velodyne::packet::{PacketSource, RawPacket};
+///
+/// /// Emits three and a half turns of packets with 1° step, timestamp of
+/// /// the packet is equal to the index of its turn
+/// struct Turns { packet: RawPacket, n: u32 }
+///
+/// impl PacketSource for Turns {
+///     fn next_packet(&mut self)
+///         -> io::Result<Option<(SocketAddrV4, &RawPacket)>>
+///     {
+///         if self.n == 1260 { return Ok(None); }
+///         let blocks = self.packet[..1200].chunks_exact_mut(100);
+///         for (i, block) in blocks.enumerate() {
+///             let azimuth = ((100*self.n + 8*i as u32) % 36000) as u16;
+///             block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///             block[2..4].copy_from_slice(&azimuth.to_le_bytes());
+///         }
+///         let turn = self.n/360;
+///         self.packet[1200..1204].copy_from_slice(&turn.to_le_bytes());
+///         self.n += 1;
+///         let addr = SocketAddrV4::new([127, 0, 0, 1].into(), 2368);
+///         Ok(Some((addr, &self.packet)))
+///     }
+/// }
+///
+/// let make_history = |capacity| {
+///     let source = Turns { packet: [0; 1206], n: 0 };
+///     let mut convertor = Hdl32Convertor::default();
+///     convertor.set_keep_no_return(true);
+///     let turns: TurnIterator<_, _, StatusListener, FullPoint> =
+///         TurnIterator::new(source, convertor).unwrap();
+///     TurnHistory::new(turns, capacity)
+/// };
+///
+/// let mut history = make_history(2);
+/// for _ in 0..3 {
+///     assert!(history.advance().unwrap().is_some());
+/// }
+/// // incomplete last turn is discarded
+/// assert!(history.advance().unwrap().is_none());
+/// let t: Vec<u32> = history.turns().iter().map(|t| t[0].timestamp).collect();
+/// assert_eq!(t, [1, 2]);
+/// assert_eq!(history.latest().unwrap()[0].timestamp, 2);
+///
+/// let mut history = make_history(1);
+/// while history.advance().unwrap().is_some() {}
+/// assert_eq!(history.turns().len(), 1);
+/// assert_eq!(history.latest().unwrap()[0].timestamp, 2);
+///
+/// let mut history = make_history(0);
+/// while history.advance().unwrap().is_some() {}
+/// assert!(history.turns().is_empty());
+/// assert!(history.latest().is_none());
+/// ```
+pub struct TurnHistory<T, C, S, P>
+    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    turn_iter: TurnIterator<T, C, S, P>,
+    turns: VecDeque<Vec<P>>,
+    capacity: usize,
+    // buffer which is filled by the next turn
+    buf: Vec<P>,
+}
+
+impl<T, C, S, P> TurnHistory<T, C, S, P>
+    where T: PacketSource, C: Convertor, S: StatusListener, P: From<FullPoint>
+{
+    /// Create new `TurnHistory` which keeps up to `capacity` last turns
+    pub fn new(turn_iter: TurnIterator<T, C, S, P>, capacity: usize) -> Self {
+        Self {
+            turn_iter, turns: VecDeque::with_capacity(capacity), capacity,
+            buf: vec![],
+        }
+    }
+
+    /// Acquire next turn and push it into the history.
+    ///
+    /// Returns sensor status at the end of the turn or `None` if packet
+    /// source did not return a packet, see `TurnIterator::fill_turn`. In the
+    /// latter case history is not modified.
+    pub fn advance(&mut self) -> io::Result<Option<S::Status>> {
+        let status = match self.turn_iter.fill_turn(&mut self.buf)? {
+            Some(status) => status,
+            None => return Ok(None),
+        };
+        if self.capacity != 0 {
+            let spare = if self.turns.len() == self.capacity {
+                self.turns.pop_front()
+            } else {
+                None
+            };
+            let turn = mem::replace(&mut self.buf, spare.unwrap_or_default());
+            self.turns.push_back(turn);
+        }
+        Ok(Some(status))
+    }
+
+    /// Get kept turns ordered from the oldest to the latest one
+    pub fn turns(&self) -> &VecDeque<Vec<P>> {
+        &self.turns
+    }
+
+    /// Get the latest kept turn
+    pub fn latest(&self) -> Option<&[P]> {
+        self.turns.back().map(|t| t.as_slice())
+    }
+
+    /// Get maximum number of kept turns
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Remove all turns from the history
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+
+    /// Get reference to the inner `TurnIterator`
+    pub fn get_turn_iter(&self) -> &TurnIterator<T, C, S, P> {
+        &self.turn_iter
+    }
+
+    /// Get mutable reference to the inner `TurnIterator`
+    pub fn get_turn_iter_mut(&mut self) -> &mut TurnIterator<T, C, S, P> {
+        &mut self.turn_iter
+    }
+}
@@ -0,0 +1,357 @@
+//! VLP-16 sensor types
+//!
+//! Each VLP-16 data block contains two firing sequences of 16 lasers, but
+//! only azimuth of the first sequence is reported. Azimuths of the individual
+//! firings are interpolated using azimuth of the next block containing the
+//! same return, i.e. the next block in the single return mode and the block
+//! after next in the dual return mode (in which both blocks of a pair share
+//! the same azimuth).
+use core::array;
+use core::ops::ControlFlow;
+use super::{FullPoint, ConversionError, Convertor, ReturnType, AzimuthScale};
+use crate::azimuth::FULL_TURN;
+use crate::math::{sin_cos, round, rem_euclid};
+use crate::packet::{
+    RawPacket, RawPoint, PacketMeta, get_status, packet_meta, block_header,
+    raw_point, packet_azimuths, dual_return_partners, return_info,
+    is_duplicate_return, BLOCKS, BlockHeaders,
+};
+
+pub use crate::hdl32::FactoryBytes;
+#[cfg(feature = "std")]
+pub use crate::hdl32::StatusListener;
+
+/// Model byte reported by VLP-16 in the factory bytes
+pub const MODEL_ID: u8 = 0x22;
+
+/// Block headers used by VLP-16
+pub const BLOCK_HEADERS: BlockHeaders = BlockHeaders(&[([0xFF, 0xEE], 0)]);
+
+const VLP_16_TABLE: [f32; 16] = [
+    -15., 1., -13., 3., -11., 5., -9., 7.,
+    -7., 9., -5., 11., -3., 13., -1., 15.,
+];
+
+/// Time between firings of consecutive lasers in microseconds
+const FIRING_DT: f32 = 2.304;
+/// Duration of one firing sequence (including recharge) in microseconds
+const SEQUENCE_DT: f32 = 55.296;
+/// Number of microseconds in an hour, timestamps wrap at this value
+const HOUR_US: u32 = 3_600_000_000;
+
+/// Default VLP-16 convertor from `RawPoint` to `FullPoint`
+///
+/// Return mode is detected using factory bytes of each packet, if they are
+/// unknown, packet is considered to be in the dual return mode if its first
+/// two blocks have equal azimuths.
+///
+/// # Example
+/// ```
+/// use velodyne::{Convertor, FullPoint};
+/// use velodyne::vlp16::Vlp16Convertor;
+///
+/// // packet with all channels measuring 2 m
+/// fn packet(azimuths: &[u16], return_mode: u8) -> [u8; 1206] {
+///     let mut data = [0u8; 1206];
+///     let blocks = data[..1200].chunks_exact_mut(100);
+///     for (block, a) in blocks.zip(azimuths) {
+///         block[..2].copy_from_slice(&[0xFF, 0xEE]);
+///         block[2..4].copy_from_slice(&a.to_le_bytes());
+///         for point in block[4..].chunks_exact_mut(3) {
+///             point.copy_from_slice(&[0xE8, 0x03, 0]);
+///         }
+///     }
+///     data[1204..].copy_from_slice(&[return_mode, 0x22]);
+///     data
+/// }
+///
+/// fn convert(data: &[u8; 1206]) -> Vec<FullPoint> {
+///     let mut points = vec![];
+///     Vlp16Convertor::default().convert(data, |p| points.push(p)).unwrap();
+///     points
+/// }
+///
+/// // point azimuth in hundredths of degree
+/// fn azimuth(p: &FullPoint) -> u16 {
+///     let a = p.xyz[0].atan2(p.xyz[1]).to_degrees();
+///     (100.*a).round().rem_euclid(36000.) as u16
+/// }
+///
+/// // single return mode, each block advances azimuth by 0.4°
+/// let azimuths: Vec<u16> = (0..12)
+///     .map(|i| (35800 + 40*i) % 36000)
+///     .collect();
+/// let points = convert(&packet(&azimuths, 0x37));
+/// assert_eq!(points.len(), 384);
+/// // first and second firing sequences of the first block
+/// assert_eq!(azimuth(&points[0]), 35800);
+/// assert_eq!(azimuth(&points[16]), 35820);
+/// // firing sequences of the last block use gap to the previous block
+/// assert_eq!(azimuth(&points[352]), 240);
+/// assert_eq!(azimuth(&points[368]), 260);
+///
+/// // dual return mode, blocks in a pair share azimuth
+/// let azimuths: Vec<u16> = (0..12)
+///     .map(|i| (35800 + 40*(i/2)) % 36000)
+///     .collect();
+/// let mut data = packet(&azimuths, 0x39);
+/// // the first block of a pair contains the last return, make it differ
+/// // from the strongest one in the first pair
+/// for point in data[4..100].chunks_exact_mut(3) {
+///     point.copy_from_slice(&[0xD0, 0x07, 0]);
+/// }
+/// let points = convert(&data);
+/// // all second returns except the ones in the first pair are duplicates
+/// assert_eq!(points.len(), 6*32 + 32);
+/// for (i, block) in points[..64].chunks_exact(32).enumerate() {
+///     assert_eq!(azimuth(&block[0]), 35800);
+///     assert_eq!(azimuth(&block[16]), 35820);
+///     // returns are numbered by distance, the last return is at 4 m
+///     assert_eq!(block[0].return_number, 2 - i as u8);
+///     assert_eq!(block[0].number_of_returns, 2);
+///     let range = if i == 0 { 4. } else { 2. };
+///     assert!(block.iter().all(|p| (p.range() - range).abs() < 1e-5));
+/// }
+/// assert_eq!(azimuth(&points[64]), 35840);
+/// assert_eq!(points[64].number_of_returns, 1);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Vlp16Convertor {
+    vert_sin: [f32; 16],
+    vert_cos: [f32; 16],
+    keep_no_return: bool,
+    firing_order: bool,
+    azimuth_scale: AzimuthScale,
+}
+
+impl Default for Vlp16Convertor {
+    fn default() -> Self {
+        let mut vert_sin = [0f32; 16];
+        let mut vert_cos = [0f32; 16];
+        for (i, a) in VLP_16_TABLE.iter().enumerate() {
+            let (sin, cos) = sin_cos(a.to_radians());
+            vert_sin[i] = sin;
+            vert_cos[i] = cos;
+        }
+        Self {
+            vert_sin, vert_cos, keep_no_return: false, firing_order: false,
+            azimuth_scale: AzimuthScale::HUNDREDTHS,
+        }
+    }
+}
+
+impl Vlp16Convertor {
+    /// Set whether points without return (i.e. with zero distance) should
+    /// be kept, by default such points are skipped.
+    ///
+    /// Kept points have NaN coordinates and zero `return_number` and
+    /// `number_of_returns`.
+    pub fn set_keep_no_return(&mut self, keep: bool) {
+        self.keep_no_return = keep;
+    }
+
+    /// Get whether points without return are kept
+    pub fn get_keep_no_return(&self) -> bool {
+        self.keep_no_return
+    }
+
+    /// Set whether points should be grouped by firing sequence, disabled by
+    /// default
+    ///
+    /// If enabled, all returns of a firing sequence are emitted before the
+    /// next sequence and point timestamps are set to the start time of their
+    /// firing sequence instead of the packet timestamp. Without it, in the
+    /// dual return mode all points of a block are emitted before its
+    /// paired block, i.e. firing sequences are interleaved.
+    ///
+    /// # Example
+    /// ```
+    /// use velodyne::{Convertor, FullPoint};
+    /// use velodyne::vlp16::Vlp16Convertor;
+    ///
+    /// // dual return packet with the last returns at 2.512 m in the first
+    /// // blocks of pairs and the strongest returns at 2 m
+    /// let mut data = [0u8; 1206];
+    /// for (i, block) in data[..1200].chunks_exact_mut(100).enumerate() {
+    ///     block[..2].copy_from_slice(&[0xFF, 0xEE]);
+    ///     block[2..4].copy_from_slice(&(40*(i as u16/2)).to_le_bytes());
+    ///     for point in block[4..].chunks_exact_mut(3) {
+    ///         point.copy_from_slice(&[0xE8, 0x04 - (i % 2) as u8, 0]);
+    ///     }
+    /// }
+    /// data[1200..1204].copy_from_slice(&1000u32.to_le_bytes());
+    /// data[1204..].copy_from_slice(&[0x39, 0x22]);
+    ///
+    /// let mut convertor = Vlp16Convertor::default();
+    /// convertor.set_firing_order(true);
+    /// let mut points = vec![];
+    /// convertor.convert(&data, |p: FullPoint| points.push(p)).unwrap();
+    /// assert_eq!(points.len(), 384);
+    /// // 12 firings with 16 lasers and 2 returns each
+    /// for (i, firing) in points.chunks_exact(32).enumerate() {
+    ///     let t = 1000 + (55.296*i as f32).round() as u32;
+    ///     assert!(firing.iter().all(|p| p.timestamp == t));
+    ///     for (j, p) in firing.iter().enumerate() {
+    ///         assert_eq!(p.laser_id as usize, j % 16);
+    ///         assert_eq!(p.return_number as usize, 2 - j/16);
+    ///         let range = if p.return_number == 2 { 2.512 } else { 2. };
+    ///         assert!((p.range() - range).abs() < 1e-5);
+    ///     }
+    /// }
+    /// ```
+    pub fn set_firing_order(&mut self, enabled: bool) {
+        self.firing_order = enabled;
+    }
+
+    /// Get whether points are grouped by firing sequence
+    pub fn get_firing_order(&self) -> bool {
+        self.firing_order
+    }
+
+    /// Set units of azimuths reported by the sensor, by default hundredths
+    /// of degree are used, see `Hdl32Convertor::set_azimuth_scale`
+    ///
+    /// Interpolation of firing azimuths is performed after conversion of
+    /// block azimuths to hundredths of degree.
+    pub fn set_azimuth_scale(&mut self, scale: AzimuthScale) {
+        self.azimuth_scale = scale;
+    }
+
+    /// Get units of azimuths reported by the sensor
+    pub fn get_azimuth_scale(&self) -> AzimuthScale {
+        self.azimuth_scale
+    }
+}
+
+/// Azimuth difference between the `block` and the next block containing the
+/// same return, for the last blocks difference with the previous blocks is
+/// used instead.
+fn azimuth_gap(azimuths: &[f32; BLOCKS], block: usize, dual: bool) -> f32 {
+    let step = if dual { 2 } else { 1 };
+    let (a, b) = if block + step < BLOCKS {
+        (block, block + step)
+    } else {
+        (block - step, block)
+    };
+    rem_euclid(azimuths[b] - azimuths[a], FULL_TURN as f32)
+}
+
+/// Compute azimuth in hundredths of degree for the laser `channel` (0..32)
+/// in the block with the given `azimuth` and `gap` to the next firing
+fn channel_azimuth(azimuth: f32, gap: f32, channel: u8) -> f32 {
+    let sequence = (channel / 16) as f32;
+    let laser = (channel % 16) as f32;
+    let dt = sequence*SEQUENCE_DT + laser*FIRING_DT;
+    (azimuth + gap*dt/(2.*SEQUENCE_DT)) % FULL_TURN as f32
+}
+
+impl Convertor for Vlp16Convertor {
+    fn laser_count(&self) -> usize {
+        VLP_16_TABLE.len()
+    }
+
+    fn try_convert_with_raw<F>(&self, raw_packet: &RawPacket, mut f: F)
+        -> Result<(PacketMeta, usize), ConversionError>
+        where F: FnMut(FullPoint, RawPoint) -> ControlFlow<()>
+    {
+        let mut meta = packet_meta(raw_packet);
+        let scale = self.azimuth_scale;
+        meta.azimuth = scale.to_raw_hundredths(meta.azimuth)
+            .ok_or(ConversionError::InvalidAzimuth)?;
+        let azimuths = packet_azimuths(raw_packet);
+        // invalid azimuths are reported when their block is reached
+        let hundredths: [f32; BLOCKS] = array::from_fn(|i| {
+            scale.to_hundredths(azimuths[i]).unwrap_or(f32::NAN)
+        });
+        let dual = match FactoryBytes::parse(get_status(raw_packet)) {
+            Some(f) => f.return_mode == ReturnType::Both,
+            None => azimuths[0] == azimuths[1],
+        };
+        let partners = dual_return_partners(raw_packet);
+        let mut n = 0;
+
+        // each block contains two firing sequences
+        for i in 0..2*BLOCKS {
+            let (block, sequence) = if dual && self.firing_order {
+                // the same sequence of both blocks in a pair is emitted
+                // consecutively
+                (2*(i/4) + i % 2, (i/2) % 2)
+            } else {
+                (i/2, i % 2)
+            };
+            // all lasers are in the same bank
+            if BLOCK_HEADERS.laser_offset(block_header(raw_packet, block))
+                .is_none()
+            {
+                return Err(ConversionError::InvalidHeader);
+            }
+            let azimuth = hundredths[block];
+            if azimuth.is_nan() { return Err(ConversionError::InvalidAzimuth); }
+            let gap = azimuth_gap(&hundredths, block, dual);
+            // in the dual return mode blocks are paired, with the first
+            // block in the pair containing the last return
+            let partner = if dual { partners[block] } else { None };
+            let timestamp = if self.firing_order {
+                let firing = if dual { block/2 } else { block };
+                let dt = (2*firing + sequence) as f32*SEQUENCE_DT;
+                ((meta.timestamp as u64 + round(dt) as u64)
+                    % HOUR_US as u64) as u32
+            } else {
+                meta.timestamp
+            };
+
+            let channels = 16*sequence as u8..16*(sequence as u8 + 1);
+            for channel in channels {
+                let raw_point = raw_point(raw_packet, block, channel);
+                let no_return = raw_point.distance == 0;
+                if no_return && !self.keep_no_return { continue; }
+
+                // skip second return if it's equal to the first one
+                if is_duplicate_return(raw_packet, block, partner, raw_point) {
+                    continue;
+                }
+
+                let laser_id = raw_point.laser % 16;
+                let l = laser_id as usize;
+                let a = channel_azimuth(azimuth, gap, raw_point.laser);
+                let xyz = if no_return {
+                    [f32::NAN; 3]
+                } else {
+                    let azim_sin_cos = sin_cos((a/100.).to_radians());
+                    let distance = (raw_point.distance as f32)/500.;
+                    let vert_sin_cos = (self.vert_sin[l], self.vert_cos[l]);
+                    compute_xyz(distance, azim_sin_cos, vert_sin_cos)
+                };
+                let intensity = raw_point.intensity;
+
+                let (return_number, number_of_returns) = if no_return {
+                    (0, 0)
+                } else {
+                    return_info(raw_packet, block, partner, raw_point)
+                };
+
+                //  TODO: add timestamp deltas
+                let point = FullPoint {
+                    xyz, intensity, intensity16: intensity as u16,
+                    laser_id, timestamp,
+                    return_number, number_of_returns,
+                    azimuth: (round(a) as u16) % FULL_TURN, sensor_id: 0,
+                };
+                n += 1;
+                if f(point, raw_point).is_break() { return Ok((meta, n)); }
+            }
+        }
+        Ok((meta, n))
+    }
+}
+
+fn compute_xyz(
+    dist: f32, (a_sin, a_cos): (f32, f32), (w_sin, w_cos): (f32, f32),
+) -> [f32; 3] {
+    let t = dist*w_cos;
+    [
+        t*a_sin,
+        t*a_cos,
+        dist*w_sin,
+    ]
+}